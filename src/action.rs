@@ -0,0 +1,301 @@
+//! Typed entries of the `actions` array of a build
+//!
+//! Actions are contributed by Jenkins core and plugins, the known ones are
+//! parsed by `_class` and everything else is kept as raw json.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{BuildCause, BuildTiming};
+
+const PARAMETERS_ACTION: &str = "hudson.model.ParametersAction";
+const CAUSE_ACTION: &str = "hudson.model.CauseAction";
+const TEST_RESULT_ACTION: &str = "hudson.tasks.junit.TestResultAction";
+const SUREFIRE_REPORT: &str = "hudson.maven.reporters.SurefireAggregatedReport";
+const TIME_IN_QUEUE_ACTION: &str = "jenkins.metrics.impl.TimeInQueueAction";
+const GIT_BUILD_DATA: &str = "hudson.plugins.git.util.BuildData";
+
+/// Entry of `actions`, see [`BuildRes::action`](crate::BuildRes::action)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Parameters(ParametersAction),
+    Cause(CauseAction),
+    /// junit or maven surefire test report
+    TestResult(TestResultAction),
+    /// recorded by the Metrics plugin
+    TimeInQueue(BuildTiming),
+    Git(GitBuildData),
+    /// action without a typed model, including `{}` placeholders
+    Unknown(Value),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParametersAction {
+    pub parameters: Vec<ParameterValue>,
+}
+
+/// Parameter value a build ran with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterValue {
+    /// e.g. `hudson.model.StringParameterValue`
+    #[serde(rename = "_class")]
+    pub class: Option<String>,
+    pub name: String,
+    /// missing for password and file parameters
+    #[serde(default)]
+    pub value: Value,
+    /// job of a run parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_name: Option<String>,
+    /// build number of a run parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+}
+
+/// Value of a [`ParameterValue`] by parameter type
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedParameterValue {
+    /// string, text, choice and other parameters with a string value
+    String(String),
+    Bool(bool),
+    /// value is never exposed by Jenkins
+    Password,
+    /// credentials id
+    Credentials(String),
+    Run {
+        job: String,
+        number: i32,
+    },
+    /// uploaded file name, if exposed
+    File(Option<String>),
+    Other(Value),
+}
+
+impl ParameterValue {
+    pub fn typed(&self) -> TypedParameterValue {
+        use TypedParameterValue as T;
+
+        let string = || self.value.as_str().map(str::to_owned);
+        match self.class.as_deref().unwrap_or_default() {
+            "hudson.model.PasswordParameterValue" => T::Password,
+            "hudson.model.BooleanParameterValue" => match self.value.as_bool() {
+                Some(b) => T::Bool(b),
+                None => T::Other(self.value.clone()),
+            },
+            "hudson.model.RunParameterValue" => {
+                let number = self.number.as_deref().and_then(|n| n.parse().ok());
+                match (&self.job_name, number) {
+                    (Some(job), Some(number)) => T::Run {
+                        job: job.clone(),
+                        number,
+                    },
+                    _ => T::Other(self.value.clone()),
+                }
+            }
+            "com.cloudbees.plugins.credentials.CredentialsParameterValue" => {
+                string().map_or_else(|| T::Other(self.value.clone()), T::Credentials)
+            }
+            "hudson.model.FileParameterValue" => T::File(string()),
+            _ => string().map_or_else(|| T::Other(self.value.clone()), T::String),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CauseAction {
+    pub causes: Vec<BuildCause>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResultAction {
+    pub fail_count: u32,
+    pub skip_count: u32,
+    pub total_count: u32,
+    /// path of the report below the build, usually `testReport`
+    pub url_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GitBuildData {
+    pub last_built_revision: Option<GitRevision>,
+    #[serde(default)]
+    pub remote_urls: Vec<String>,
+    pub scm_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GitRevision {
+    #[serde(rename = "SHA1")]
+    pub sha1: String,
+    #[serde(default)]
+    pub branch: Vec<GitBranch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GitBranch {
+    #[serde(rename = "SHA1")]
+    pub sha1: String,
+    pub name: String,
+}
+
+impl Action {
+    pub fn from_json(raw: Value) -> Action {
+        use serde_json::from_value;
+
+        let parsed = match raw["_class"].as_str().unwrap_or_default() {
+            PARAMETERS_ACTION => from_value(raw.clone()).map(Action::Parameters),
+            CAUSE_ACTION => from_value(raw.clone()).map(Action::Cause),
+            TEST_RESULT_ACTION | SUREFIRE_REPORT => from_value(raw.clone()).map(Action::TestResult),
+            TIME_IN_QUEUE_ACTION => from_value(raw.clone()).map(Action::TimeInQueue),
+            GIT_BUILD_DATA => from_value(raw.clone()).map(Action::Git),
+            _ => return Action::Unknown(raw),
+        };
+        parsed.unwrap_or(Action::Unknown(raw))
+    }
+
+    /// `_class` of the action, `None` for placeholders
+    pub fn class(&self) -> Option<&str> {
+        match self {
+            Action::Parameters(_) => Some(PARAMETERS_ACTION),
+            Action::Cause(_) => Some(CAUSE_ACTION),
+            Action::TestResult(_) => Some(TEST_RESULT_ACTION),
+            Action::TimeInQueue(_) => Some(TIME_IN_QUEUE_ACTION),
+            Action::Git(_) => Some(GIT_BUILD_DATA),
+            Action::Unknown(raw) => raw["_class"].as_str(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Value::deserialize(d).map(Action::from_json)
+    }
+}
+
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match self {
+            Action::Parameters(a) => serde_json::to_value(a),
+            Action::Cause(a) => serde_json::to_value(a),
+            Action::TestResult(a) => serde_json::to_value(a),
+            Action::TimeInQueue(a) => serde_json::to_value(a),
+            Action::Git(a) => serde_json::to_value(a),
+            Action::Unknown(raw) => return raw.serialize(serializer),
+        };
+        let mut value = value.map_err(serde::ser::Error::custom)?;
+        if let (Value::Object(map), Some(class)) = (&mut value, self.class()) {
+            map.insert("_class".to_owned(), class.into());
+        }
+        value.serialize(serializer)
+    }
+}
+
+/// Typed action looked up by [`BuildRes::action`](crate::BuildRes::action)
+pub trait FromAction {
+    fn from_action(action: &Action) -> Option<&Self>;
+}
+
+impl FromAction for ParametersAction {
+    fn from_action(action: &Action) -> Option<&Self> {
+        match action {
+            Action::Parameters(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl FromAction for CauseAction {
+    fn from_action(action: &Action) -> Option<&Self> {
+        match action {
+            Action::Cause(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl FromAction for TestResultAction {
+    fn from_action(action: &Action) -> Option<&Self> {
+        match action {
+            Action::TestResult(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl FromAction for BuildTiming {
+    fn from_action(action: &Action) -> Option<&Self> {
+        match action {
+            Action::TimeInQueue(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl FromAction for GitBuildData {
+    fn from_action(action: &Action) -> Option<&Self> {
+        match action {
+            Action::Git(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_actions() {
+        let actions: Vec<Action> = serde_json::from_str(
+            r#"[{"_class":"hudson.model.ParametersAction","parameters":[
+                    {"_class":"hudson.model.StringParameterValue","name":"VERSION","value":"1.4"}]},
+                {},
+                {"_class":"hudson.tasks.junit.TestResultAction","failCount":1,"skipCount":0,
+                 "totalCount":12,"urlName":"testReport"},
+                {"_class":"com.example.CustomAction","flag":true}]"#,
+        )
+        .unwrap();
+        assert!(matches!(&actions[0], Action::Parameters(p) if p.parameters[0].value == "1.4"));
+        assert_eq!(actions[1], Action::Unknown(serde_json::json!({})));
+        assert_eq!(
+            TestResultAction::from_action(&actions[2]).map(|a| a.total_count),
+            Some(12)
+        );
+        assert_eq!(actions[3].class(), Some("com.example.CustomAction"));
+
+        let json = serde_json::to_string(&actions).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Action>>(&json).unwrap(), actions);
+    }
+
+    #[test]
+    fn typed_parameter_values() {
+        let action: ParametersAction = serde_json::from_str(
+            r#"{"parameters":[
+                {"_class":"hudson.model.StringParameterValue","name":"VERSION","value":"1.4"},
+                {"_class":"hudson.model.BooleanParameterValue","name":"DRY_RUN","value":true},
+                {"_class":"hudson.model.PasswordParameterValue","name":"TOKEN"},
+                {"_class":"hudson.model.RunParameterValue","name":"UP","jobName":"app","number":"12"}]}"#,
+        )
+        .unwrap();
+        let typed: Vec<_> = action
+            .parameters
+            .iter()
+            .map(ParameterValue::typed)
+            .collect();
+        assert_eq!(
+            typed,
+            vec![
+                TypedParameterValue::String("1.4".to_owned()),
+                TypedParameterValue::Bool(true),
+                TypedParameterValue::Password,
+                TypedParameterValue::Run {
+                    job: "app".to_owned(),
+                    number: 12
+                },
+            ]
+        );
+    }
+}
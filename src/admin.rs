@@ -0,0 +1,371 @@
+//! Controller administration: plugins, restarts, backups, audit log and roles
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "job-config")]
+use crate::job_config;
+use crate::{Error, Jenkins, Multipart};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BusyExecutorsRes {
+    busy_executors: i32,
+}
+
+/// Step of [`Jenkins::drain_and_restart`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainProgress {
+    QuietingDown,
+    Draining { busy_executors: i32 },
+    Restarting,
+    WaitingOnline,
+    Online,
+}
+
+/// Options of [`Jenkins::drain_and_restart`]
+pub struct DrainRestartOptions {
+    /// max time to wait for running builds
+    pub drain_timeout: Duration,
+    /// max time to wait for the controller to come back
+    pub restart_timeout: Duration,
+    pub poll_interval: Duration,
+    pub on_progress: Option<Box<dyn Fn(DrainProgress) + Send + Sync>>,
+}
+
+impl Default for DrainRestartOptions {
+    fn default() -> Self {
+        DrainRestartOptions {
+            drain_timeout: Duration::from_secs(3600),
+            restart_timeout: Duration::from_secs(600),
+            poll_interval: Duration::from_secs(10),
+            on_progress: None,
+        }
+    }
+}
+
+/// Single quoted groovy literal, which does not interpolate `$`
+pub(crate) fn groovy_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Prints entries in the `add(String)` format of the installed matrix-auth version
+#[cfg(feature = "job-config")]
+const GLOBAL_PERMISSIONS_SCRIPT: &str = r#"
+def s = jenkins.model.Jenkins.get().authorizationStrategy
+if (!(s instanceof hudson.security.GlobalMatrixAuthorizationStrategy)) { throw new IllegalStateException('matrix authorization not enabled') }
+if (s.metaClass.respondsTo(s, 'getGrantedPermissionEntries')) {
+    s.grantedPermissionEntries.each { p, entries -> entries.each { println "${it.type}:${p.id}:${it.sid}" } }
+} else {
+    s.grantedPermissions.each { p, sids -> sids.each { println "${p.id}:${it}" } }
+}
+"#;
+
+/// See [`Jenkins::get_update_center`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCenterRes {
+    #[serde(default)]
+    pub sites: Vec<UpdateSite>,
+    #[serde(default)]
+    pub jobs: Vec<UpdateCenterJob>,
+    #[serde(default)]
+    pub restart_required_for_completion: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSite {
+    pub id: String,
+    pub url: String,
+    pub data_timestamp: Option<i64>,
+    #[serde(default)]
+    pub updates: Vec<AvailablePlugin>,
+    #[serde(default)]
+    pub available: Vec<AvailablePlugin>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailablePlugin {
+    pub name: String,
+    pub version: String,
+    pub title: Option<String>,
+    pub required_core: Option<String>,
+}
+
+/// Installation, update check or restart job of the update center
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCenterJob {
+    pub id: i64,
+    /// e.g. `InstallationJob`, `ConnectionCheckJob`, `RestartJenkinsJob`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// plugin name of installation jobs
+    pub name: Option<String>,
+    pub error_message: Option<String>,
+    pub status: Option<UpdateCenterJobStatus>,
+}
+
+impl UpdateCenterJob {
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.status.as_ref().map(|s| s.kind.as_str()),
+            Some("Pending" | "Installing")
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UpdateCenterJobStatus {
+    /// e.g. `Pending`, `Installing`, `Success`, `SuccessButRequiresRestart`, `Failure`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub success: Option<bool>,
+    /// download progress of `Installing`
+    pub percentage: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RestartRequiredRes {
+    restart_required_for_completion: bool,
+}
+
+impl Jenkins {
+    /// Get global matrix authorization entries
+    #[cfg(feature = "job-config")]
+    pub async fn get_global_permissions(&self) -> Result<Vec<job_config::PermissionEntry>> {
+        let out = self.run_script(GLOBAL_PERMISSIONS_SCRIPT).await?;
+        out.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.trim().parse())
+            .collect()
+    }
+
+    /// Add global matrix authorization entries
+    #[cfg(feature = "job-config")]
+    pub async fn grant_global(&self, entries: &[job_config::PermissionEntry]) -> Result<()> {
+        let entries: Vec<String> = entries
+            .iter()
+            .map(|e| groovy_string(&e.to_string()))
+            .collect();
+        let script = format!(
+            r#"
+def j = jenkins.model.Jenkins.get()
+def s = j.authorizationStrategy
+if (!(s instanceof hudson.security.GlobalMatrixAuthorizationStrategy)) {{ throw new IllegalStateException('matrix authorization not enabled') }}
+[{}].each {{ s.add(it) }}
+j.save()
+"#,
+            entries.join(", ")
+        );
+        self.run_script(&script).await?;
+        Ok(())
+    }
+
+    /// Install a plugin by uploading its `.hpi`/`.jpi` file
+    ///
+    /// ## Arguments
+    ///
+    /// * `file_name` - e.g. `git.hpi`
+    /// * `content` - plugin file content
+    ///
+    pub async fn upload_plugin(&self, file_name: &str, content: &[u8]) -> Result<()> {
+        let url = format!("{}/pluginManager/uploadPlugin", self.url);
+        let mut form = Multipart::new();
+        form.file("name", file_name, content);
+        let res = self.send(form.apply(self.post(&url))).await?;
+        // jenkins redirects to the update center after upload
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("uploadPlugin - file={}, res={:?}", file_name, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("uploadPlugin - file={}", file_name);
+        Ok(())
+    }
+
+    /// Install a plugin from a local `.hpi`/`.jpi` file, see [`Jenkins::upload_plugin`]
+    pub async fn upload_plugin_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("read plugin file {}", path.display()))?;
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("plugin.hpi");
+        self.upload_plugin(file_name, &content).await
+    }
+
+    /// Refresh update center metadata, like "Check now" in plugin manager
+    pub async fn check_update_center(&self) -> Result<()> {
+        let url = format!("{}/pluginManager/checkUpdatesServer", self.url);
+        let res = self.send(self.post(&url)).await?;
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("checkUpdatesServer - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(())
+    }
+
+    /// Whether a restart is needed to complete plugin installs/updates
+    pub async fn restart_required(&self) -> Result<bool> {
+        let url = format!(
+            "{}/updateCenter/api/json?tree=restartRequiredForCompletion",
+            self.url
+        );
+        let res: RestartRequiredRes = self.get_json(&url).await?;
+        Ok(res.restart_required_for_completion)
+    }
+
+    /// Get update center sites, available updates and installation jobs
+    pub async fn get_update_center(&self) -> Result<UpdateCenterRes> {
+        let url = format!("{}/updateCenter/api/json?depth=2", self.url);
+        self.get_json(&url).await
+    }
+
+    /// Poll update center until no installation job is pending or running
+    ///
+    /// ## Arguments
+    ///
+    /// * `timeout` - give up after this long
+    ///
+    pub async fn wait_plugin_installs(&self, timeout: Duration) -> Result<Vec<UpdateCenterJob>> {
+        let start = Instant::now();
+        loop {
+            let uc = self.get_update_center().await?;
+            if uc.jobs.iter().all(|j| !j.is_running()) {
+                return Ok(uc.jobs);
+            }
+            if start.elapsed() > timeout {
+                bail!(Error::APIError(
+                    "timeout waiting for plugin installs".to_owned()
+                ))
+            }
+            trace!("plugin installs running - jobs={:?}", uc.jobs);
+            self.pause(Duration::from_secs(3)).await?;
+        }
+    }
+
+    /// Run a system groovy script in the [script console](https://www.jenkins.io/doc/book/managing/script-console/)
+    ///
+    /// ## Arguments
+    ///
+    /// * `script` - groovy script, its printed output is returned
+    ///
+    pub async fn run_script(&self, script: &str) -> Result<String> {
+        let url = format!("{}/scriptText", self.url);
+        let res = self
+            .send(self.post(&url).form(&[("script", script)]))
+            .await?;
+        if !res.status().is_success() {
+            warn!("scriptText - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    pub(crate) async fn post_manage(&self, action: &str) -> Result<()> {
+        let url = format!("{}/{}", self.url, action);
+        let res = self.send(self.post(&url)).await?;
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("{} - res={:?}", action, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("{}", action);
+        Ok(())
+    }
+
+    /// Stop starting new builds, running builds continue
+    pub async fn quiet_down(&self) -> Result<()> {
+        self.post_manage("quietDown").await
+    }
+
+    pub async fn cancel_quiet_down(&self) -> Result<()> {
+        self.post_manage("cancelQuietDown").await
+    }
+
+    /// Restart once no build is running
+    pub async fn safe_restart(&self) -> Result<()> {
+        self.post_manage("safeRestart").await
+    }
+
+    /// Number of executors running builds across all nodes
+    pub async fn busy_executors(&self) -> Result<i32> {
+        let url = format!("{}/computer/api/json?tree=busyExecutors", self.url);
+        let res: BusyExecutorsRes = self.get_json(&url).await?;
+        Ok(res.busy_executors)
+    }
+
+    /// Whether the controller is up and serving API requests
+    pub async fn is_online(&self) -> bool {
+        let url = format!("{}/api/json?tree=mode", self.url);
+        matches!(self.send(self.get(&url)).await, Ok(res) if res.status().is_success())
+    }
+
+    /// Quiet down, wait for running builds, safe-restart and wait until online again
+    ///
+    /// Quiet down is cancelled if builds are still running after `opts.drain_timeout`.
+    pub async fn drain_and_restart(&self, opts: DrainRestartOptions) -> Result<()> {
+        let progress = |p: DrainProgress| {
+            info!("drain and restart - {:?}", p);
+            if let Some(cb) = &opts.on_progress {
+                cb(p);
+            }
+        };
+        progress(DrainProgress::QuietingDown);
+        self.quiet_down().await?;
+        let start = Instant::now();
+        loop {
+            let busy = self.busy_executors().await?;
+            progress(DrainProgress::Draining {
+                busy_executors: busy,
+            });
+            if busy == 0 {
+                break;
+            }
+            if start.elapsed() > opts.drain_timeout {
+                self.cancel_quiet_down().await?;
+                bail!(Error::APIError(format!(
+                    "timeout draining builds, {} executors still busy",
+                    busy
+                )))
+            }
+            self.pause(opts.poll_interval).await?;
+        }
+        progress(DrainProgress::Restarting);
+        self.safe_restart().await?;
+        // give jenkins time to go down before polling for it to come back
+        self.pause(opts.poll_interval).await?;
+        let start = Instant::now();
+        loop {
+            progress(DrainProgress::WaitingOnline);
+            if self.is_online().await {
+                progress(DrainProgress::Online);
+                return Ok(());
+            }
+            if start.elapsed() > opts.restart_timeout {
+                bail!(Error::APIError("timeout waiting for restart".to_owned()))
+            }
+            self.pause(opts.poll_interval).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groovy_string_escapes() {
+        assert_eq!(groovy_string(r"it's $HOME\x"), r"'it\'s $HOME\\x'");
+    }
+}
@@ -0,0 +1,117 @@
+//! Blocking client for callers without an async runtime, enabled by the
+//! `blocking` feature
+//!
+//! ```no_run
+//! # fn f() -> anyhow::Result<()> {
+//! use std::collections::HashMap;
+//!
+//! let cli = jenkins_rs::blocking::Jenkins::new(jenkins_rs::Jenkins::new(
+//!     "http://localhost:8080",
+//!     "bot",
+//!     "token",
+//! ))?;
+//! let queued = cli.build_with_parameter("deploy", HashMap::from([("ENV", "staging")]))?;
+//! // anything else of the async client
+//! let config = cli.call(|cli| async move { cli.get_job_config("deploy").await })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, future::Future};
+
+use anyhow::Result;
+use tokio::runtime::Runtime;
+
+use crate::{BuildHandle, BuildRes, QueueItemHandle, QueueItemRes};
+
+/// Runs a [`crate::Jenkins`] on a runtime of its own
+///
+/// Must not be used from within an async runtime, blocking there panics.
+pub struct Jenkins {
+    inner: crate::Jenkins,
+    rt: Runtime,
+}
+
+impl Jenkins {
+    pub fn new(inner: crate::Jenkins) -> Result<Jenkins> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Jenkins { inner, rt })
+    }
+
+    /// The wrapped async client
+    pub fn inner(&self) -> &crate::Jenkins {
+        &self.inner
+    }
+
+    /// Block on any call of the async client
+    pub fn call<F, Fut, T>(&self, f: F) -> T
+    where
+        F: FnOnce(crate::Jenkins) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.rt.block_on(f(self.inner.clone()))
+    }
+
+    /// See [`crate::Jenkins::build_with_parameter`]
+    pub fn build_with_parameter(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<QueueItemRes> {
+        self.rt
+            .block_on(self.inner.build_with_parameter(job, params))
+    }
+
+    /// See [`crate::Jenkins::queue_build_with_parameter`]
+    pub fn queue_build_with_parameter(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<QueueItemHandle> {
+        self.rt
+            .block_on(self.inner.queue_build_with_parameter(job, params))
+    }
+
+    /// See [`crate::Jenkins::resume`]
+    pub fn resume(&self, handle: &QueueItemHandle) -> Result<QueueItemRes> {
+        self.rt.block_on(self.inner.resume(handle))
+    }
+
+    /// See [`crate::Jenkins::get_build`]
+    pub fn get_build(&self, job: &str, number: i32) -> Result<BuildRes> {
+        self.rt.block_on(self.inner.get_build(job, number))
+    }
+
+    /// See [`crate::Jenkins::wait_build`]
+    pub fn wait_build(&self, handle: &BuildHandle) -> Result<BuildRes> {
+        self.rt.block_on(self.inner.wait_build(handle))
+    }
+
+    /// See [`crate::Jenkins::get_console_text`]
+    pub fn get_console_text(&self, job: &str, number: i32) -> Result<String> {
+        self.rt.block_on(self.inner.get_console_text(job, number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{response, MockServer};
+
+    #[test]
+    fn blocking_calls() {
+        // serves from its own worker threads
+        let rt = Runtime::new().unwrap();
+        let server = rt.block_on(MockServer::start(vec![response(
+            "200 OK",
+            &[],
+            "Started by user bot\nFinished: SUCCESS\n",
+        )]));
+        let cli = Jenkins::new(crate::Jenkins::new(&server.url, "user", "token")).unwrap();
+        let text = cli.get_console_text("api", 3).unwrap();
+        assert!(text.ends_with("Finished: SUCCESS\n"));
+        assert!(server.requests()[0].starts_with("GET /job/api/3/consoleText "));
+    }
+}
@@ -0,0 +1,58 @@
+//! [Blue Ocean](https://www.jenkins.io/doc/book/blueocean/) REST API, enabled by the
+//! `blueocean` feature
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use log::warn;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Jenkins};
+
+/// Artifact of a pipeline run, see [`Jenkins::get_run_artifacts`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunArtifact {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub size: i64,
+    /// download path relative to the Jenkins root, e.g. `/job/x/1/artifact/a.txt`
+    pub url: String,
+    #[serde(default)]
+    pub downloadable: bool,
+}
+
+impl Jenkins {
+    /// List artifacts of a pipeline run via the Blue Ocean REST API
+    ///
+    /// Includes artifacts archived by `archiveArtifacts` in every stage.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_run_artifacts(&self, job: &str, number: i32) -> Result<Vec<RunArtifact>> {
+        let url = format!(
+            "{}/blue/rest/organizations/jenkins/pipelines/{}/runs/{}/artifacts/?limit=1000",
+            self.url,
+            job.replace('/', "/pipelines/"),
+            number
+        );
+        self.get_json(&url).await
+    }
+
+    /// Download an artifact listed by [`Jenkins::get_run_artifacts`]
+    pub async fn download_run_artifact(&self, artifact: &RunArtifact) -> Result<Bytes> {
+        let url = Url::parse(&self.url)?.join(&artifact.url)?;
+        let res = self.send(self.get(url.as_str())).await?;
+        if !res.status().is_success() {
+            warn!(
+                "download run artifact - path={}, res={:?}",
+                artifact.path, res
+            );
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.bytes().await.map_err(Error::NetworkError)?)
+    }
+}
@@ -0,0 +1,2159 @@
+//! Builds: results, artifacts, test reports, pipeline stages and inputs
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    future::Future,
+    time::{Duration, SystemTime},
+};
+#[cfg(feature = "download")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use anyhow::{bail, Result};
+#[cfg(feature = "download")]
+use bytes::Bytes;
+#[cfg(feature = "stream")]
+use futures_util::{stream, Stream};
+use log::{info, trace, warn};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "download")]
+use sha2::{digest::DynDigest, Digest, Sha256};
+#[cfg(feature = "download")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+
+#[cfg(feature = "download")]
+use crate::download;
+#[cfg(feature = "extras")]
+use crate::Extras;
+use crate::{
+    action::{Action, FromAction, ParametersAction, TypedParameterValue},
+    model::{duration_millis, epoch_millis, from_epoch_millis, to_epoch_millis, Timestamped},
+};
+use crate::{
+    glob, join::try_join_all, Artifact, BuildCause, BuildHandle, BuildRes, Combination, Error,
+    Jenkins, Multipart,
+};
+
+/// Number of recent builds searched by [`Jenkins::find_running_builds`]
+const RUNNING_SCAN_BUILDS: usize = 50;
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BuildHistoryRes {
+    pub(crate) builds: Vec<BuildHistoryEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BuildHistoryEntry {
+    pub(crate) number: i32,
+    pub(crate) url: String,
+    #[serde(default)]
+    building: bool,
+    #[serde(default)]
+    actions: Vec<BuildHistoryAction>,
+}
+
+impl BuildHistoryEntry {
+    fn parameters(&self) -> impl Iterator<Item = &BuildParameterRes> {
+        self.actions.iter().flat_map(|a| &a.parameters)
+    }
+
+    /// Every `(name, value)` of `filter` matches a parameter of the build
+    fn matches_parameters(&self, filter: &HashMap<&str, &str>) -> bool {
+        filter.iter().all(|(name, value)| {
+            self.parameters().any(|p| {
+                p.name == *name
+                    && match &p.value {
+                        serde_json::Value::String(v) => v == value,
+                        serde_json::Value::Bool(v) => value.parse() == Ok(*v),
+                        serde_json::Value::Number(v) => value.parse().ok() == v.as_f64(),
+                        _ => false,
+                    }
+            })
+        })
+    }
+
+    /// Started by build `number` of the upstream job `job`
+    pub(crate) fn triggered_by(&self, job: &str, number: i32) -> bool {
+        self.actions
+            .iter()
+            .flat_map(|a| &a.causes)
+            .any(|c| c.upstream_project.as_deref() == Some(job) && c.upstream_build == Some(number))
+    }
+
+    /// `marker` passed as a parameter value or as `cause` text
+    pub(crate) fn has_marker(&self, marker: &str) -> bool {
+        self.parameters().any(|p| p.value.as_str() == Some(marker))
+            || self
+                .actions
+                .iter()
+                .flat_map(|a| &a.causes)
+                .any(|c| c.note.as_deref() == Some(marker))
+    }
+}
+
+/// Union of the actions fields queried from build history
+#[derive(Deserialize, Debug)]
+struct BuildHistoryAction {
+    #[serde(default)]
+    parameters: Vec<BuildParameterRes>,
+    #[serde(default)]
+    causes: Vec<BuildCause>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BuildParameterRes {
+    name: String,
+    /// missing for parameters like passwords and files
+    #[serde(default)]
+    value: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MavenArtifact {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub file_name: String,
+    pub canonical_name: String,
+    pub md5sum: Option<String>,
+}
+
+/// Module of a Maven job build, see [`Jenkins::get_maven_modules`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MavenModule {
+    pub pom_artifact: MavenArtifact,
+    /// `None` for `pom` packaging
+    pub main_artifact: Option<MavenArtifact>,
+    #[serde(default)]
+    pub attached_artifacts: Vec<MavenArtifact>,
+}
+
+impl MavenModule {
+    /// All artifacts of the module: pom, main and attached
+    pub fn artifacts(&self) -> impl Iterator<Item = &MavenArtifact> {
+        std::iter::once(&self.pom_artifact)
+            .chain(self.main_artifact.iter())
+            .chain(self.attached_artifacts.iter())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MavenArtifactsRes {
+    module_records: Vec<MavenModule>,
+}
+
+/// Pipeline build, see [`Jenkins::get_pipeline_run`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRun {
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    #[serde(rename = "startTimeMillis", with = "epoch_millis")]
+    pub start_time: SystemTime,
+    #[serde(rename = "durationMillis", with = "duration_millis")]
+    pub duration: Duration,
+    #[serde(default)]
+    pub stages: Vec<PipelineStage>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+impl Timestamped for PipelineRun {
+    fn time(&self) -> SystemTime {
+        self.start_time
+    }
+}
+
+impl PipelineRun {
+    pub fn start_time_millis(&self) -> i64 {
+        to_epoch_millis(self.start_time)
+    }
+
+    pub fn duration_millis(&self) -> i64 {
+        self.duration.as_millis() as i64
+    }
+
+    /// Distinct agents the stages ran on
+    pub fn nodes(&self) -> BTreeSet<&str> {
+        self.stages
+            .iter()
+            .map(|s| s.exec_node.as_str())
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStage {
+    /// flow node id, see [`Jenkins::get_stage_log`]
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    #[serde(rename = "startTimeMillis", with = "epoch_millis")]
+    pub start_time: SystemTime,
+    #[serde(rename = "durationMillis", with = "duration_millis")]
+    pub duration: Duration,
+    /// agent the stage ran on, `""` for the controller or a stage outside `node`
+    #[serde(default)]
+    pub exec_node: String,
+}
+
+impl Timestamped for PipelineStage {
+    fn time(&self) -> SystemTime {
+        self.start_time
+    }
+}
+
+impl PipelineStage {
+    pub fn start_time_millis(&self) -> i64 {
+        to_epoch_millis(self.start_time)
+    }
+
+    pub fn duration_millis(&self) -> i64 {
+        self.duration.as_millis() as i64
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PipelineStageDescribe {
+    #[serde(default)]
+    stage_flow_nodes: Vec<PipelineStage>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PipelineNodeLog {
+    text: Option<String>,
+    /// `text` is only the end of a longer log
+    #[serde(default)]
+    has_more: bool,
+}
+
+/// `input` step waiting for approval, see [`Jenkins::get_pending_inputs`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingInput {
+    pub id: String,
+    pub message: String,
+    pub proceed_text: Option<String>,
+    #[serde(default)]
+    pub inputs: Vec<PendingInputParameter>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingInputParameter {
+    /// e.g. `StringParameterDefinition`, `BooleanParameterDefinition`, `FileParameterDefinition`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub definition: Option<serde_json::Value>,
+}
+
+/// Parameter submitted with [`Jenkins::proceed_input`]
+#[derive(Debug, Clone)]
+pub struct InputParameter {
+    pub name: String,
+    pub value: InputValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum InputValue {
+    String(String),
+    /// checkbox (boolean) parameter
+    Bool(bool),
+    File {
+        file_name: String,
+        content: Vec<u8>,
+    },
+}
+
+/// Download progress callback, called with bytes downloaded and total size if known
+#[cfg(feature = "download")]
+pub type ProgressFn = Box<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+/// Options of [`Jenkins::download_artifact_to`]
+#[cfg(feature = "download")]
+pub struct DownloadOptions {
+    /// bytes already downloaded, e.g. size of a partial file to resume
+    pub offset: u64,
+    /// max number of resumes after network errors
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub on_progress: Option<ProgressFn>,
+}
+
+#[cfg(feature = "download")]
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            offset: 0,
+            max_retries: 3,
+            retry_delay: Duration::from_secs(3),
+            on_progress: None,
+        }
+    }
+}
+
+/// Expected digest of [`Jenkins::download_artifact_verified`]
+#[cfg(feature = "download")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// hex encoded SHA-256
+    Sha256(String),
+    /// hex encoded MD5
+    Md5(String),
+    /// MD5 recorded by Jenkins when the artifact was fingerprinted
+    Fingerprint,
+}
+
+#[cfg(feature = "download")]
+impl Checksum {
+    /// Check `content` against the digest, [`Checksum::Fingerprint`] always passes
+    pub fn verify(&self, content: &[u8]) -> Result<(), Error> {
+        let Some(mut hasher) = self.hasher() else {
+            return Ok(());
+        };
+        hasher.update(content);
+        self.check(hasher)
+    }
+
+    fn hasher(&self) -> Option<Box<dyn DynDigest + Send>> {
+        match self {
+            Checksum::Sha256(_) => Some(Box::new(Sha256::new())),
+            Checksum::Md5(_) => Some(Box::new(md5::Md5::new())),
+            Checksum::Fingerprint => None,
+        }
+    }
+
+    fn check(&self, hasher: Box<dyn DynDigest + Send>) -> Result<(), Error> {
+        let (Checksum::Sha256(expected) | Checksum::Md5(expected)) = self else {
+            return Ok(());
+        };
+        let actual: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if actual.eq_ignore_ascii_case(expected) {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch {
+                expected: expected.to_owned(),
+                actual,
+            })
+        }
+    }
+}
+
+/// Writer hashing everything written through it
+#[cfg(feature = "download")]
+struct HashingWriter<'a, W> {
+    inner: &'a mut W,
+    hasher: Box<dyn DynDigest + Send>,
+}
+
+#[cfg(feature = "download")]
+impl<W: AsyncWrite + Unpin> AsyncWrite for HashingWriter<'_, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut *this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            this.hasher.update(&buf[..n]);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "download")]
+#[derive(Deserialize, Debug)]
+struct FingerprintsRes {
+    #[serde(default)]
+    fingerprint: Vec<FingerprintRes>,
+}
+
+#[cfg(feature = "download")]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintRes {
+    file_name: String,
+    /// MD5
+    hash: String,
+}
+
+/// MD5 recorded for the artifact at `relative_path`
+///
+/// Fingerprints are recorded under the archived path. A record of the bare
+/// file name is only used when it is the only one with that name.
+#[cfg(feature = "download")]
+fn fingerprint_of(fingerprints: Vec<FingerprintRes>, relative_path: &str) -> Option<String> {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    if let Some(f) = fingerprints.iter().find(|f| f.file_name == relative_path) {
+        return Some(f.hash.clone());
+    }
+    let mut by_name = fingerprints
+        .into_iter()
+        .filter(|f| f.file_name == file_name);
+    match (by_name.next(), by_name.next()) {
+        (Some(f), None) => Some(f.hash),
+        _ => None,
+    }
+}
+
+/// Where an artifact is served from, see [`Jenkins::get_artifact_location`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactLocation {
+    /// served by Jenkins itself, needs credentials
+    Jenkins(String),
+    /// redirected to external storage, e.g. a presigned S3 url
+    External(String),
+}
+
+const TEST_RESULT_ACTIONS: [&str; 2] = [
+    "hudson.tasks.junit.TestResultAction",
+    "hudson.tasks.test.AggregatedTestResultAction",
+];
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestTrendRes {
+    #[serde(default)]
+    all_builds: Vec<TestTrendBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TestTrendBuild {
+    number: i32,
+    timestamp: i64,
+    result: Option<String>,
+    actions: Vec<TestTrendAction>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestTrendAction {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+    #[serde(default)]
+    fail_count: u32,
+    #[serde(default)]
+    skip_count: u32,
+    #[serde(default)]
+    total_count: u32,
+}
+
+/// Test result counts of one build, see [`Jenkins::get_test_trend`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestTrendPoint {
+    pub number: i32,
+    /// build start
+    pub timestamp: SystemTime,
+    pub result: Option<String>,
+    pub total: u32,
+    pub failed: u32,
+    pub skipped: u32,
+}
+
+impl TestTrendPoint {
+    pub fn passed(&self) -> u32 {
+        self.total.saturating_sub(self.failed + self.skipped)
+    }
+
+    fn from_build(build: TestTrendBuild) -> Option<TestTrendPoint> {
+        let action = build.actions.into_iter().find(|a| {
+            a.class
+                .as_deref()
+                .is_some_and(|c| TEST_RESULT_ACTIONS.contains(&c))
+        })?;
+        Some(TestTrendPoint {
+            number: build.number,
+            timestamp: from_epoch_millis(build.timestamp),
+            result: build.result,
+            total: action.total_count,
+            failed: action.fail_count,
+            skipped: action.skip_count,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TestReportRes {
+    #[serde(default)]
+    suites: Vec<TestSuiteRes>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TestSuiteRes {
+    cases: Vec<TestCaseRes>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestCaseRes {
+    class_name: String,
+    name: String,
+    /// `PASSED`, `FIXED`, `FAILED`, `REGRESSION` or `SKIPPED`
+    status: String,
+}
+
+/// Differences between two builds of a job, see [`Jenkins::diff_builds`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildDiff {
+    pub from_result: Option<String>,
+    pub to_result: Option<String>,
+    /// parameters added, removed or changed
+    pub parameters: Vec<ParameterChange>,
+    /// commits of the builds after `from` up to `to`
+    pub changes: Vec<ChangeSetItem>,
+    pub tests: TestDiff,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterChange {
+    pub name: String,
+    pub from: Option<TypedParameterValue>,
+    pub to: Option<TypedParameterValue>,
+}
+
+impl ParameterChange {
+    fn diff(
+        from: &BTreeMap<String, TypedParameterValue>,
+        to: &BTreeMap<String, TypedParameterValue>,
+    ) -> Vec<ParameterChange> {
+        let mut names: Vec<&String> = from.keys().chain(to.keys()).collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .filter(|name| from.get(*name) != to.get(*name))
+            .map(|name| ParameterChange {
+                name: name.clone(),
+                from: from.get(name).cloned(),
+                to: to.get(name).cloned(),
+            })
+            .collect()
+    }
+}
+
+/// Commit of a build changeset
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetItem {
+    pub commit_id: Option<String>,
+    pub msg: String,
+    pub author: Option<ChangeSetAuthor>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetAuthor {
+    pub full_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChangeSetsRes {
+    all_builds: Vec<ChangeSetsBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChangeSetsBuild {
+    number: i32,
+    /// freestyle jobs
+    change_set: Option<ChangeSetRes>,
+    /// pipeline jobs, one per checkout
+    #[serde(default)]
+    change_sets: Vec<ChangeSetRes>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangeSetRes {
+    #[serde(default)]
+    items: Vec<ChangeSetItem>,
+}
+
+/// Test outcome changes by `ClassName.name`, empty unless both builds have a report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestDiff {
+    pub newly_failing: Vec<String>,
+    pub fixed: Vec<String>,
+    pub still_failing: Vec<String>,
+}
+
+impl TestDiff {
+    fn new(from: &TestReportRes, to: &TestReportRes) -> TestDiff {
+        let outcomes = |report: &TestReportRes| -> BTreeMap<String, bool> {
+            report
+                .suites
+                .iter()
+                .flat_map(|s| &s.cases)
+                .filter(|c| c.status != "SKIPPED")
+                .map(|c| {
+                    let failed = c.status == "FAILED" || c.status == "REGRESSION";
+                    (format!("{}.{}", c.class_name, c.name), failed)
+                })
+                .collect()
+        };
+        let from = outcomes(from);
+        let mut diff = TestDiff::default();
+        for (test, failed) in outcomes(to) {
+            match (from.get(&test), failed) {
+                (Some(true), true) => diff.still_failing.push(test),
+                (Some(true), false) => diff.fixed.push(test),
+                (_, true) => diff.newly_failing.push(test),
+                _ => {}
+            }
+        }
+        diff
+    }
+}
+
+/// Test whose outcome flipped across builds, see [`Jenkins::find_flaky_tests`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlakyTest {
+    pub class_name: String,
+    pub name: String,
+    /// builds the test ran in, skipped runs excluded
+    pub runs: u32,
+    pub failures: u32,
+    /// number of pass/fail transitions between consecutive runs
+    pub flips: u32,
+}
+
+impl FlakyTest {
+    pub fn failure_rate(&self) -> f64 {
+        self.failures as f64 / self.runs as f64
+    }
+
+    /// Tests with at least two flips in `reports` ordered oldest first,
+    /// a single flip is a test that broke or got fixed
+    fn detect(reports: &[TestReportRes]) -> Vec<FlakyTest> {
+        let mut tests: BTreeMap<(&str, &str), (FlakyTest, Option<bool>)> = BTreeMap::new();
+        let cases = reports
+            .iter()
+            .flat_map(|r| &r.suites)
+            .flat_map(|s| &s.cases);
+        for case in cases {
+            let failed = match case.status.as_str() {
+                "PASSED" | "FIXED" => false,
+                "FAILED" | "REGRESSION" => true,
+                _ => continue,
+            };
+            let (test, last) = tests
+                .entry((&case.class_name, &case.name))
+                .or_insert_with(|| {
+                    let test = FlakyTest {
+                        class_name: case.class_name.clone(),
+                        name: case.name.clone(),
+                        runs: 0,
+                        failures: 0,
+                        flips: 0,
+                    };
+                    (test, None)
+                });
+            test.runs += 1;
+            test.failures += failed as u32;
+            if last.is_some_and(|l| l != failed) {
+                test.flips += 1;
+            }
+            *last = Some(failed);
+        }
+        let mut flaky: Vec<FlakyTest> = tests
+            .into_values()
+            .map(|(test, _)| test)
+            .filter(|t| t.flips >= 2)
+            .collect();
+        flaky.sort_by(|a, b| b.failure_rate().total_cmp(&a.failure_rate()));
+        flaky
+    }
+}
+
+const TIME_IN_QUEUE_ACTION: &str = "jenkins.metrics.impl.TimeInQueueAction";
+
+#[derive(Deserialize, Debug)]
+struct BuildActionsRes<T> {
+    actions: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BuildTimingAction {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+    #[serde(flatten)]
+    timing: BuildTiming,
+}
+
+/// Timing of `TimeInQueueAction`, see [`Jenkins::get_build_timing`]
+///
+/// `*_duration` are wall clock durations of each phase, `*_time` sum the
+/// phase over all subtasks of the build.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BuildTiming {
+    /// waiting for the quiet period
+    #[serde(rename = "waitingDurationMillis", with = "duration_millis")]
+    pub waiting_duration: Duration,
+    #[serde(rename = "waitingTimeMillis", with = "duration_millis")]
+    pub waiting_time: Duration,
+    /// blocked, e.g. by another build of the job or throttling
+    #[serde(rename = "blockedDurationMillis", with = "duration_millis")]
+    pub blocked_duration: Duration,
+    #[serde(rename = "blockedTimeMillis", with = "duration_millis")]
+    pub blocked_time: Duration,
+    /// ready to run, waiting for an executor
+    #[serde(rename = "buildableDurationMillis", with = "duration_millis")]
+    pub buildable_duration: Duration,
+    #[serde(rename = "buildableTimeMillis", with = "duration_millis")]
+    pub buildable_time: Duration,
+    /// total time in the queue
+    #[serde(rename = "queuingDurationMillis", with = "duration_millis")]
+    pub queuing_duration: Duration,
+    #[serde(rename = "queuingTimeMillis", with = "duration_millis")]
+    pub queuing_time: Duration,
+    #[serde(rename = "buildingDurationMillis", with = "duration_millis")]
+    pub building_duration: Duration,
+    #[serde(rename = "executingTimeMillis", with = "duration_millis")]
+    pub executing_time: Duration,
+    #[serde(rename = "totalDurationMillis", with = "duration_millis")]
+    pub total_duration: Duration,
+    /// executing time divided by building duration
+    pub executor_utilization: f64,
+    pub sub_task_count: i32,
+}
+
+/// Builds per `allBuilds` range request when paging the build history
+const BUILDS_PAGE: usize = 100;
+
+/// Build in the history of a job, see [`Jenkins::on_build_complete`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildSummary {
+    pub number: i32,
+    pub url: String,
+    #[serde(default)]
+    pub building: bool,
+    pub result: Option<String>,
+    /// zero while building
+    #[serde(with = "crate::model::duration_millis")]
+    pub duration: Duration,
+    /// start of the build
+    #[serde(with = "crate::model::epoch_millis")]
+    pub timestamp: SystemTime,
+}
+
+impl Timestamped for BuildSummary {
+    fn time(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AllBuildsRes {
+    all_builds: Vec<BuildSummary>,
+}
+
+/// Position of [`Jenkins::builds_stream`] in the build history
+struct BuildsCursor {
+    /// index of the next page in `allBuilds`
+    from: usize,
+    buffer: VecDeque<BuildSummary>,
+    /// lowest build number buffered so far
+    lowest: Option<i32>,
+    done: bool,
+}
+
+impl BuildsCursor {
+    fn new() -> BuildsCursor {
+        BuildsCursor {
+            from: 0,
+            buffer: VecDeque::new(),
+            lowest: None,
+            done: false,
+        }
+    }
+
+    /// Buffer a page, skipping builds already seen because new builds
+    /// started since the previous page shifted the range
+    fn push_page(&mut self, page: Vec<BuildSummary>) {
+        self.done = page.len() < BUILDS_PAGE;
+        self.from += page.len();
+        for build in page {
+            if self.lowest.is_none_or(|lowest| build.number < lowest) {
+                self.lowest = Some(build.number);
+                self.buffer.push_back(build);
+            }
+        }
+    }
+}
+
+/// Builds of a job already passed to the callback of
+/// [`Jenkins::on_build_complete`], persist it to resume after a restart
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionCursor {
+    /// every build up to this number was notified, `None` to start with
+    /// the builds completing from now on
+    pub through: Option<i32>,
+    /// builds above `through` notified out of order
+    #[serde(default)]
+    pub notified: BTreeSet<i32>,
+}
+
+impl CompletionCursor {
+    /// Start past the completed builds among `builds`, newest first
+    fn starting_at(builds: &[BuildSummary]) -> CompletionCursor {
+        let mut cursor = CompletionCursor {
+            through: Some(0),
+            notified: builds
+                .iter()
+                .filter(|b| !b.building)
+                .map(|b| b.number)
+                .collect(),
+        };
+        cursor.compact(builds);
+        cursor
+    }
+
+    /// Completed builds among `builds` not notified yet, oldest first
+    fn pending<'a>(&self, builds: &'a [BuildSummary]) -> Vec<&'a BuildSummary> {
+        let through = self.through.unwrap_or(0);
+        builds
+            .iter()
+            .rev()
+            .filter(|b| b.number > through && !b.building && !self.notified.contains(&b.number))
+            .collect()
+    }
+
+    /// Move `through` up to the oldest build still running among `builds`,
+    /// all builds above `through` once all were notified
+    fn compact(&mut self, builds: &[BuildSummary]) {
+        let through = self.through.unwrap_or(0);
+        let running = builds
+            .iter()
+            .filter(|b| b.number > through && b.building)
+            .map(|b| b.number - 1)
+            .min();
+        let newest = builds.iter().map(|b| b.number).max().unwrap_or(through);
+        let through = running.unwrap_or(newest).max(through);
+        self.notified.retain(|n| *n > through);
+        self.through = Some(through);
+    }
+}
+
+impl Jenkins {
+    /// Get build info
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_build(&self, job: &str, number: i32) -> Result<BuildRes> {
+        let url = format!("{}/job/{}/{}/api/json", self.url, job, number);
+        self.get_json(&url).await
+    }
+
+    /// Stream all builds of a job from newest to oldest, fetching pages lazily,
+    /// enabled by the `stream` feature
+    ///
+    /// Stop consuming to stop fetching, e.g. for the builds of the last week:
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use std::time::{Duration, SystemTime};
+    /// use futures_util::{future::ready, TryStreamExt};
+    ///
+    /// let cutoff = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+    /// let recent: Vec<_> = cli
+    ///     .builds_stream("deploy")
+    ///     .try_take_while(|b| ready(Ok(b.timestamp > cutoff)))
+    ///     .try_collect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    #[cfg(feature = "stream")]
+    pub fn builds_stream<'a>(
+        &'a self,
+        job: &'a str,
+    ) -> impl Stream<Item = Result<BuildSummary>> + Send + 'a {
+        stream::try_unfold(BuildsCursor::new(), move |mut cursor| async move {
+            let build = self.next_build(job, &mut cursor).await?;
+            Ok(build.map(|build| (build, cursor)))
+        })
+    }
+
+    /// Next build of the history, fetching the next page once the buffered
+    /// one is used up
+    async fn next_build(
+        &self,
+        job: &str,
+        cursor: &mut BuildsCursor,
+    ) -> Result<Option<BuildSummary>> {
+        while cursor.buffer.is_empty() && !cursor.done {
+            if self.shutdown.is_shutdown() {
+                bail!(Error::Shutdown)
+            }
+            let url = format!(
+                "{}/job/{}/api/json?tree=allBuilds[number,url,building,result,duration,timestamp]{{{},{}}}",
+                self.url,
+                job,
+                cursor.from,
+                cursor.from + BUILDS_PAGE
+            );
+            let res: AllBuildsRes = self.get_json(&url).await?;
+            trace!(
+                "builds page - job={}, from={}, builds={}",
+                job,
+                cursor.from,
+                res.all_builds.len()
+            );
+            cursor.push_page(res.all_builds);
+        }
+        Ok(cursor.buffer.pop_front())
+    }
+
+    /// Call `callback` with each build of a job once it completes, oldest first
+    ///
+    /// Polls the build history every `interval`. Delivery is at least once:
+    /// the callback gets the cursor to persist once it handled the build,
+    /// restarting from the last persisted cursor redelivers the builds after
+    /// it. Runs until polling or the callback fails, or the client is shut
+    /// down.
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use jenkins_rs::CompletionCursor;
+    ///
+    /// let cursor = match tokio::fs::read("deploy.cursor").await {
+    ///     Ok(json) => serde_json::from_slice(&json)?,
+    ///     Err(_) => CompletionCursor::default(),
+    /// };
+    /// let interval = std::time::Duration::from_secs(10);
+    /// cli.on_build_complete("deploy", cursor, interval, |build, cursor| async move {
+    ///     println!("deploy #{} finished: {:?}", build.number, build.result);
+    ///     tokio::fs::write("deploy.cursor", serde_json::to_vec(&cursor)?).await?;
+    ///     Ok(())
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `cursor` - builds already notified, default to skip past builds
+    /// * `interval` - pause between polls
+    /// * `callback` - receives the completed build and the cursor after it
+    ///
+    pub async fn on_build_complete<F, Fut>(
+        &self,
+        job: &str,
+        mut cursor: CompletionCursor,
+        interval: Duration,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(BuildRes, CompletionCursor) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        info!(
+            "watching completed builds - job={}, cursor={:?}",
+            job, cursor
+        );
+        loop {
+            // the builds after `through`, or the latest page to start from
+            let mut history = BuildsCursor::new();
+            let mut builds = Vec::new();
+            while cursor.through.is_some() || builds.len() < BUILDS_PAGE {
+                let Some(build) = self.next_build(job, &mut history).await? else {
+                    break;
+                };
+                if cursor
+                    .through
+                    .is_some_and(|through| build.number <= through)
+                {
+                    break;
+                }
+                builds.push(build);
+            }
+            if cursor.through.is_none() {
+                cursor = CompletionCursor::starting_at(&builds);
+            }
+            for summary in cursor.pending(&builds) {
+                let build = self.get_build(job, summary.number).await?;
+                trace!("build completed - job={}, number={}", job, summary.number);
+                cursor.notified.insert(summary.number);
+                callback(build, cursor.clone()).await?;
+            }
+            cursor.compact(&builds);
+            self.pause(interval).await?;
+        }
+    }
+
+    /// Get the parameters a build ran with, password values are masked
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_build_parameters(
+        &self,
+        job: &str,
+        number: i32,
+    ) -> Result<BTreeMap<String, TypedParameterValue>> {
+        let url = format!(
+            "{}/job/{}/{}/api/json?tree=actions[parameters[name,value,jobName,number]]",
+            self.url, job, number
+        );
+        let res: BuildActionsRes<Action> = self.get_json(&url).await?;
+        Ok(res
+            .actions
+            .iter()
+            .filter_map(ParametersAction::from_action)
+            .flat_map(|a| &a.parameters)
+            .map(|p| (p.name.clone(), p.typed()))
+            .collect())
+    }
+
+    /// Get queue and execution timing of a build recorded by the Metrics plugin
+    ///
+    /// Returns `None` if the build has no `TimeInQueueAction`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_build_timing(&self, job: &str, number: i32) -> Result<Option<BuildTiming>> {
+        let url = format!(
+            "{}/job/{}/{}/api/json?tree=actions[*]",
+            self.url, job, number
+        );
+        let res: BuildActionsRes<BuildTimingAction> = self.get_json(&url).await?;
+        Ok(res
+            .actions
+            .into_iter()
+            .find_map(|a| (a.class.as_deref() == Some(TIME_IN_QUEUE_ACTION)).then_some(a.timing)))
+    }
+
+    /// Resolve where an artifact is served from
+    ///
+    /// Artifact managers like artifact-manager-s3 redirect downloads to external
+    /// storage with presigned urls, which must be fetched without Jenkins credentials.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `relative_path` - `relativePath` of [`Artifact`]
+    ///
+    pub async fn get_artifact_location(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+    ) -> Result<ArtifactLocation> {
+        let url = format!(
+            "{}/job/{}/{}/artifact/{}",
+            self.url, job, number, relative_path
+        );
+        let res = self
+            .send_via(
+                &self.hc_no_redirect,
+                self.request_via(&self.hc_no_redirect, Method::GET, &url)
+                    .header(reqwest::header::RANGE, "bytes=0-0"),
+            )
+            .await?;
+        if res.status().is_redirection() {
+            let location = res
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| Error::APIError("location header not available".to_owned()))?;
+            info!("artifact redirected - url={}, location={}", url, location);
+            Ok(ArtifactLocation::External(location.to_owned()))
+        } else if res.status().is_success() {
+            Ok(ArtifactLocation::Jenkins(url))
+        } else {
+            warn!("Get {}: res={:?}", url, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+    }
+
+    /// Download an artifact of a build, following redirects to external storage
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `relative_path` - `relativePath` of [`Artifact`]
+    ///
+    #[cfg(feature = "download")]
+    pub async fn download_artifact(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+    ) -> Result<Bytes> {
+        let req = match self
+            .get_artifact_location(job, number, relative_path)
+            .await?
+        {
+            ArtifactLocation::Jenkins(url) => self.get(&url),
+            ArtifactLocation::External(url) => self.hc.get(url),
+        };
+        let res = self.send(req).await?;
+        if !res.status().is_success() {
+            warn!("download artifact - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.bytes().await.map_err(Error::NetworkError)?)
+    }
+
+    /// Stream an artifact into `writer`, resuming with HTTP range requests on network errors
+    ///
+    /// Returns the total number of bytes written including `opts.offset`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `relative_path` - `relativePath` of [`Artifact`]
+    /// * `writer` - destination, e.g. a [`tokio::fs::File`]
+    ///
+    #[cfg(feature = "download")]
+    pub async fn download_artifact_to<W: AsyncWrite + Unpin>(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+        writer: &mut W,
+        opts: &DownloadOptions,
+    ) -> Result<u64> {
+        self.download_artifact_limited(job, number, relative_path, writer, opts, None)
+            .await
+    }
+
+    #[cfg(feature = "download")]
+    pub(crate) async fn download_artifact_limited<W: AsyncWrite + Unpin>(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+        writer: &mut W,
+        opts: &DownloadOptions,
+        limiter: Option<&download::RateLimiter>,
+    ) -> Result<u64> {
+        let location = self
+            .get_artifact_location(job, number, relative_path)
+            .await?;
+        let mut written = opts.offset;
+        let mut retries = 0;
+        loop {
+            match self
+                .download_range(&location, &mut written, writer, opts, limiter)
+                .await
+            {
+                Ok(()) => {
+                    writer.flush().await?;
+                    return Ok(written);
+                }
+                Err(err)
+                    if retries < opts.max_retries
+                        && matches!(err.downcast_ref(), Some(Error::NetworkError(_))) =>
+                {
+                    retries += 1;
+                    warn!(
+                        "download interrupted, resume from {} - path={}, retry={}, err={:?}",
+                        written, relative_path, retries, err
+                    );
+                    self.pause(opts.retry_delay).await?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    #[cfg(feature = "download")]
+    pub(crate) async fn download_range<W: AsyncWrite + Unpin>(
+        &self,
+        location: &ArtifactLocation,
+        written: &mut u64,
+        writer: &mut W,
+        opts: &DownloadOptions,
+        limiter: Option<&download::RateLimiter>,
+    ) -> Result<()> {
+        let mut req = match location {
+            ArtifactLocation::Jenkins(url) => self.get(url),
+            ArtifactLocation::External(url) => self.hc.get(url),
+        };
+        if *written > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", written));
+        }
+        let mut res = self.send(req).await?;
+        let status = res.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // nothing left after `written`
+            return Ok(());
+        }
+        if !status.is_success() {
+            warn!("download - location={:?}, res={:?}", location, res);
+            bail!(Error::APIError(format!("http status: {}", status)))
+        }
+        if *written > 0 && status != StatusCode::PARTIAL_CONTENT {
+            bail!(Error::APIError(
+                "server does not support range requests, cannot resume".to_owned()
+            ))
+        }
+        let total = res.content_length().map(|len| len + *written);
+        while let Some(chunk) = self
+            .until_shutdown(res.chunk())
+            .await?
+            .map_err(Error::NetworkError)?
+        {
+            if let Some(limiter) = limiter {
+                limiter.consume(chunk.len() as u64).await;
+            }
+            writer.write_all(&chunk).await?;
+            *written += chunk.len() as u64;
+            if let Some(cb) = &opts.on_progress {
+                cb(*written, total);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stream an artifact into `writer` and verify its checksum, hashing on the way
+    ///
+    /// Fails with [`Error::ChecksumMismatch`] if the content does not match,
+    /// after all of it was written. Returns the number of bytes written.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `relative_path` - `relativePath` of [`Artifact`]
+    /// * `writer` - destination, e.g. a [`tokio::fs::File`]
+    /// * `checksum` - expected digest, or [`Checksum::Fingerprint`] to use the MD5 recorded by Jenkins
+    ///
+    #[cfg(feature = "download")]
+    pub async fn download_artifact_verified<W: AsyncWrite + Unpin>(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+        writer: &mut W,
+        checksum: Checksum,
+    ) -> Result<u64> {
+        let checksum = match checksum {
+            Checksum::Fingerprint => {
+                Checksum::Md5(self.get_fingerprint_md5(job, number, relative_path).await?)
+            }
+            checksum => checksum,
+        };
+        let Some(hasher) = checksum.hasher() else {
+            unreachable!("fingerprint resolved to md5")
+        };
+        let mut writer = HashingWriter {
+            inner: writer,
+            hasher,
+        };
+        let written = self
+            .download_artifact_to(
+                job,
+                number,
+                relative_path,
+                &mut writer,
+                &DownloadOptions::default(),
+            )
+            .await?;
+        checksum.check(writer.hasher)?;
+        Ok(written)
+    }
+
+    #[cfg(feature = "download")]
+    pub(crate) async fn get_fingerprint_md5(
+        &self,
+        job: &str,
+        number: i32,
+        relative_path: &str,
+    ) -> Result<String> {
+        let url = format!(
+            "{}/job/{}/{}/api/json?tree=fingerprint[fileName,hash]",
+            self.url, job, number
+        );
+        let res: FingerprintsRes = self.get_json(&url).await?;
+        match fingerprint_of(res.fingerprint, relative_path) {
+            Some(hash) => Ok(hash),
+            None => bail!(Error::APIError(format!(
+                "artifact {} not fingerprinted",
+                relative_path
+            ))),
+        }
+    }
+
+    /// Get modules of a Maven job build with their artifacts
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_maven_modules(&self, job: &str, number: i32) -> Result<Vec<MavenModule>> {
+        let url = format!(
+            "{}/job/{}/{}/mavenArtifacts/api/json",
+            self.url, job, number
+        );
+        let res: MavenArtifactsRes = self.get_json(&url).await?;
+        Ok(res.module_records)
+    }
+
+    /// Get build info of one configuration of a matrix job build
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `combination` - axis values of the configuration
+    /// * `number` - build number
+    ///
+    pub async fn get_matrix_build(
+        &self,
+        job: &str,
+        combination: &Combination,
+        number: i32,
+    ) -> Result<BuildRes> {
+        let url = format!(
+            "{}/job/{}/{}/{}/api/json",
+            self.url, job, combination, number
+        );
+        self.get_json(&url).await
+    }
+
+    /// Get stages of a pipeline build via [Pipeline REST API](https://github.com/jenkinsci/pipeline-stage-view-plugin/tree/master/rest-api)
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_pipeline_run(&self, job: &str, number: i32) -> Result<PipelineRun> {
+        let url = format!("{}/job/{}/{}/wfapi/describe", self.url, job, number);
+        self.get_json(&url).await
+    }
+
+    /// Get log of one pipeline stage, concatenating the logs of its steps
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `node_id` - `id` of [`PipelineStage`]
+    ///
+    pub async fn get_stage_log(&self, job: &str, number: i32, node_id: &str) -> Result<String> {
+        let node_url = format!(
+            "{}/job/{}/{}/execution/node/{}/wfapi",
+            self.url, job, number, node_id
+        );
+        let stage: PipelineStageDescribe = self.get_json(&format!("{}/describe", node_url)).await?;
+        let mut log = String::new();
+        for step in stage.stage_flow_nodes {
+            let step_url = format!(
+                "{}/job/{}/{}/execution/node/{}",
+                self.url, job, number, step.id
+            );
+            let step_log: PipelineNodeLog =
+                self.get_json(&format!("{}/wfapi/log", step_url)).await?;
+            if step_log.has_more {
+                // wfapi truncates long logs, the log action has all of it
+                let url = format!("{}/log/logText/progressiveText?start=0", step_url);
+                let res = self.send(self.get(&url)).await?;
+                if !res.status().is_success() {
+                    warn!("get step log - url={}, res={:?}", url, res);
+                    bail!(Error::APIError(format!("http status: {}", res.status())))
+                }
+                log.push_str(&res.text().await.map_err(Error::NetworkError)?);
+            } else {
+                log.push_str(&step_log.text.unwrap_or_default());
+            }
+        }
+        Ok(log)
+    }
+
+    /// List `input` steps of a pipeline build waiting for approval
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_pending_inputs(&self, job: &str, number: i32) -> Result<Vec<PendingInput>> {
+        let url = format!(
+            "{}/job/{}/{}/wfapi/pendingInputActions",
+            self.url, job, number
+        );
+        self.get_json(&url).await
+    }
+
+    /// Proceed an `input` step, submitting its parameters
+    ///
+    /// File parameters are uploaded as `multipart/form-data`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `input_id` - `id` of [`PendingInput`]
+    /// * `params` - values of the input parameters
+    ///
+    pub async fn proceed_input(
+        &self,
+        job: &str,
+        number: i32,
+        input_id: &str,
+        params: Vec<InputParameter>,
+    ) -> Result<()> {
+        let url = format!(
+            "{}/job/{}/{}/input/{}/submit",
+            self.url, job, number, input_id
+        );
+        let mut files = Vec::new();
+        let json_params: Vec<serde_json::Value> = params
+            .into_iter()
+            .map(|p| match p.value {
+                InputValue::String(value) => serde_json::json!({"name": p.name, "value": value}),
+                InputValue::Bool(value) => serde_json::json!({"name": p.name, "value": value}),
+                InputValue::File { file_name, content } => {
+                    let field = format!("file{}", files.len());
+                    let json = serde_json::json!({"name": p.name, "file": field});
+                    files.push((field, file_name, content));
+                    json
+                }
+            })
+            .collect();
+        let json = serde_json::json!({ "parameter": json_params }).to_string();
+        let req = if files.is_empty() {
+            self.post(&url)
+                .form(&[("proceed", "Proceed"), ("json", json.as_str())])
+        } else {
+            let mut form = Multipart::new();
+            form.text("proceed", "Proceed");
+            form.text("json", &json);
+            for (field, file_name, content) in &files {
+                form.file(field, file_name, content);
+            }
+            form.apply(self.post(&url))
+        };
+        let res = self.send(req).await?;
+        // jenkins redirects to the build page after submit
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("proceed input - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!(
+            "proceed input - job={}, number={}, input={}",
+            job, number, input_id
+        );
+        Ok(())
+    }
+
+    /// Abort an `input` step
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `input_id` - `id` of [`PendingInput`]
+    ///
+    pub async fn abort_input(&self, job: &str, number: i32, input_id: &str) -> Result<()> {
+        let url = format!(
+            "{}/job/{}/{}/input/{}/abort",
+            self.url, job, number, input_id
+        );
+        let res = self.send(self.post(&url)).await?;
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("abort input - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(())
+    }
+
+    /// List artifacts of a build whose relative path matches a glob pattern
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `pattern` - glob pattern, e.g. `target/*.jar` or `**/*.xml`
+    ///
+    pub async fn list_artifacts_matching(
+        &self,
+        job: &str,
+        number: i32,
+        pattern: &str,
+    ) -> Result<Vec<Artifact>> {
+        let glob = glob::Glob::new(pattern);
+        let build = self.get_build(job, number).await?;
+        Ok(build
+            .artifacts
+            .into_iter()
+            .filter(|a| glob.is_match(&a.relative_path))
+            .collect())
+    }
+
+    /// Get test result counts of the last `last_n` builds, oldest first
+    ///
+    /// Builds without a test report are skipped.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `last_n` - number of recent builds to look at
+    ///
+    pub async fn get_test_trend(&self, job: &str, last_n: usize) -> Result<Vec<TestTrendPoint>> {
+        let mut builds = BTreeMap::new();
+        let mut start = 0;
+        while builds.len() < last_n {
+            let url = format!(
+                "{}/job/{}/api/json?tree=allBuilds[number,timestamp,result,actions[_class,failCount,skipCount,totalCount]]{{{},{}}}",
+                self.url,
+                job,
+                start,
+                start + BUILDS_PAGE
+            );
+            let page = self.get_json::<TestTrendRes>(&url).await?.all_builds;
+            let done = page.len() < BUILDS_PAGE;
+            start += page.len();
+            for build in page {
+                // a build started since the previous page shifts it
+                builds.entry(build.number).or_insert(build);
+            }
+            if done {
+                break;
+            }
+        }
+        let skip = builds.len().saturating_sub(last_n);
+        Ok(builds
+            .into_values()
+            .skip(skip)
+            .filter_map(TestTrendPoint::from_build)
+            .collect())
+    }
+
+    /// Find tests that alternated between passing and failing in recent builds
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `window` - number of recent builds with test reports to look at,
+    ///   builds without a report are passed over
+    ///
+    pub async fn find_flaky_tests(&self, job: &str, window: usize) -> Result<Vec<FlakyTest>> {
+        let trend = self.get_recent_test_points(job, window).await?;
+        let sem = Semaphore::new(4);
+        let reports = try_join_all(trend.iter().map(|point| async {
+            let _permit = sem.acquire().await?;
+            let url = format!(
+                "{}/job/{}/{}/testReport/api/json?tree=suites[cases[className,name,status]]",
+                self.url, job, point.number
+            );
+            self.get_json::<TestReportRes>(&url).await
+        }))
+        .await?;
+        let flaky = FlakyTest::detect(&reports);
+        info!("flaky tests - job={}, count={}", job, flaky.len());
+        Ok(flaky)
+    }
+
+    /// Compare parameters, commits, results and test outcomes of two builds
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `from` - earlier build number
+    /// * `to` - later build number
+    ///
+    pub async fn diff_builds(&self, job: &str, from: i32, to: i32) -> Result<BuildDiff> {
+        let (from_build, to_build, from_params, to_params) = tokio::try_join!(
+            self.get_build(job, from),
+            self.get_build(job, to),
+            self.get_build_parameters(job, from),
+            self.get_build_parameters(job, to),
+        )?;
+        let (changes, from_tests, to_tests) = tokio::try_join!(
+            self.get_changes_between(job, from, to),
+            self.get_test_report(job, from),
+            self.get_test_report(job, to),
+        )?;
+        let tests = match (from_tests, to_tests) {
+            (Some(from), Some(to)) => TestDiff::new(&from, &to),
+            _ => TestDiff::default(),
+        };
+        Ok(BuildDiff {
+            from_result: from_build.result,
+            to_result: to_build.result,
+            parameters: ParameterChange::diff(&from_params, &to_params),
+            changes,
+            tests,
+        })
+    }
+
+    /// Last `count` builds with a test report, oldest first
+    ///
+    /// Pages through `allBuilds` until enough builds have a report, builds
+    /// without one don't count.
+    async fn get_recent_test_points(&self, job: &str, count: usize) -> Result<Vec<TestTrendPoint>> {
+        let mut points = BTreeMap::new();
+        let mut start = 0;
+        while points.len() < count {
+            let url = format!(
+                "{}/job/{}/api/json?tree=allBuilds[number,timestamp,result,actions[_class,failCount,skipCount,totalCount]]{{{},{}}}",
+                self.url,
+                job,
+                start,
+                start + BUILDS_PAGE
+            );
+            let page = self.get_json::<TestTrendRes>(&url).await?.all_builds;
+            let done = page.len() < BUILDS_PAGE;
+            start += page.len();
+            for point in page.into_iter().filter_map(TestTrendPoint::from_build) {
+                // a build started since the previous page shifts it
+                points.entry(point.number).or_insert(point);
+            }
+            if done {
+                break;
+            }
+        }
+        let skip = points.len().saturating_sub(count);
+        Ok(points.into_values().skip(skip).collect())
+    }
+
+    /// Commits of the builds after `from` up to `to`, oldest first
+    ///
+    /// Pages through `allBuilds` from the newest build, the `builds` list
+    /// stops at the last 100.
+    async fn get_changes_between(
+        &self,
+        job: &str,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<ChangeSetItem>> {
+        let mut builds = BTreeMap::new();
+        let mut start = 0;
+        loop {
+            let url = format!(
+                "{}/job/{}/api/json?tree=allBuilds[number,changeSet[{items}],changeSets[{items}]]{{{},{}}}",
+                self.url,
+                job,
+                start,
+                start + BUILDS_PAGE,
+                items = "items[commitId,msg,author[fullName]]"
+            );
+            let page = self.get_json::<ChangeSetsRes>(&url).await?.all_builds;
+            let done = page.len() < BUILDS_PAGE || page.iter().any(|b| b.number <= from);
+            start += page.len();
+            for build in page {
+                if build.number > from && build.number <= to {
+                    // a build started since the previous page shifts it, keep the first copy
+                    builds.entry(build.number).or_insert(build);
+                }
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(builds
+            .into_values()
+            .flat_map(|b| b.change_set.into_iter().chain(b.change_sets))
+            .flat_map(|c| c.items)
+            .collect())
+    }
+
+    /// `None` if the build has no test report
+    async fn get_test_report(&self, job: &str, number: i32) -> Result<Option<TestReportRes>> {
+        let url = format!(
+            "{}/job/{}/{}/testReport/api/json?tree=suites[cases[className,name,status]]",
+            self.url, job, number
+        );
+        let res = self.send(self.get(&url)).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(self.json_body(res).await?)),
+            status => bail!(Error::APIError(format!("http status: {}", status))),
+        }
+    }
+
+    /// Get plain console log of a build
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_console_text(&self, job: &str, number: i32) -> Result<String> {
+        let url = format!("{}/job/{}/{}/consoleText", self.url, job, number);
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get console text - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    /// Find running builds of a job triggered with the given parameter values
+    ///
+    /// Useful to attach to an already running build instead of triggering a
+    /// duplicate, e.g. a deploy of the same version.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `param_filter` - parameter values the build must have, `true`/`false` for booleans
+    ///
+    pub async fn find_running_builds(
+        &self,
+        job: &str,
+        param_filter: &HashMap<&str, &str>,
+    ) -> Result<Vec<BuildHandle>> {
+        let url = format!(
+            "{}/api/json?tree=builds[number,url,building,actions[parameters[name,value]]]{{0,{}}}",
+            self.item_url(job),
+            RUNNING_SCAN_BUILDS
+        );
+        let res: BuildHistoryRes = self.get_json(&url).await?;
+        let handle_job = crate::job::full_name(job).replace('/', "/job/");
+        Ok(res
+            .builds
+            .into_iter()
+            .filter(|b| b.building && b.matches_parameters(param_filter))
+            .map(|b| BuildHandle {
+                job: handle_job.clone(),
+                number: b.number,
+                url: b.url,
+            })
+            .collect())
+    }
+
+    /// Poll a persisted build until it is finished
+    pub async fn wait_build(&self, handle: &BuildHandle) -> Result<BuildRes> {
+        loop {
+            let build = self.get_build(&handle.job, handle.number).await?;
+            if !build.building {
+                info!("build finished - job={}, build={:?}", handle.job, build);
+                return Ok(build);
+            }
+            trace!(
+                "build running - job={}, number={}",
+                handle.job,
+                handle.number
+            );
+            self.pause(Duration::from_secs(3)).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trend_skips_builds_without_report() {
+        let res: TestTrendRes = serde_json::from_str(
+            r#"{"allBuilds":[
+                {"number":3,"timestamp":3000,"result":"UNSTABLE","actions":[
+                    {"_class":"hudson.model.CauseAction"},
+                    {"_class":"hudson.tasks.junit.TestResultAction","failCount":2,"skipCount":1,"totalCount":10}]},
+                {"number":2,"timestamp":2000,"result":"FAILURE","actions":[{}]}
+            ]}"#,
+        )
+        .unwrap();
+        let trend: Vec<_> = res
+            .all_builds
+            .into_iter()
+            .filter_map(TestTrendPoint::from_build)
+            .collect();
+        assert_eq!(trend.len(), 1);
+        assert_eq!(trend[0].number, 3);
+        assert_eq!(trend[0].passed(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_trend_pages_past_100_builds() {
+        use crate::mock::{response, MockServer};
+
+        let action = r#"{"_class":"hudson.tasks.junit.TestResultAction","failCount":0,"skipCount":0,"totalCount":1}"#;
+        let page = |numbers: std::ops::RangeInclusive<i32>| {
+            let builds: Vec<String> = numbers
+                .rev()
+                .map(|n| {
+                    format!(
+                        r#"{{"number":{n},"timestamp":0,"result":"SUCCESS","actions":[{action}]}}"#
+                    )
+                })
+                .collect();
+            format!(r#"{{"allBuilds":[{}]}}"#, builds.join(","))
+        };
+        let server = MockServer::start(vec![
+            response("200 OK", &[], page(61..=160)),
+            response("200 OK", &[], page(1..=60)),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let trend = cli.get_test_trend("api", 150).await.unwrap();
+        assert_eq!(trend.len(), 150);
+        assert_eq!(trend[0].number, 11);
+        assert_eq!(trend[149].number, 160);
+        let requests = server.requests();
+        assert!(requests[1].contains("100,200"));
+    }
+
+    #[tokio::test]
+    async fn flaky_window_counts_builds_with_reports() {
+        use crate::mock::{response, MockServer};
+
+        let json = [("Content-Type", "application/json")];
+        let action = r#"{"_class":"hudson.tasks.junit.TestResultAction","failCount":0,"skipCount":0,"totalCount":1}"#;
+        let builds = format!(
+            r#"{{"allBuilds":[
+                {{"number":5,"timestamp":0,"result":"SUCCESS","actions":[{action}]}},
+                {{"number":4,"timestamp":0,"result":"FAILURE","actions":[{{}}]}},
+                {{"number":3,"timestamp":0,"result":"SUCCESS","actions":[{action}]}},
+                {{"number":2,"timestamp":0,"result":"SUCCESS","actions":[{action}]}}]}}"#
+        );
+        let report = r#"{"suites":[{"cases":[{"className":"a.B","name":"t","status":"PASSED"}]}]}"#;
+        let server = MockServer::start(vec![
+            response("200 OK", &json, builds),
+            response("200 OK", &json, report),
+            response("200 OK", &json, report),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let flaky = cli.find_flaky_tests("api", 2).await.unwrap();
+        assert!(flaky.is_empty());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].contains("allBuilds[") && requests[0].contains("{0,100}"));
+        // 4 has no report, the window reaches back to 3 but not to 2
+        let mut reports: Vec<&str> = requests[1..]
+            .iter()
+            .map(|r| r.split(' ').nth(1).unwrap())
+            .collect();
+        reports.sort();
+        assert!(reports[0].starts_with("/job/api/3/testReport/"));
+        assert!(reports[1].starts_with("/job/api/5/testReport/"));
+    }
+
+    #[test]
+    fn detect_flaky_tests() {
+        let report = |statuses: [&str; 2]| -> TestReportRes {
+            let cases = ["stable", "flaky"]
+                .iter()
+                .zip(statuses)
+                .map(|(name, status)| {
+                    format!(r#"{{"className":"a.B","name":"{name}","status":"{status}"}}"#)
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            serde_json::from_str(&format!(r#"{{"suites":[{{"cases":[{cases}]}}]}}"#)).unwrap()
+        };
+        let reports = [
+            report(["PASSED", "PASSED"]),
+            report(["FAILED", "REGRESSION"]),
+            report(["FAILED", "FIXED"]),
+            report(["SKIPPED", "FAILED"]),
+        ];
+        let flaky = FlakyTest::detect(&reports);
+        assert_eq!(flaky.len(), 1);
+        assert_eq!(flaky[0].name, "flaky");
+        assert_eq!(
+            (flaky[0].runs, flaky[0].failures, flaky[0].flips),
+            (4, 2, 3)
+        );
+        assert_eq!(flaky[0].failure_rate(), 0.5);
+    }
+
+    #[test]
+    fn parse_build_timing() {
+        let res: BuildActionsRes<BuildTimingAction> = serde_json::from_str(
+            r#"{"actions":[{"_class":"hudson.model.CauseAction"},{},
+                {"_class":"jenkins.metrics.impl.TimeInQueueAction","blockedDurationMillis":0,
+                 "buildableDurationMillis":4200,"queuingDurationMillis":9200,
+                 "waitingDurationMillis":5000,"executorUtilization":0.98,"subTaskCount":2}]}"#,
+        )
+        .unwrap();
+        let timing = res
+            .actions
+            .into_iter()
+            .find(|a| a.class.as_deref() == Some(TIME_IN_QUEUE_ACTION))
+            .unwrap()
+            .timing;
+        assert_eq!(timing.buildable_duration, Duration::from_millis(4200));
+        assert_eq!(timing.queuing_duration, Duration::from_millis(9200));
+        assert_eq!(timing.sub_task_count, 2);
+    }
+
+    #[test]
+    fn match_build_parameters() {
+        let res: BuildHistoryRes = serde_json::from_str(
+            r#"{"builds":[{"number":7,"url":"u/7","building":true,"actions":[
+                {"_class":"hudson.model.ParametersAction","parameters":[
+                    {"name":"VERSION","value":"1.4.2"},{"name":"DRY_RUN","value":false},{"name":"SECRET"}]},
+                {}]}]}"#,
+        )
+        .unwrap();
+        let build = &res.builds[0];
+        let filter = HashMap::from([("VERSION", "1.4.2"), ("DRY_RUN", "false")]);
+        assert!(build.matches_parameters(&filter));
+        assert!(!build.matches_parameters(&HashMap::from([("VERSION", "1.4.3")])));
+    }
+
+    #[test]
+    fn diff_parameters_and_tests() {
+        let from = BTreeMap::from([
+            ("A".to_owned(), TypedParameterValue::String("1".to_owned())),
+            ("B".to_owned(), TypedParameterValue::Bool(true)),
+        ]);
+        let to = BTreeMap::from([
+            ("A".to_owned(), TypedParameterValue::String("2".to_owned())),
+            ("B".to_owned(), TypedParameterValue::Bool(true)),
+            ("C".to_owned(), TypedParameterValue::Password),
+        ]);
+        let changes = ParameterChange::diff(&from, &to);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].name, "A");
+        assert_eq!(changes[1].from, None);
+
+        let report = |statuses: &[(&str, &str)]| TestReportRes {
+            suites: vec![TestSuiteRes {
+                cases: statuses
+                    .iter()
+                    .map(|(name, status)| TestCaseRes {
+                        class_name: "T".to_owned(),
+                        name: name.to_string(),
+                        status: status.to_string(),
+                    })
+                    .collect(),
+            }],
+        };
+        let diff = TestDiff::new(
+            &report(&[("a", "PASSED"), ("b", "FAILED"), ("c", "FAILED")]),
+            &report(&[
+                ("a", "REGRESSION"),
+                ("b", "FIXED"),
+                ("c", "FAILED"),
+                ("d", "FAILED"),
+            ]),
+        );
+        assert_eq!(diff.newly_failing, vec!["T.a", "T.d"]);
+        assert_eq!(diff.fixed, vec!["T.b"]);
+        assert_eq!(diff.still_failing, vec!["T.c"]);
+    }
+
+    #[test]
+    fn pipeline_stage_nodes() {
+        let run: PipelineRun = serde_json::from_str(
+            r#"{"id":"7","name":"7","status":"SUCCESS","startTimeMillis":1,"durationMillis":2,"stages":[
+                {"id":"6","name":"Checkout","execNode":"","status":"SUCCESS","startTimeMillis":1,"durationMillis":1},
+                {"id":"12","name":"Build","execNode":"agent-2","status":"SUCCESS","startTimeMillis":1,"durationMillis":1},
+                {"id":"20","name":"Test","execNode":"agent-2","status":"SUCCESS","startTimeMillis":1,"durationMillis":1}]}"#,
+        )
+        .unwrap();
+        assert_eq!(run.nodes().into_iter().collect::<Vec<_>>(), ["agent-2"]);
+
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":3,"url":"u","building":false,"result":"SUCCESS","duration":1,"timestamp":2,"builtOn":"agent-1"}"#,
+        )
+        .unwrap();
+        assert_eq!(build.built_on.as_deref(), Some("agent-1"));
+    }
+
+    #[test]
+    fn builds_cursor_skips_shifted() {
+        let build = |number| BuildSummary {
+            number,
+            url: format!("http://ci/job/deploy/{}/", number),
+            building: false,
+            result: Some("SUCCESS".to_owned()),
+            duration: Duration::ZERO,
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
+        let mut cursor = BuildsCursor {
+            from: 0,
+            buffer: VecDeque::new(),
+            lowest: None,
+            done: false,
+        };
+        cursor.push_page((101..=200).rev().map(build).collect());
+        assert!(!cursor.done);
+        // two builds started meanwhile, the second page starts at 102 again
+        cursor.push_page(vec![build(102), build(101), build(100), build(99)]);
+        assert!(cursor.done);
+        assert_eq!(cursor.from, 104);
+        let numbers: Vec<i32> = cursor.buffer.iter().map(|b| b.number).collect();
+        assert_eq!(numbers[98..], [102, 101, 100, 99]);
+        assert_eq!(numbers.len(), 102);
+    }
+
+    #[test]
+    fn completion_cursor_catches_up() {
+        let build = |number, building| BuildSummary {
+            number,
+            url: format!("http://ci/job/deploy/{}/", number),
+            building,
+            result: (!building).then(|| "SUCCESS".to_owned()),
+            duration: Duration::ZERO,
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
+        // 10 still running when watching starts
+        let mut cursor =
+            CompletionCursor::starting_at(&[build(11, false), build(10, true), build(9, false)]);
+        assert_eq!(cursor.through, Some(9));
+        assert_eq!(cursor.notified, BTreeSet::from([11]));
+
+        let builds = [build(12, true), build(11, false), build(10, false)];
+        let pending: Vec<_> = cursor.pending(&builds).iter().map(|b| b.number).collect();
+        assert_eq!(pending, [10]);
+        cursor.notified.insert(10);
+        cursor.compact(&builds);
+        assert_eq!(cursor.through, Some(11));
+        assert!(cursor.notified.is_empty());
+    }
+
+    #[cfg(feature = "download")]
+    #[tokio::test]
+    async fn checksum_while_streaming() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        let md5 = Checksum::Md5("9e107d9d372bb6826bd81d3542a419d6".to_owned());
+        let mut out = Vec::new();
+        let mut writer = HashingWriter {
+            inner: &mut out,
+            hasher: md5.hasher().unwrap(),
+        };
+        for chunk in data.chunks(7) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        md5.check(writer.hasher).unwrap();
+        assert_eq!(out, data);
+        assert!(Checksum::Sha256("00".repeat(32)).verify(data).is_err());
+        Checksum::Sha256(
+            "D7A8FBB307D7809469CA9ABCB0082E4F8D5651E46D3CDB762D02D0BF37C9E592".to_owned(),
+        )
+        .verify(data)
+        .unwrap();
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn fingerprint_by_relative_path() {
+        let record = |file_name: &str, hash: &str| FingerprintRes {
+            file_name: file_name.to_owned(),
+            hash: hash.to_owned(),
+        };
+        let records = || {
+            vec![
+                record("api/target/app.jar", "a1"),
+                record("web/target/app.jar", "b2"),
+                record("README.md", "c3"),
+            ]
+        };
+        assert_eq!(
+            fingerprint_of(records(), "web/target/app.jar").as_deref(),
+            Some("b2")
+        );
+        assert_eq!(fingerprint_of(records(), "cli/target/app.jar"), None);
+        assert_eq!(
+            fingerprint_of(records(), "docs/README.md").as_deref(),
+            Some("c3")
+        );
+    }
+
+    #[tokio::test]
+    async fn stage_log_follows_truncated_steps() {
+        use crate::mock::{response, MockServer};
+        let json = [("Content-Type", "application/json")];
+        let server = MockServer::start(vec![
+            response(
+                "200 OK",
+                &json,
+                r#"{"id":"6","stageFlowNodes":[
+                    {"id":"7","name":"Shell Script","status":"SUCCESS","startTimeMillis":1,"durationMillis":2},
+                    {"id":"9","name":"Shell Script","status":"SUCCESS","startTimeMillis":3,"durationMillis":4}]}"#,
+            ),
+            response("200 OK", &json, r#"{"nodeId":"7","text":"build\n","hasMore":false}"#),
+            response("200 OK", &json, r#"{"nodeId":"9","text":"end\n","hasMore":true}"#),
+            response("200 OK", &[], "start\nmiddle\nend\n"),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "bot", "token");
+        let log = cli.get_stage_log("api", 3, "6").await.unwrap();
+        assert_eq!(log, "build\nstart\nmiddle\nend\n");
+        assert!(server.requests()[3]
+            .starts_with("GET /job/api/3/execution/node/9/log/logText/progressiveText?start=0"));
+    }
+
+    #[tokio::test]
+    async fn changes_beyond_last_100_builds() {
+        use crate::mock::{response, MockServer};
+        let page = |numbers: std::ops::RangeInclusive<i32>| {
+            let builds: Vec<String> = numbers
+                .rev()
+                .map(|n| {
+                    format!(
+                        r#"{{"number":{},"changeSets":[{{"items":[{{"msg":"c{}"}}]}}]}}"#,
+                        n, n
+                    )
+                })
+                .collect();
+            response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                format!(r#"{{"allBuilds":[{}]}}"#, builds.join(",")),
+            )
+        };
+        let server = MockServer::start(vec![page(51..=150), page(1..=50)]).await;
+        let cli = Jenkins::new(&server.url, "bot", "token");
+        let changes = cli.get_changes_between("api", 20, 120).await.unwrap();
+        let msgs: Vec<String> = changes.into_iter().map(|c| c.msg).collect();
+        assert_eq!(
+            msgs,
+            (21..=120).map(|n| format!("c{}", n)).collect::<Vec<_>>()
+        );
+        let requests = server.requests();
+        assert!(requests[1].contains("{100,200}"), "{}", requests[1]);
+    }
+}
@@ -0,0 +1,296 @@
+//! [Jenkins CLI](https://www.jenkins.io/doc/book/managing/cli/) over HTTP
+//!
+//! Speaks the plain CLI protocol through the full-duplex `/cli` endpoint (the
+//! transport of `jenkins-cli.jar -http`), for commands not exposed via REST.
+//!
+//! The websocket transport `/cli/ws` of `jenkins-cli.jar -webSocket` is not
+//! implemented. It carries the same frames but needs a websocket client
+//! beside reqwest, while the `/cli` endpoint goes through the client like
+//! every other request, with its credentials, hooks and connection options.
+//! The price is two concurrent requests, one downloading and one uploading,
+//! which a proxy in front of Jenkins must not buffer and a load balancer must
+//! route to the same controller.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use log::{info, warn};
+
+use crate::{version::MinVersion, Error, Jenkins};
+
+/// Frame op codes of `hudson.cli.PlainCLIProtocol`
+mod op {
+    pub const ARG: u8 = 0;
+    pub const LOCALE: u8 = 1;
+    pub const ENCODING: u8 = 2;
+    pub const START: u8 = 3;
+    pub const EXIT: u8 = 4;
+    pub const STDIN: u8 = 5;
+    pub const END_STDIN: u8 = 6;
+    pub const STDOUT: u8 = 7;
+    pub const STDERR: u8 = 8;
+}
+
+/// Command run by [`Jenkins::cli`]
+#[derive(Debug, Clone)]
+pub enum CliCommand {
+    /// `reload-configuration`
+    ReloadConfiguration,
+    /// `install-plugin` uploading the `.hpi`/`.jpi` content
+    InstallPlugin {
+        content: Bytes,
+        deploy: bool,
+        restart: bool,
+    },
+    /// `groovy =` running a system groovy script
+    Groovy { script: String },
+    /// `who-am-i`
+    WhoAmI,
+    /// any other command with its arguments and optional stdin
+    Custom {
+        args: Vec<String>,
+        stdin: Option<Bytes>,
+    },
+}
+
+impl CliCommand {
+    fn args(&self) -> Vec<String> {
+        let args: Vec<&str> = match self {
+            CliCommand::ReloadConfiguration => vec!["reload-configuration"],
+            CliCommand::InstallPlugin {
+                deploy, restart, ..
+            } => {
+                let mut args = vec!["install-plugin", "="];
+                if *deploy {
+                    args.push("-deploy");
+                }
+                if *restart {
+                    args.push("-restart");
+                }
+                args
+            }
+            CliCommand::Groovy { .. } => vec!["groovy", "="],
+            CliCommand::WhoAmI => vec!["who-am-i"],
+            CliCommand::Custom { args, .. } => return args.clone(),
+        };
+        args.into_iter().map(str::to_owned).collect()
+    }
+
+    fn stdin(&self) -> Option<&[u8]> {
+        match self {
+            CliCommand::InstallPlugin { content, .. } => Some(content),
+            CliCommand::Groovy { script } => Some(script.as_bytes()),
+            CliCommand::Custom {
+                stdin: Some(stdin), ..
+            } => Some(stdin),
+            _ => None,
+        }
+    }
+}
+
+/// Result of [`Jenkins::cli`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliOutput {
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CliOutput {
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+}
+
+fn frame(buf: &mut Vec<u8>, op: u8, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.push(op);
+    buf.extend_from_slice(data);
+}
+
+/// Java `DataOutput.writeUTF`: length prefixed modified UTF-8
+///
+/// Fails like Java's `UTFDataFormatException` past 65535 encoded bytes.
+fn java_utf(s: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(s.len() + 2);
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => data.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                data.push(0xC0 | (unit >> 6) as u8);
+                data.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                data.push(0xE0 | (unit >> 12) as u8);
+                data.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                data.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    let Ok(len) = u16::try_from(data.len()) else {
+        bail!(Error::APIError(format!(
+            "cli argument too long: {} encoded bytes",
+            data.len()
+        )))
+    };
+    let mut buf = len.to_be_bytes().to_vec();
+    buf.extend(data);
+    Ok(buf)
+}
+
+fn upload_body(cmd: &CliCommand) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    for arg in cmd.args() {
+        frame(&mut buf, op::ARG, &java_utf(&arg)?);
+    }
+    frame(&mut buf, op::ENCODING, &java_utf("UTF-8")?);
+    frame(&mut buf, op::LOCALE, &java_utf("en")?);
+    frame(&mut buf, op::START, &[]);
+    if let Some(stdin) = cmd.stdin() {
+        for chunk in stdin.chunks(8192) {
+            frame(&mut buf, op::STDIN, chunk);
+        }
+    }
+    frame(&mut buf, op::END_STDIN, &[]);
+    Ok(buf)
+}
+
+/// Parse complete frames from `buf` into `out`, returns `true` on `EXIT`
+fn read_frames(buf: &mut Vec<u8>, out: &mut CliOutput) -> bool {
+    let mut pos = 0;
+    let mut exited = false;
+    while buf.len() - pos >= 5 {
+        let len = u32::from_be_bytes(buf[pos..pos + 4].try_into().expect("4 bytes")) as usize;
+        if buf.len() - pos - 5 < len {
+            break;
+        }
+        let data = &buf[pos + 5..pos + 5 + len];
+        match buf[pos + 4] {
+            op::STDOUT => out.stdout.extend_from_slice(data),
+            op::STDERR => out.stderr.extend_from_slice(data),
+            op::EXIT if len >= 4 => {
+                out.exit_code = i32::from_be_bytes(data[..4].try_into().expect("4 bytes"));
+                exited = true;
+            }
+            _ => {}
+        }
+        pos += 5 + len;
+    }
+    buf.drain(..pos);
+    exited
+}
+
+fn session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let hex = format!("{:032x}", nanos ^ ((std::process::id() as u128) << 96));
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl Jenkins {
+    /// Run a [Jenkins CLI](https://www.jenkins.io/doc/book/managing/cli/) command
+    ///
+    /// ## Arguments
+    ///
+    /// * `cmd` - command to run
+    ///
+    pub async fn cli(&self, cmd: &CliCommand) -> Result<CliOutput> {
+        self.require_version(MinVersion::CLI_HTTP).await?;
+        let body = upload_body(cmd)?;
+        let url = format!("{}/cli?remoting=false", self.url);
+        let session = session_id();
+        let mut download = self
+            .send(
+                self.post(&url)
+                    .header("Session", &session)
+                    .header("Side", "download"),
+            )
+            .await?;
+        if !download.status().is_success() {
+            warn!("cli download - res={:?}", download);
+            bail!(Error::APIError(format!(
+                "http status: {}",
+                download.status()
+            )))
+        }
+
+        let upload = self.send(
+            self.post(&url)
+                .header("Session", &session)
+                .header("Side", "upload")
+                .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+                .body(body),
+        );
+        let read = async {
+            let mut buf = Vec::new();
+            let mut hello = false;
+            let mut out = CliOutput::default();
+            while let Some(chunk) = self
+                .until_shutdown(download.chunk())
+                .await?
+                .map_err(Error::NetworkError)?
+            {
+                buf.extend_from_slice(&chunk);
+                if !hello {
+                    if buf.first() != Some(&0) {
+                        bail!(Error::APIError("cli: not talking to jenkins".to_owned()))
+                    }
+                    buf.remove(0);
+                    hello = true;
+                }
+                if read_frames(&mut buf, &mut out) {
+                    return Ok(out);
+                }
+            }
+            bail!(Error::APIError(
+                "cli: connection closed before exit".to_owned()
+            ))
+        };
+        let (upload, out) = tokio::join!(upload, read);
+        upload?;
+        let out = out?;
+        info!("cli - args={:?}, exit={}", cmd.args(), out.exit_code);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_roundtrip() {
+        let mut buf = Vec::new();
+        frame(&mut buf, op::STDOUT, b"hello ");
+        frame(&mut buf, op::STDERR, b"warn");
+        frame(&mut buf, op::STDOUT, b"world");
+        frame(&mut buf, op::EXIT, &3i32.to_be_bytes());
+        let mut partial = buf[..7].to_vec();
+        let mut out = CliOutput::default();
+        assert!(!read_frames(&mut partial, &mut out));
+        assert_eq!(partial.len(), 7);
+        assert!(read_frames(&mut buf, &mut out));
+        assert_eq!(out.stdout, b"hello world");
+        assert_eq!(out.stderr, b"warn");
+        assert_eq!(out.exit_code, 3);
+    }
+
+    #[test]
+    fn java_modified_utf8() {
+        assert_eq!(java_utf("ab").unwrap(), vec![0, 2, b'a', b'b']);
+        assert_eq!(java_utf("\0").unwrap(), vec![0, 2, 0xC0, 0x80]);
+        assert_eq!(java_utf(&"a".repeat(65535)).unwrap().len(), 65537);
+        // 3 bytes each
+        assert!(java_utf(&"\u{20ac}".repeat(21846)).is_err());
+    }
+}
@@ -0,0 +1,1136 @@
+//! [`Jenkins`] client: construction, request hooks and the shared http helpers
+
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use reqwest::{Method, RequestBuilder, Response, ResponseBuilderExt, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use tokio::sync::Semaphore;
+
+use crate::{
+    join::{try_join_all, BoxFuture},
+    shutdown::Shutdown,
+    strict,
+    version::{Crumb, JenkinsVersion},
+    Error,
+};
+
+/// Request info passed to [`JenkinsBuilder::on_request`] hook
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    pub method: Method,
+    pub url: Url,
+}
+
+/// Response info passed to [`JenkinsBuilder::on_response`] hook
+#[derive(Debug, Clone)]
+pub struct ResponseEvent {
+    pub method: Method,
+    pub url: Url,
+    /// `None` if the request failed without a response
+    pub status: Option<StatusCode>,
+    pub duration: Duration,
+}
+
+/// Queue item location synthesized for triggers in dry-run mode
+const DRY_RUN_QUEUE_ITEM: &str = "queue/item/0/";
+
+pub(crate) const DRY_RUN_QUEUE_ITEM_ID: u64 = 0;
+/// In-flight requests of batches issued on behalf of the caller
+pub(crate) const FETCH_CONCURRENCY: usize = 8;
+
+type RequestHook = Arc<dyn Fn(&RequestEvent) + Send + Sync>;
+
+type ResponseHook = Arc<dyn Fn(&ResponseEvent) + Send + Sync>;
+
+type ClientConfig = Box<dyn Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync>;
+
+/// resolved addresses by host, with when they were resolved
+type DnsEntries = HashMap<String, (Instant, Vec<SocketAddr>)>;
+
+/// User and password or api token for basic auth
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(user: &str, password: &str) -> Credentials {
+        Credentials {
+            user: user.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+}
+
+// keep tokens out of logs
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("user", &self.user)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+/// Source of [`Credentials`], consulted per request
+///
+/// Implemented by [`Credentials`] for fixed values and closures returning
+/// [`Credentials`]. The `credentials` feature adds `EnvCredentials`,
+/// `RotatingCredentials` and the Vault and AWS secret stores.
+pub trait CredentialsProvider: Send + Sync {
+    fn credentials(&self) -> Credentials;
+
+    /// Called when Jenkins rejects the credentials with `401`, returns whether
+    /// new ones were loaded and the request is worth retrying
+    fn refresh(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async { false })
+    }
+}
+
+impl CredentialsProvider for Credentials {
+    fn credentials(&self) -> Credentials {
+        self.clone()
+    }
+}
+
+impl<F: Fn() -> Credentials + Send + Sync> CredentialsProvider for F {
+    fn credentials(&self) -> Credentials {
+        self()
+    }
+}
+
+/// [Jenkins : Remote access API](https://wiki.jenkins.io/display/JENKINS/Remote+access+API)
+///
+/// `Send + Sync` and cheap to clone (the connection pool, url and credentials
+/// are shared), so one instance can live in application state and be used
+/// concurrently from request handlers or spawned tasks:
+///
+/// ```no_run
+/// # async fn f() -> anyhow::Result<()> {
+/// let cli = jenkins_rs::Jenkins::new("https://ci.example.com", "bot", "token");
+/// let handles: Vec<_> = ["api", "web"]
+///     .into_iter()
+///     .map(|job| {
+///         let cli = cli.clone();
+///         tokio::spawn(async move { cli.get_build(job, 1).await })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.await??;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Jenkins {
+    pub(crate) hc: reqwest::Client,
+    /// same as `hc` but does not follow redirects, to resolve external artifact urls
+    pub(crate) hc_no_redirect: reqwest::Client,
+    pub(crate) url: Arc<str>,
+    pub(crate) credentials: Arc<dyn CredentialsProvider>,
+    pub(crate) on_request: Option<RequestHook>,
+    pub(crate) on_response: Option<ResponseHook>,
+    pub(crate) dry_run: bool,
+    pub(crate) strict: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) compression: Compression,
+    pub(crate) options: RequestOptions,
+    /// cached by [`Jenkins::server_version`]
+    pub(crate) server_version: Arc<tokio::sync::OnceCell<JenkinsVersion>>,
+    /// fetched by [`Jenkins::warm_up`] or once a request is rejected for
+    /// lack of it, sent with every non `GET` request
+    pub(crate) crumb: Arc<RwLock<Option<Crumb>>>,
+    pub(crate) shutdown: Shutdown,
+    #[cfg(feature = "otel")]
+    pub(crate) trace: Option<crate::trace::TracePropagation>,
+    #[cfg(feature = "fault-injection")]
+    pub(crate) faults: Option<Arc<crate::fault_injection::FaultInjector>>,
+    #[cfg(feature = "replay")]
+    pub(crate) cassette: Option<Arc<crate::replay::Cassette>>,
+}
+
+/// Per-call overrides applied to requests, see [`Jenkins::with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// total timeout of each request
+    pub timeout: Option<Duration>,
+    /// extra headers sent with each request
+    pub headers: reqwest::header::HeaderMap,
+}
+
+/// HTTP compression, see [`JenkinsBuilder::compression`]
+///
+/// Like the connection options, response compression is left to a
+/// [`JenkinsBuilder::client`] when one is passed.
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compression {
+    /// accept gzip and brotli responses, decompressed while streaming
+    pub responses: bool,
+    /// gzip `config.xml` uploads of at least this many bytes, needs a reverse
+    /// proxy or servlet container accepting `Content-Encoding: gzip` requests
+    pub gzip_uploads_from: Option<usize>,
+}
+
+/// Builder of [`Jenkins`] for options beyond [`Jenkins::new`]
+pub struct JenkinsBuilder {
+    url: String,
+    credentials: Arc<dyn CredentialsProvider>,
+    on_request: Option<RequestHook>,
+    on_response: Option<ResponseHook>,
+    dry_run: bool,
+    strict: bool,
+    #[cfg(feature = "compression")]
+    compression: Compression,
+    pool: PoolOptions,
+    #[cfg(feature = "otel")]
+    trace: Option<crate::trace::TracePropagation>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<crate::fault_injection::FaultInjection>,
+    #[cfg(feature = "replay")]
+    cassette: Option<crate::replay::Cassette>,
+    client: Option<reqwest::Client>,
+    no_redirect_client: Option<reqwest::Client>,
+    configure_client: Option<ClientConfig>,
+}
+
+/// Connection tuning of the client built by [`JenkinsBuilder::build`]
+#[derive(Debug, Clone, Default)]
+struct PoolOptions {
+    http2_prior_knowledge: bool,
+    idle_timeout: Option<Option<Duration>>,
+    max_idle_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+    dns_cache_ttl: Option<Duration>,
+}
+
+/// Resolver remembering addresses for a fixed time instead of asking the
+/// system resolver for every new connection
+#[derive(Clone)]
+struct DnsCache {
+    ttl: Duration,
+    entries: Arc<Mutex<DnsEntries>>,
+}
+
+impl reqwest::dns::Resolve for DnsCache {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let cache = self.clone();
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            let cached = cache
+                .entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&host)
+                .filter(|(at, _)| at.elapsed() < cache.ttl)
+                .map(|(_, addrs)| addrs.clone());
+            let addrs = match cached {
+                Some(addrs) => addrs,
+                None => {
+                    let lookup = host.clone();
+                    // port 0 is replaced by the one of the url
+                    let addrs: Vec<SocketAddr> =
+                        tokio::task::spawn_blocking(move || (lookup.as_str(), 0).to_socket_addrs())
+                            .await??
+                            .collect();
+                    cache
+                        .entries
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(host, (Instant::now(), addrs.clone()));
+                    addrs
+                }
+            };
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+impl JenkinsBuilder {
+    /// Called before every request sent to Jenkins, e.g. for audit logging
+    pub fn on_request(mut self, hook: impl Fn(&RequestEvent) + Send + Sync + 'static) -> Self {
+        self.on_request = Some(Arc::new(hook));
+        self
+    }
+
+    /// Called after every request sent to Jenkins with status and duration
+    pub fn on_response(mut self, hook: impl Fn(&ResponseEvent) + Send + Sync + 'static) -> Self {
+        self.on_response = Some(Arc::new(hook));
+        self
+    }
+
+    /// Log mutating (non `GET`) requests and return synthesized success results
+    /// instead of sending them to Jenkins
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Fail on response fields the models don't know, like `deny_unknown_fields`
+    ///
+    /// Off by default. Meant for CI against a new Jenkins or plugin version,
+    /// to notice API drift before lenient parsing silently drops data.
+    /// Only models covering every field of their response are checked, and
+    /// `_class` is always accepted.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Take user and token from `provider` on every request instead of the
+    /// fixed ones passed to [`Jenkins::builder`]
+    pub fn credentials(mut self, provider: impl CredentialsProvider + 'static) -> Self {
+        self.credentials = Arc::new(provider);
+        self
+    }
+
+    /// Compress responses and large uploads, off by default
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Speak HTTP/2 without negotiating it first, for controllers behind an h2c proxy
+    ///
+    /// Like the other connection options, ignored when passing a [`JenkinsBuilder::client`].
+    pub fn http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.pool.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Close pooled connections idle for this long, `None` to keep them
+    ///
+    /// Set it below the idle timeout of load balancers in between, so the
+    /// client drops connections before the balancer resets them mid-request.
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep at most `max` idle connections per host
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool.max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Send TCP keepalive probes on idle connections at this interval, 15s by default
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.pool.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Cache resolved addresses of Jenkins for `ttl`, so triggers after
+    /// [`Jenkins::warm_up`] don't wait for a slow resolver
+    pub fn dns_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.pool.dns_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Pass the trace context from `provider` to every triggered build
+    #[cfg(feature = "otel")]
+    pub fn trace_context(
+        mut self,
+        provider: impl crate::trace::TraceContextProvider + 'static,
+        propagation: crate::trace::Propagation,
+    ) -> Self {
+        self.trace = Some(crate::trace::TracePropagation {
+            provider: Arc::new(provider),
+            propagation,
+        });
+        self
+    }
+
+    /// Make requests fail, slow down or lose part of their body at random
+    ///
+    /// For testing code built on the client, never enable it in production.
+    #[cfg(feature = "fault-injection")]
+    pub fn fault_injection(mut self, faults: crate::fault_injection::FaultInjection) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Record all interactions to a cassette file, or answer them from one
+    #[cfg(feature = "replay")]
+    pub fn cassette(mut self, cassette: crate::replay::Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Send requests through `client` instead of a new one, to share its connection pool
+    ///
+    /// Artifact redirects are resolved by a client of the other options
+    /// unless [`JenkinsBuilder::no_redirect_client`] is passed as well.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Resolve artifact redirects through `client`, the counterpart of
+    /// [`JenkinsBuilder::client`] with the same proxy and TLS settings and
+    /// redirects turned off
+    pub fn no_redirect_client(mut self, client: reqwest::Client) -> Self {
+        self.no_redirect_client = Some(client);
+        self
+    }
+
+    /// Adjust the clients built by [`JenkinsBuilder::build`], e.g. for a
+    /// proxy or a private root certificate
+    ///
+    /// Applied to the client of all requests and to the one resolving artifact
+    /// redirects, which only differs by not following them.
+    pub fn configure_client(
+        mut self,
+        config: impl Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync + 'static,
+    ) -> Self {
+        self.configure_client = Some(Box::new(config));
+        self
+    }
+
+    pub fn build(self) -> Jenkins {
+        let pool = self.pool;
+        let dns_cache = pool.dns_cache_ttl.map(|ttl| DnsCache {
+            ttl,
+            entries: Arc::default(),
+        });
+        let new_client = |redirect: reqwest::redirect::Policy| {
+            let mut builder = reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(3))
+                .redirect(redirect);
+            #[cfg(feature = "compression")]
+            {
+                builder = builder
+                    .gzip(self.compression.responses)
+                    .brotli(self.compression.responses);
+            }
+            if let Some(interval) = pool.tcp_keepalive {
+                builder = builder.tcp_keepalive(interval);
+            }
+            if pool.http2_prior_knowledge {
+                builder = builder.http2_prior_knowledge();
+            }
+            if let Some(timeout) = pool.idle_timeout {
+                builder = builder.pool_idle_timeout(timeout);
+            }
+            if let Some(max) = pool.max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max);
+            }
+            if let Some(cache) = &dns_cache {
+                builder = builder.dns_resolver(Arc::new(cache.clone()));
+            }
+            if let Some(config) = &self.configure_client {
+                builder = config(builder);
+            }
+            builder.build().expect("failed to init http client")
+        };
+        let hc = match self.client {
+            Some(client) => client,
+            None => new_client(reqwest::redirect::Policy::default()),
+        };
+        let hc_no_redirect = match self.no_redirect_client {
+            Some(client) => client,
+            None => new_client(reqwest::redirect::Policy::none()),
+        };
+        Jenkins {
+            hc,
+            hc_no_redirect,
+            url: self.url.into(),
+            credentials: self.credentials,
+            on_request: self.on_request,
+            on_response: self.on_response,
+            dry_run: self.dry_run,
+            strict: self.strict,
+            #[cfg(feature = "compression")]
+            compression: self.compression,
+            options: RequestOptions::default(),
+            server_version: Arc::default(),
+            crumb: Arc::default(),
+            shutdown: Shutdown::default(),
+            #[cfg(feature = "otel")]
+            trace: self.trace,
+            #[cfg(feature = "fault-injection")]
+            faults: self
+                .faults
+                .map(|f| Arc::new(crate::fault_injection::FaultInjector::new(f))),
+            #[cfg(feature = "replay")]
+            cassette: self.cassette.map(Arc::new),
+        }
+    }
+}
+
+/// `{base}/{path}/api/json?tree={tree}`, `path` may also be an absolute url
+fn api_json_url(base: &str, path: &str, tree: &str) -> Result<Url> {
+    let base = Url::parse(&format!("{}/", base))?;
+    let mut url = base.join(path.trim_start_matches('/'))?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    let mut url = url.join("api/json")?;
+    if !tree.is_empty() {
+        url.query_pairs_mut().append_pair("tree", tree);
+    }
+    Ok(url)
+}
+
+/// Make the encoding named by the XML declaration match the UTF-8 body
+///
+/// Jenkins decodes the body by the declared encoding, so a config saved with
+/// e.g. `encoding='ISO-8859-1'` would garble non-ASCII text once sent as UTF-8.
+fn utf8_declaration(xml: &str) -> String {
+    let xml = xml.trim_start_matches('\u{feff}');
+    let Some(end) = xml.strip_prefix("<?xml").and_then(|_| xml.find("?>")) else {
+        return xml.to_owned();
+    };
+    let decl = &xml[..end];
+    let Some(start) = decl.find("encoding=") else {
+        return xml.to_owned();
+    };
+    let value = &decl[start + "encoding=".len()..];
+    let Some(quote) = value.chars().next().filter(|c| *c == '\'' || *c == '"') else {
+        return xml.to_owned();
+    };
+    let Some(len) = value[1..].find(quote) else {
+        return xml.to_owned();
+    };
+    if value[1..len + 1].eq_ignore_ascii_case("utf-8") {
+        return xml.to_owned();
+    }
+    let value_start = start + "encoding=".len() + 1;
+    format!("{}UTF-8{}", &xml[..value_start], &xml[value_start + len..])
+}
+
+#[cfg(feature = "compression")]
+fn gzip(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .expect("gzip into memory")
+}
+
+/// Minimal `multipart/form-data` body builder
+pub(crate) struct Multipart {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Multipart {
+    pub(crate) fn new() -> Multipart {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Multipart {
+            boundary: format!("----jenkins-rs-{:x}", nanos),
+            body: Vec::new(),
+        }
+    }
+
+    pub(crate) fn text(&mut self, name: &str, value: &str) {
+        self.body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                self.boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+
+    pub(crate) fn file(&mut self, name: &str, file_name: &str, content: &[u8]) {
+        self.body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                self.boundary, name, file_name
+            )
+            .as_bytes(),
+        );
+        self.body.extend_from_slice(content);
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    pub(crate) fn apply(mut self, req: RequestBuilder) -> RequestBuilder {
+        self.body
+            .extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        req.header(
+            reqwest::header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", self.boundary),
+        )
+        .body(self.body)
+    }
+}
+
+impl Jenkins {
+    /// Create Jenkins instance
+    ///
+    /// ## Arguments
+    ///
+    /// * `password` - password or api token of user
+    ///
+    pub fn new(url: &str, user: &str, password: &str) -> Jenkins {
+        Jenkins::builder(url, user, password).build()
+    }
+
+    /// Create [`JenkinsBuilder`] to customize the instance
+    ///
+    /// ## Arguments
+    ///
+    /// * `password` - password or api token of user
+    ///
+    pub fn builder(url: &str, user: &str, password: &str) -> JenkinsBuilder {
+        JenkinsBuilder {
+            // urls are built as `{url}/job/...`
+            url: url.trim_end_matches('/').to_owned(),
+            credentials: Arc::new(Credentials::new(user, password)),
+            on_request: None,
+            on_response: None,
+            dry_run: false,
+            strict: false,
+            #[cfg(feature = "compression")]
+            compression: Compression::default(),
+            pool: PoolOptions::default(),
+            #[cfg(feature = "otel")]
+            trace: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+            #[cfg(feature = "replay")]
+            cassette: None,
+            client: None,
+            no_redirect_client: None,
+            configure_client: None,
+        }
+    }
+
+    /// Create Jenkins instance on an existing client
+    ///
+    /// Applications talking to many controllers can share one connection pool
+    /// and TLS state this way.
+    ///
+    /// ## Arguments
+    ///
+    /// * `password` - password or api token of user
+    ///
+    pub fn with_client(client: reqwest::Client, url: &str, user: &str, password: &str) -> Jenkins {
+        Jenkins::builder(url, user, password).client(client).build()
+    }
+
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Scoped instance sharing the connection pool whose requests apply `options`
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use std::time::Duration;
+    /// let build = cli
+    ///     .with_timeout(Duration::from_secs(5))
+    ///     .get_build("deploy", 42)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options(&self, options: RequestOptions) -> Jenkins {
+        Jenkins {
+            options,
+            ..self.clone()
+        }
+    }
+
+    /// Shortcut of [`Jenkins::with_options`] overriding timeout only
+    pub fn with_timeout(&self, timeout: Duration) -> Jenkins {
+        self.with_options(RequestOptions {
+            timeout: Some(timeout),
+            headers: self.options.headers.clone(),
+        })
+    }
+
+    pub(crate) fn request_via(
+        &self,
+        hc: &reqwest::Client,
+        method: Method,
+        url: &str,
+    ) -> RequestBuilder {
+        let credentials = self.credentials.credentials();
+        let crumb = match method {
+            Method::GET => None,
+            _ => self.crumb.read().unwrap_or_else(|e| e.into_inner()).clone(),
+        };
+        let mut req = hc
+            .request(method, url)
+            .basic_auth(&credentials.user, Some(&credentials.password))
+            .headers(self.options.headers.clone());
+        if let Some(crumb) = crumb {
+            req = req.header(&crumb.field, &crumb.value);
+        }
+        if let Some(timeout) = self.options.timeout {
+            req = req.timeout(timeout);
+        }
+        req
+    }
+
+    pub(crate) fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.request_via(&self.hc, method, url)
+    }
+
+    pub(crate) fn post(&self, url: &str) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub(crate) fn get(&self, url: &str) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    /// `POST` an XML document like `config.xml`, labelled as UTF-8
+    pub(crate) fn post_xml(&self, url: &str, body: &str) -> RequestBuilder {
+        let body = utf8_declaration(body);
+        let req = self.post(url).header(
+            reqwest::header::CONTENT_TYPE,
+            "application/xml; charset=UTF-8",
+        );
+        #[cfg(feature = "compression")]
+        if let Some(min) = self.compression.gzip_uploads_from {
+            if body.len() >= min {
+                return req
+                    .header(reqwest::header::CONTENT_ENCODING, "gzip")
+                    .body(gzip(body.as_bytes()));
+            }
+        }
+        req.body(body)
+    }
+
+    pub(crate) async fn send(&self, req: RequestBuilder) -> Result<Response> {
+        self.send_via(&self.hc, req).await
+    }
+
+    /// Send through `hc`, failing with [`Error::NetworkError`], or with
+    /// [`Error::Shutdown`] once the client is shut down, even mid-request
+    pub(crate) async fn send_via(
+        &self,
+        hc: &reqwest::Client,
+        req: RequestBuilder,
+    ) -> Result<Response> {
+        Ok(self
+            .until_shutdown(self.exchange(hc, req))
+            .await?
+            .map_err(Error::NetworkError)?)
+    }
+
+    async fn exchange(
+        &self,
+        hc: &reqwest::Client,
+        req: RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        let req = req.build()?;
+        let method = req.method().clone();
+        let url = req.url().clone();
+        if let Some(hook) = &self.on_request {
+            hook(&RequestEvent {
+                method: method.clone(),
+                url: url.clone(),
+            });
+        }
+        let start = Instant::now();
+        #[cfg(feature = "fault-injection")]
+        let injected = match &self.faults {
+            Some(faults) => faults.before(&method, &url).await,
+            None => None,
+        };
+        #[cfg(not(feature = "fault-injection"))]
+        let injected: Option<Response> = None;
+        #[cfg(feature = "replay")]
+        let request_body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .filter(|b| !b.is_empty())
+            .map(|b| String::from_utf8_lossy(b).into_owned());
+        #[cfg(feature = "replay")]
+        let injected = match (injected, &self.cassette) {
+            (None, Some(cassette)) => cassette.play(&method, &url, request_body.as_deref()).await,
+            (injected, _) => injected,
+        };
+        let res = if self.dry_run && method != Method::GET && method != Method::HEAD {
+            info!("dry-run {} {}", method, url);
+            Ok(self.dry_run_response())
+        } else if let Some(res) = injected {
+            Ok(res)
+        } else {
+            // streamed bodies can't be sent twice
+            let retry = req.try_clone();
+            let crumb_retry = req.try_clone();
+            let res = hc.execute(req).await;
+            let res = match (res, retry) {
+                (Ok(res), Some(mut retry))
+                    if res.status() == StatusCode::UNAUTHORIZED
+                        && self.credentials.refresh().await =>
+                {
+                    warn!(
+                        "unauthorized, retrying with refreshed credentials - url={}",
+                        url
+                    );
+                    self.authorize(hc, &mut retry)?;
+                    hc.execute(retry).await
+                }
+                (res, _) => res,
+            };
+            match (res, crumb_retry) {
+                (Ok(res), Some(retry))
+                    if res.status() == StatusCode::FORBIDDEN && method != Method::GET =>
+                {
+                    self.retry_with_new_crumb(hc, res, retry).await
+                }
+                (res, _) => res,
+            }
+        };
+        if let Some(hook) = &self.on_response {
+            hook(&ResponseEvent {
+                method: method.clone(),
+                url: url.clone(),
+                status: res.as_ref().ok().map(|r| r.status()),
+                duration: start.elapsed(),
+            });
+        }
+        #[cfg(feature = "fault-injection")]
+        let res = match (&self.faults, res) {
+            (Some(faults), Ok(res)) => faults.after(res).await,
+            (_, res) => res,
+        };
+        #[cfg(feature = "replay")]
+        let res = match (&self.cassette, res) {
+            (Some(cassette), Ok(res)) => {
+                cassette
+                    .record_response(&method, &url, request_body, res)
+                    .await
+            }
+            (_, res) => res,
+        };
+        res
+    }
+
+    /// Replace the basic auth header of `req` by the current credentials
+    fn authorize(
+        &self,
+        hc: &reqwest::Client,
+        req: &mut reqwest::Request,
+    ) -> Result<(), reqwest::Error> {
+        let credentials = self.credentials.credentials();
+        let auth = hc
+            .get(req.url().clone())
+            .basic_auth(&credentials.user, Some(&credentials.password))
+            .build()?
+            .headers_mut()
+            .remove(reqwest::header::AUTHORIZATION);
+        if let Some(auth) = auth {
+            req.headers_mut()
+                .insert(reqwest::header::AUTHORIZATION, auth);
+        }
+        Ok(())
+    }
+
+    /// Retry once with a new crumb if Jenkins rejected the request for its
+    /// crumb, e.g. the cached one expired with a restart of the controller
+    ///
+    /// Boxed since `get_crumb_via` sends through here again.
+    fn retry_with_new_crumb<'a>(
+        &'a self,
+        hc: &'a reqwest::Client,
+        res: Response,
+        mut retry: reqwest::Request,
+    ) -> BoxFuture<'a, Result<Response, reqwest::Error>> {
+        Box::pin(async move {
+            let (status, version, headers) = (res.status(), res.version(), res.headers().clone());
+            let url = res.url().clone();
+            let body = res.bytes().await?;
+            // `No valid crumb was included in the request`
+            if String::from_utf8_lossy(&body)
+                .to_lowercase()
+                .contains("crumb")
+            {
+                let stale = self.crumb.write().unwrap_or_else(|e| e.into_inner()).take();
+                match self.get_crumb_via(hc).await {
+                    Ok(crumb) => {
+                        warn!("crumb rejected, retrying with a new one - url={}", url);
+                        if let Some(stale) = stale {
+                            retry.headers_mut().remove(stale.field.as_str());
+                        }
+                        if let Some(crumb) = &crumb {
+                            if let (Ok(field), Ok(value)) = (
+                                reqwest::header::HeaderName::from_bytes(crumb.field.as_bytes()),
+                                reqwest::header::HeaderValue::from_str(&crumb.value),
+                            ) {
+                                retry.headers_mut().insert(field, value);
+                            }
+                        }
+                        *self.crumb.write().unwrap_or_else(|e| e.into_inner()) = crumb;
+                        self.authorize(hc, &mut retry)?;
+                        return hc.execute(retry).await;
+                    }
+                    Err(e) => warn!("refetch crumb - url={}, err={}", url, e),
+                }
+            }
+            let mut copy = http::Response::builder()
+                .status(status)
+                .version(version)
+                .url(url)
+                .body(body)
+                .expect("forbidden response");
+            *copy.headers_mut() = headers;
+            Ok(copy.into())
+        })
+    }
+
+    pub(crate) fn dry_run_response(&self) -> Response {
+        http::Response::builder()
+            .status(StatusCode::CREATED)
+            .header(
+                reqwest::header::LOCATION,
+                format!("{}/{}", self.url, DRY_RUN_QUEUE_ITEM),
+            )
+            .body("")
+            .expect("dry-run response")
+            .into()
+    }
+
+    pub(crate) async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let res = self.send(self.get(url)).await?;
+        if !res.status().is_success() {
+            warn!("Get {}: res={:?}", url, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        self.json_body(res)
+            .await
+            .with_context(|| format!("parse {} payload as json", url))
+    }
+
+    /// Parse a json response, see [`Error::Deserialize`] and [`JenkinsBuilder::strict`]
+    pub(crate) async fn json_body<T: DeserializeOwned>(&self, res: Response) -> Result<T, Error> {
+        let body = res.bytes().await.map_err(Error::NetworkError)?;
+        let t = serde_json::from_slice(&body).map_err(|e| Error::deserialize(&body, e))?;
+        if self.strict {
+            let value: serde_json::Value =
+                serde_json::from_slice(&body).map_err(|e| Error::deserialize(&body, e))?;
+            let (_, unknown) = strict::from_value_tracked::<T>(&value)
+                .map_err(|e| Error::deserialize(&body, e))?;
+            if !unknown.is_empty() {
+                return Err(Error::UnknownFields(unknown));
+            }
+        }
+        Ok(t)
+    }
+
+    /// Concurrently GET `api/json` of many objects with the same `tree` filter
+    ///
+    /// Results keep the order of `paths`, the first failure aborts the batch.
+    ///
+    /// ## Arguments
+    ///
+    /// * `paths` - object paths relative to jenkins url (e.g. `job/a/12`) or absolute urls
+    /// * `tree` - tree filter applied to every request, empty for none
+    /// * `concurrency` - max in-flight requests
+    ///
+    pub async fn fetch_many<T: DeserializeOwned>(
+        &self,
+        paths: &[&str],
+        tree: &str,
+        concurrency: usize,
+    ) -> Result<Vec<T>> {
+        let sem = Semaphore::new(concurrency.max(1));
+        let res = try_join_all(paths.iter().map(|path| async {
+            let url = api_json_url(&self.url, path, tree)?;
+            let _permit = sem.acquire().await?;
+            self.get_json::<T>(url.as_str()).await
+        }))
+        .await?;
+        info!("fetch many - count={}, tree={}", res.len(), tree);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn multipart_body() {
+        let mut form = Multipart::new();
+        form.text("json", "{}");
+        form.file("file0", "a.txt", b"hello");
+        let boundary = form.boundary.clone();
+        let req = form
+            .apply(reqwest::Client::new().post("http://localhost/"))
+            .build()
+            .unwrap();
+        let body = String::from_utf8(req.body().unwrap().as_bytes().unwrap().to_vec()).unwrap();
+        assert!(body.contains("name=\"file0\"; filename=\"a.txt\"\r\n"));
+        assert!(body.contains("\r\n\r\nhello\r\n"));
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+    }
+
+    #[test]
+    fn fetch_many_urls() {
+        let base = "http://ci.example.com/jenkins";
+        assert_eq!(
+            api_json_url(base, "job/a/12", "result").unwrap().as_str(),
+            "http://ci.example.com/jenkins/job/a/12/api/json?tree=result"
+        );
+        assert_eq!(
+            api_json_url(base, "http://ci.example.com/jenkins/job/b/", "")
+                .unwrap()
+                .as_str(),
+            "http://ci.example.com/jenkins/job/b/api/json"
+        );
+    }
+
+    #[test]
+    fn jenkins_is_shareable() {
+        fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+        fn assert_send<T: Send>(_: &T) {}
+        assert_shareable::<Jenkins>();
+        let cli = Jenkins::new("http://localhost:8080", "user", "token");
+        assert_send(&cli.get_build("job", 1));
+        assert_send(&cli.build_with_parameter("job", HashMap::new()));
+    }
+
+    #[cfg(feature = "credentials")]
+    #[test]
+    fn credentials_per_request() {
+        use crate::credentials::RotatingCredentials;
+
+        let rotating = RotatingCredentials::new(Credentials::new("bot", "old"));
+        let cli = Jenkins::builder("http://ci", "", "")
+            .credentials(rotating.clone())
+            .build();
+        let auth = |cli: &Jenkins| {
+            cli.get("http://ci/api/json").build().unwrap().headers()["authorization"].clone()
+        };
+        let before = auth(&cli);
+        rotating.rotate(Credentials::new("bot", "new"));
+        assert_ne!(auth(&cli), before);
+        assert_eq!(auth(&cli), auth(&Jenkins::new("http://ci", "bot", "new")));
+    }
+
+    #[test]
+    fn xml_declared_as_utf8() {
+        assert_eq!(
+            utf8_declaration("<?xml version='1.1' encoding='ISO-8859-1'?>\n<project/>"),
+            "<?xml version='1.1' encoding='UTF-8'?>\n<project/>"
+        );
+        let utf8 = "<?xml version=\"1.0\" encoding=\"utf-8\"?><project/>";
+        assert_eq!(utf8_declaration(utf8), utf8);
+        assert_eq!(utf8_declaration("\u{feff}<project/>"), "<project/>");
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compressed_responses() {
+        let log = "Started by user admin\n".repeat(100);
+        let server = crate::mock::MockServer::start(vec![crate::mock::response(
+            "200 OK",
+            &[("Content-Encoding", "gzip")],
+            gzip(log.as_bytes()),
+        )])
+        .await;
+        let cli = Jenkins::builder(&server.url, "bot", "token")
+            .compression(Compression {
+                responses: true,
+                ..Default::default()
+            })
+            .build();
+        let url = format!("{}/job/api/1/consoleText", server.url);
+        let res = cli.send(cli.get(&url)).await.unwrap();
+        assert_eq!(res.text().await.unwrap(), log);
+        let request = server.requests()[0].to_lowercase();
+        assert!(request.contains("accept-encoding: gzip,br"), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn rejected_crumb_is_refetched() {
+        use crate::mock::{response, MockServer};
+
+        let server = MockServer::start(vec![
+            response(
+                "403 Forbidden",
+                &[],
+                "No valid crumb was included in the request",
+            ),
+            response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                r#"{"crumbRequestField":"Jenkins-Crumb","crumb":"new"}"#,
+            ),
+            response("201 Created", &[("Location", "/queue/item/5/")], ""),
+            response(
+                "403 Forbidden",
+                &[],
+                "bot is missing the Job/Build permission",
+            ),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        *cli.crumb.write().unwrap() = Some(Crumb {
+            field: "Jenkins-Crumb".to_owned(),
+            value: "old".to_owned(),
+        });
+        let handle = cli
+            .queue_build_with_parameter("api", HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(handle.queue_item_url.id(), 5);
+        let requests = server.requests();
+        assert!(requests[0].contains("jenkins-crumb: old"));
+        assert!(requests[1].starts_with("GET /crumbIssuer/api/json "));
+        assert!(requests[2].contains("jenkins-crumb: new"));
+        assert!(!requests[2].contains("jenkins-crumb: old"));
+
+        // other refusals are passed on as they are
+        let err = cli
+            .queue_build_with_parameter("api", HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("403"), "{}", err);
+        assert_eq!(server.requests().len(), 4);
+        assert_eq!(cli.crumb.read().unwrap().as_ref().unwrap().value, "new");
+    }
+
+    #[tokio::test]
+    async fn redirects_resolved_with_the_same_client_config() {
+        use crate::{
+            build::ArtifactLocation,
+            mock::{response, MockServer},
+        };
+
+        let s3 = "https://bucket.s3.amazonaws.com/a.zip?X-Amz-Signature=1";
+        let server = MockServer::start(vec![
+            response("200 OK", &[], r#"{"jobs":[]}"#),
+            response("302 Found", &[("Location", s3)], ""),
+        ])
+        .await;
+        let cli = Jenkins::builder(&server.url, "user", "token")
+            .configure_client(|builder| {
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert("x-proxy-auth", "secret".parse().unwrap());
+                builder.default_headers(headers)
+            })
+            .build();
+        cli.get_json::<serde_json::Value>(&format!("{}/api/json", server.url))
+            .await
+            .unwrap();
+        let location = cli.get_artifact_location("api", 3, "a.zip").await.unwrap();
+        assert_eq!(location, ArtifactLocation::External(s3.to_owned()));
+        for request in server.requests() {
+            assert!(request.contains("x-proxy-auth: secret"), "{}", request);
+        }
+    }
+}
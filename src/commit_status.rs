@@ -0,0 +1,315 @@
+//! Commit statuses for GitHub, GitLab and Gerrit from build results
+//!
+//! Map a build into the payload of the code host, then send it with
+//! [`Jenkins::post_commit_status`] or any other http client.
+
+use anyhow::{bail, Result};
+use log::info;
+use serde_json::{json, Value};
+
+use crate::{BuildRes, Error, Jenkins};
+
+/// State of a commit status, named after GitHub's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitState {
+    /// build running
+    Pending,
+    Success,
+    /// `FAILURE` and `UNSTABLE`
+    Failure,
+    /// `ABORTED`, `NOT_BUILT` and unknown results
+    Error,
+}
+
+impl CommitState {
+    pub fn from_build(build: &BuildRes) -> CommitState {
+        if build.building {
+            return CommitState::Pending;
+        }
+        match build.result.as_deref() {
+            Some("SUCCESS") => CommitState::Success,
+            Some("FAILURE") | Some("UNSTABLE") => CommitState::Failure,
+            None => CommitState::Pending,
+            Some(_) => CommitState::Error,
+        }
+    }
+
+    fn github(self) -> &'static str {
+        match self {
+            CommitState::Pending => "pending",
+            CommitState::Success => "success",
+            CommitState::Failure => "failure",
+            CommitState::Error => "error",
+        }
+    }
+
+    fn gitlab(self) -> &'static str {
+        match self {
+            CommitState::Pending => "running",
+            CommitState::Success => "success",
+            CommitState::Failure => "failed",
+            CommitState::Error => "canceled",
+        }
+    }
+
+    /// vote on the `Verified` label
+    fn gerrit_verified(self) -> i8 {
+        match self {
+            CommitState::Pending => 0,
+            CommitState::Success => 1,
+            CommitState::Failure | CommitState::Error => -1,
+        }
+    }
+}
+
+/// Status of a commit built by Jenkins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitStatus {
+    pub state: CommitState,
+    /// build url
+    pub target_url: String,
+    /// name of the check, e.g. `ci/jenkins`
+    pub context: String,
+    pub description: String,
+}
+
+impl CommitStatus {
+    pub fn from_build(build: &BuildRes, context: &str) -> CommitStatus {
+        let state = CommitState::from_build(build);
+        let description = match state {
+            CommitState::Pending => format!("Build #{} running", build.number),
+            _ => format!(
+                "Build #{} {}",
+                build.number,
+                build.result.as_deref().unwrap_or_default().to_lowercase()
+            ),
+        };
+        CommitStatus {
+            state,
+            target_url: build.url.clone(),
+            context: context.to_owned(),
+            description,
+        }
+    }
+
+    /// Body of `POST /repos/{repo}/statuses/{sha}`
+    pub fn github_payload(&self) -> Value {
+        json!({
+            "state": self.state.github(),
+            "target_url": self.target_url,
+            "context": self.context,
+            "description": self.description,
+        })
+    }
+
+    /// Body of `POST /projects/{id}/statuses/{sha}`
+    pub fn gitlab_payload(&self) -> Value {
+        json!({
+            "state": self.state.gitlab(),
+            "target_url": self.target_url,
+            "name": self.context,
+            "description": self.description,
+        })
+    }
+
+    /// Body of `POST /changes/{change}/revisions/{revision}/review`
+    pub fn gerrit_payload(&self) -> Value {
+        json!({
+            "message": format!("{}: {} {}", self.context, self.description, self.target_url),
+            "labels": {"Verified": self.state.gerrit_verified()},
+        })
+    }
+}
+
+/// Where [`Jenkins::post_commit_status`] reports to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusTarget {
+    GitHub {
+        /// `https://api.github.com` or `https://{host}/api/v3` for GitHub Enterprise
+        api_url: String,
+        /// `owner/name`
+        repo: String,
+        sha: String,
+        token: String,
+    },
+    GitLab {
+        /// e.g. `https://gitlab.com`
+        url: String,
+        /// numeric id or url encoded path
+        project: String,
+        sha: String,
+        token: String,
+    },
+    Gerrit {
+        url: String,
+        change: String,
+        revision: String,
+        user: String,
+        password: String,
+    },
+}
+
+impl Jenkins {
+    /// Post a commit status to a code host
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use jenkins_rs::commit_status::{CommitStatus, StatusTarget};
+    /// let build = cli.get_build("app", 42).await?;
+    /// let target = StatusTarget::GitHub {
+    ///     api_url: "https://api.github.com".to_owned(),
+    ///     repo: "jarod/app".to_owned(),
+    ///     sha: "3f2a1c".to_owned(),
+    ///     token: std::env::var("GITHUB_TOKEN")?,
+    /// };
+    /// cli.post_commit_status(&CommitStatus::from_build(&build, "ci/jenkins"), &target)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Arguments
+    ///
+    /// * `status` - status to report
+    /// * `target` - code host, commit and credentials
+    ///
+    pub async fn post_commit_status(
+        &self,
+        status: &CommitStatus,
+        target: &StatusTarget,
+    ) -> Result<()> {
+        let req = match target {
+            StatusTarget::GitHub {
+                api_url,
+                repo,
+                sha,
+                token,
+            } => self
+                .hc
+                .post(format!(
+                    "{}/repos/{}/statuses/{}",
+                    api_url.trim_end_matches('/'),
+                    repo,
+                    sha
+                ))
+                .bearer_auth(token)
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", "jenkins-rs")
+                .json(&status.github_payload()),
+            StatusTarget::GitLab {
+                url,
+                project,
+                sha,
+                token,
+            } => self
+                .hc
+                .post(format!(
+                    "{}/api/v4/projects/{}/statuses/{}",
+                    url.trim_end_matches('/'),
+                    project,
+                    sha
+                ))
+                .header("PRIVATE-TOKEN", token)
+                .json(&status.gitlab_payload()),
+            StatusTarget::Gerrit {
+                url,
+                change,
+                revision,
+                user,
+                password,
+            } => self
+                .hc
+                .post(format!(
+                    "{}/a/changes/{}/revisions/{}/review",
+                    url.trim_end_matches('/'),
+                    change,
+                    revision
+                ))
+                .basic_auth(user, Some(password))
+                .json(&status.gerrit_payload()),
+        };
+        // straight to the code host: the Jenkins credentials retry, dry-run and
+        // test hooks of `send` are not for third parties
+        let req = req.build().map_err(Error::NetworkError)?;
+        let res = self.hc.execute(req).await.map_err(Error::NetworkError)?;
+        match res.status() {
+            code if code.is_success() => {
+                info!(
+                    "commit status posted - context={}, state={:?}",
+                    status.context, status.state
+                );
+                Ok(())
+            }
+            code => bail!(Error::APIError(format!("http status: {}", code))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_status_payloads() {
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":42,"url":"http://ci/job/app/42/","building":false,"result":"UNSTABLE",
+                "duration":10,"timestamp":1}"#,
+        )
+        .unwrap();
+        let status = CommitStatus::from_build(&build, "ci/jenkins");
+        assert_eq!(status.state, CommitState::Failure);
+        assert_eq!(
+            status.github_payload(),
+            json!({"state":"failure","target_url":"http://ci/job/app/42/",
+                   "context":"ci/jenkins","description":"Build #42 unstable"})
+        );
+        assert_eq!(status.gitlab_payload()["state"], "failed");
+        assert_eq!(status.gerrit_payload()["labels"]["Verified"], -1);
+    }
+
+    #[tokio::test]
+    async fn code_host_401_not_retried_with_jenkins_auth() {
+        use crate::{
+            mock::{response, MockServer},
+            BoxFuture, Credentials, CredentialsProvider,
+        };
+
+        struct Refreshing;
+        impl CredentialsProvider for Refreshing {
+            fn credentials(&self) -> Credentials {
+                Credentials::new("jenkins-user", "jenkins-token")
+            }
+            fn refresh(&self) -> BoxFuture<'_, bool> {
+                Box::pin(async { true })
+            }
+        }
+
+        let server = MockServer::start(vec![
+            response("401 Unauthorized", &[], ""),
+            response("201 Created", &[], "{}"),
+        ])
+        .await;
+        let cli = Jenkins::builder("http://127.0.0.1:9", "", "")
+            .credentials(Refreshing)
+            .build();
+        let target = StatusTarget::GitHub {
+            api_url: server.url.clone(),
+            repo: "jarod/app".to_owned(),
+            sha: "3f2a1c".to_owned(),
+            token: "gh-token".to_owned(),
+        };
+        let status = CommitStatus {
+            state: CommitState::Success,
+            context: "ci/jenkins".to_owned(),
+            description: String::new(),
+            target_url: "http://ci/job/app/1/".to_owned(),
+        };
+        assert!(cli.post_commit_status(&status, &target).await.is_err());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("authorization: Bearer gh-token"));
+        assert!(!requests[0]
+            .to_ascii_lowercase()
+            .contains("authorization: basic"));
+    }
+}
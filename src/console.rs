@@ -0,0 +1,576 @@
+//! Console log retrieval and parsing: pipeline stages, `[Pipeline]` markers and errors
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use log::{info, trace, warn};
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use crate::{Error, Jenkins};
+
+/// How [`Jenkins::get_console`] returns the log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsoleFormat {
+    /// `/consoleText` as is
+    Raw,
+    /// `/consoleText` with console notes and ANSI escape sequences removed
+    #[default]
+    Plain,
+    /// annotated HTML as rendered by the console page
+    Html,
+}
+
+/// Pause between progressive log requests of [`Jenkins::tail_log_to`]
+const TAIL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// When [`Jenkins::tail_log_to`] flushes the log file to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fsync {
+    /// leave it to the OS
+    #[default]
+    Never,
+    /// after every chunk of log received
+    EveryChunk,
+    /// when a file is rotated out and at the end of the build
+    OnRotate,
+}
+
+/// Size based rotation of the file written by [`Jenkins::tail_log_to`]
+///
+/// The file at `path` is renamed to `path.1`, `path.1` to `path.2` and so
+/// on, the oldest beyond `keep` is deleted. Files are cut at line ends
+/// unless a single line exceeds `max_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRotation {
+    /// `None` keeps everything in one file
+    pub max_bytes: Option<u64>,
+    /// rotated files kept besides the current one
+    pub keep: usize,
+    pub fsync: Fsync,
+}
+
+impl LogRotation {
+    /// Single file, never rotated
+    pub fn none() -> LogRotation {
+        LogRotation {
+            max_bytes: None,
+            keep: 0,
+            fsync: Fsync::Never,
+        }
+    }
+
+    pub fn size(max_bytes: u64, keep: usize) -> LogRotation {
+        LogRotation {
+            max_bytes: Some(max_bytes),
+            keep,
+            fsync: Fsync::Never,
+        }
+    }
+
+    pub fn fsync(mut self, fsync: Fsync) -> Self {
+        self.fsync = fsync;
+        self
+    }
+}
+
+impl Jenkins {
+    /// Get console log of a build
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `format` - raw, plain text or annotated HTML
+    ///
+    pub async fn get_console(
+        &self,
+        job: &str,
+        number: i32,
+        format: ConsoleFormat,
+    ) -> Result<String> {
+        if format != ConsoleFormat::Html {
+            let log = self.get_console_text(job, number).await?;
+            return Ok(match format {
+                ConsoleFormat::Plain => strip_ansi(&strip_console_notes(&log)),
+                _ => log,
+            });
+        }
+        let url = format!(
+            "{}/job/{}/{}/logText/progressiveHtml",
+            self.url, job, number
+        );
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get console html - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    /// Get console log lines with the time they were written, via the timestamper plugin
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn console_text_with_timestamps(
+        &self,
+        job: &str,
+        number: i32,
+    ) -> Result<Vec<(SystemTime, String)>> {
+        let url = format!(
+            "{}/job/{}/{}/timestamps/?precision=milliseconds&appendLog",
+            self.url, job, number
+        );
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get timestamps - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let text = res.text().await.map_err(Error::NetworkError)?;
+        Ok(parse_timestamped(&text))
+    }
+
+    /// Follow the console log of a build until it completes, writing it to `path`
+    ///
+    /// Never holds more than one chunk of the log in memory, for builds whose
+    /// logs are too large for [`Jenkins::get_console`]. Returns the number of
+    /// bytes written.
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use jenkins_rs::console::{Fsync, LogRotation};
+    ///
+    /// let rotation = LogRotation::size(100 << 20, 5).fsync(Fsync::OnRotate);
+    /// cli.tail_log_to("soak-test", 42, "logs/soak-test.log", rotation).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `path` - log file, replaced if it exists
+    /// * `rotation` - when to start a new file and fsync
+    ///
+    pub async fn tail_log_to(
+        &self,
+        job: &str,
+        number: i32,
+        path: impl AsRef<Path>,
+        rotation: LogRotation,
+    ) -> Result<u64> {
+        let mut file = LogFile::create(path.as_ref(), rotation).await?;
+        let mut start = 0u64;
+        loop {
+            let url = format!(
+                "{}/job/{}/{}/logText/progressiveText?start={}",
+                self.url, job, number, start
+            );
+            let mut res = self.send(self.get(&url)).await?;
+            if !res.status().is_success() {
+                warn!("tail log - job={}, number={}, res={:?}", job, number, res);
+                bail!(Error::APIError(format!("http status: {}", res.status())))
+            }
+            let header = |name: &str| {
+                res.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_owned)
+            };
+            let more = header("X-More-Data").is_some_and(|v| v == "true");
+            let size = header("X-Text-Size").and_then(|v| v.parse().ok());
+            let mut received = 0;
+            while let Some(chunk) = self
+                .until_shutdown(res.chunk())
+                .await?
+                .map_err(Error::NetworkError)?
+            {
+                file.write(&chunk).await?;
+                received += chunk.len() as u64;
+            }
+            trace!(
+                "tail log - job={}, number={}, start={}, len={}",
+                job,
+                number,
+                start,
+                received
+            );
+            start = size.unwrap_or(start + received);
+            if !more {
+                break;
+            }
+            self.pause(TAIL_INTERVAL).await?;
+        }
+        file.finish().await?;
+        info!(
+            "tail log - job={}, number={}, bytes={}",
+            job, number, file.total
+        );
+        Ok(file.total)
+    }
+}
+
+/// Log file of [`Jenkins::tail_log_to`] with its rotated predecessors
+struct LogFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    file: File,
+    /// bytes in the current file
+    size: u64,
+    total: u64,
+}
+
+impl LogFile {
+    async fn create(path: &Path, rotation: LogRotation) -> Result<LogFile> {
+        Ok(LogFile {
+            path: path.to_owned(),
+            rotation,
+            file: File::create(path).await?,
+            size: 0,
+            total: 0,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    async fn write(&mut self, mut chunk: &[u8]) -> Result<()> {
+        while !chunk.is_empty() {
+            let room = match self.rotation.max_bytes {
+                Some(max) => max.saturating_sub(self.size) as usize,
+                None => usize::MAX,
+            };
+            let len = if chunk.len() <= room {
+                chunk.len()
+            } else {
+                match chunk[..room].iter().rposition(|b| *b == b'\n') {
+                    Some(end) => end + 1,
+                    None if self.size > 0 => 0,
+                    // a line longer than a whole file
+                    None => chunk
+                        .iter()
+                        .position(|b| *b == b'\n')
+                        .map_or(chunk.len(), |end| end + 1),
+                }
+            };
+            self.file.write_all(&chunk[..len]).await?;
+            self.size += len as u64;
+            self.total += len as u64;
+            chunk = &chunk[len..];
+            if !chunk.is_empty() {
+                self.rotate().await?;
+            }
+        }
+        if self.rotation.fsync == Fsync::EveryChunk {
+            self.file.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> Result<()> {
+        self.finish().await?;
+        let keep = self.rotation.keep;
+        if keep > 0 {
+            match tokio::fs::remove_file(self.rotated(keep)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            for n in (1..keep).rev() {
+                match tokio::fs::rename(self.rotated(n), self.rotated(n + 1)).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            tokio::fs::rename(&self.path, self.rotated(1)).await?;
+        }
+        trace!("rotate log - path={:?}, size={}", self.path, self.size);
+        self.file = File::create(&self.path).await?;
+        self.size = 0;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        if self.rotation.fsync != Fsync::Never {
+            self.file.sync_data().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse `<seconds since epoch>  <line>` output of the timestamper plugin,
+/// lines without a timestamp get the one of the previous line
+fn parse_timestamped(text: &str) -> Vec<(SystemTime, String)> {
+    let mut last = UNIX_EPOCH;
+    text.lines()
+        .map(|line| {
+            let (time, log) = line.split_once("  ").unwrap_or((line, ""));
+            match time.parse::<f64>() {
+                Ok(secs) if secs >= 0.0 => {
+                    last = UNIX_EPOCH + Duration::from_secs_f64(secs);
+                    (last, log.to_owned())
+                }
+                _ => (last, line.to_owned()),
+            }
+        })
+        .collect()
+}
+
+/// Pipeline block with a name, e.g. a stage or a parallel branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleStage {
+    pub name: String,
+    /// line of `[Pipeline] { (name)`, 0-based
+    pub start_line: usize,
+    /// line of the closing `[Pipeline] }`, `None` if the log ends inside the stage
+    pub end_line: Option<usize>,
+}
+
+/// First error found in a log with the lines following it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorSnippet {
+    /// 0-based
+    pub line: usize,
+    pub text: String,
+}
+
+/// Result of [`parse_console`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsoleSummary {
+    /// in order of appearance
+    pub stages: Vec<ConsoleStage>,
+    /// `[Pipeline]` lines with their 0-based line number, e.g. `(12, "sh")`
+    pub pipeline_markers: Vec<(usize, String)>,
+    pub first_error: Option<ErrorSnippet>,
+}
+
+impl ConsoleSummary {
+    /// Innermost stage containing `line`
+    pub fn stage_at(&self, line: usize) -> Option<&ConsoleStage> {
+        self.stages
+            .iter()
+            .filter(|s| s.start_line <= line && s.end_line.is_none_or(|end| line <= end))
+            .max_by_key(|s| s.start_line)
+    }
+}
+
+const SNIPPET_MAX_LINES: usize = 20;
+const PIPELINE_PREFIX: &str = "[Pipeline] ";
+
+/// Parse a console log, console notes and ANSI escape sequences are stripped first
+pub fn parse_console(log: &str) -> ConsoleSummary {
+    let log = strip_ansi(&strip_console_notes(log));
+    let lines: Vec<&str> = log.lines().collect();
+    let mut summary = ConsoleSummary::default();
+    // open `{` blocks, with the index into `stages` for named ones
+    let mut blocks: Vec<Option<usize>> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(marker) = line.strip_prefix(PIPELINE_PREFIX) {
+            summary.pipeline_markers.push((i, marker.to_owned()));
+            if let Some(rest) = marker.strip_prefix('{') {
+                let name = rest
+                    .trim()
+                    .strip_prefix('(')
+                    .and_then(|r| r.strip_suffix(')'));
+                blocks.push(name.map(|name| {
+                    summary.stages.push(ConsoleStage {
+                        name: name.to_owned(),
+                        start_line: i,
+                        end_line: None,
+                    });
+                    summary.stages.len() - 1
+                }));
+            } else if marker == "}" {
+                if let Some(Some(stage)) = blocks.pop() {
+                    summary.stages[stage].end_line = Some(i);
+                }
+            }
+            continue;
+        }
+        if summary.first_error.is_none() && is_error(line) {
+            let trace = lines[i + 1..]
+                .iter()
+                .take(SNIPPET_MAX_LINES - 1)
+                .take_while(|l| is_continuation(l))
+                .count();
+            summary.first_error = Some(ErrorSnippet {
+                line: i,
+                text: lines[i..=i + trace].join("\n"),
+            });
+        }
+    }
+    summary
+}
+
+fn is_error(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("ERROR:")
+        || line.starts_with("FATAL:")
+        || line.starts_with("error:")
+        || line.starts_with("Traceback (most recent call last)")
+        || line.contains(": error:")
+        || line
+            .split(|c: char| c.is_whitespace() || c == ':')
+            .next()
+            .is_some_and(|word| word.ends_with("Exception") || word.ends_with("Error"))
+            && line.contains('.')
+}
+
+/// Stack trace and indented lines belonging to the error above
+fn is_continuation(line: &str) -> bool {
+    line.starts_with(char::is_whitespace)
+        || line.starts_with("Caused by:")
+        || line.starts_with("Also:")
+}
+
+const NOTE_PREAMBLE: &str = "\x1b[8mha:";
+const NOTE_POSTAMBLE: &str = "\x1b[0m";
+
+/// Remove Jenkins console notes, the hidden `ESC[8mha:<base64>ESC[0m` markers
+/// carrying serialized annotations
+pub fn strip_console_notes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find(NOTE_PREAMBLE) {
+        out.push_str(&rest[..start]);
+        let note = &rest[start + NOTE_PREAMBLE.len()..];
+        rest = match note.find(NOTE_POSTAMBLE) {
+            Some(end) => &note[end + NOTE_POSTAMBLE.len()..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, OSC titles)
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters then a final byte in `@`..=`~`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ESC `\`
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pipeline_log() {
+        let log = "Started by user admin\n\
+            [Pipeline] Start of Pipeline\n\
+            [Pipeline] node\n\
+            [Pipeline] {\n\
+            [Pipeline] stage\n\
+            [Pipeline] { (Build)\n\
+            [Pipeline] sh\n\
+            + make\n\
+            [Pipeline] }\n\
+            [Pipeline] // stage\n\
+            [Pipeline] stage\n\
+            [Pipeline] { (Test)\n\
+            [Pipeline] sh\n\
+            \x1b[31mjava.lang.IllegalStateException: boom\x1b[0m\n\
+            \tat com.example.Foo.bar(Foo.java:42)\n\
+            Caused by: java.io.IOException: disk\n\
+            Finished: FAILURE\n";
+        let summary = parse_console(log);
+        assert_eq!(
+            summary
+                .stages
+                .iter()
+                .map(|s| (s.name.as_str(), s.start_line, s.end_line))
+                .collect::<Vec<_>>(),
+            [("Build", 5, Some(8)), ("Test", 11, None)]
+        );
+        assert_eq!(
+            summary.pipeline_markers[0],
+            (1, "Start of Pipeline".to_owned())
+        );
+        let error = summary.first_error.as_ref().unwrap();
+        assert_eq!(error.line, 13);
+        assert!(error
+            .text
+            .starts_with("java.lang.IllegalStateException: boom\n\tat "));
+        assert!(error.text.ends_with("Caused by: java.io.IOException: disk"));
+        assert_eq!(summary.stage_at(error.line).unwrap().name, "Test");
+    }
+
+    #[test]
+    fn strip_ansi_sequences() {
+        assert_eq!(strip_ansi("\x1b[1;32mok\x1b[0m done"), "ok done");
+        assert_eq!(strip_ansi("\x1b]0;title\x07text"), "text");
+        assert_eq!(
+            strip_console_notes("\x1b[8mha:////4AAAAB+LCAAA\x1b[0m[Pipeline] sh\n"),
+            "[Pipeline] sh\n"
+        );
+    }
+
+    #[test]
+    fn parse_timestamper_output() {
+        let lines = parse_timestamped("1700000000.250  Started by user admin\nno timestamp\n");
+        let expected = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        assert_eq!(lines[0], (expected, "Started by user admin".to_owned()));
+        assert_eq!(lines[1], (expected, "no timestamp".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn rotate_log_file() {
+        let dir = std::env::temp_dir().join(format!("tail-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("build.log");
+        let mut file = LogFile::create(&path, LogRotation::size(10, 2))
+            .await
+            .unwrap();
+        file.write(b"one\ntwo\nthree\n").await.unwrap();
+        file.write(b"a very long line\nfour\n").await.unwrap();
+        file.finish().await.unwrap();
+        assert_eq!(file.total, 36);
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("build.log"), "four\n");
+        assert_eq!(read("build.log.1"), "a very long line\n");
+        assert_eq!(read("build.log.2"), "three\n");
+        // beyond `keep`
+        assert!(!dir.join("build.log.3").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+//! Where the user and api token of requests come from, enabled by the
+//! `credentials` feature
+//!
+//! The provider is asked on every request, so a token rotated at runtime is
+//! used by the next request without rebuilding [`Jenkins`](crate::Jenkins).
+
+use std::sync::{Arc, RwLock};
+
+use log::warn;
+
+pub use crate::client::{Credentials, CredentialsProvider};
+
+/// Read user and token from environment variables on each request
+#[derive(Debug, Clone)]
+pub struct EnvCredentials {
+    pub user_var: String,
+    pub password_var: String,
+}
+
+impl EnvCredentials {
+    pub fn new(user_var: &str, password_var: &str) -> EnvCredentials {
+        EnvCredentials {
+            user_var: user_var.to_owned(),
+            password_var: password_var.to_owned(),
+        }
+    }
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> Credentials {
+        let var = |name: &str| {
+            std::env::var(name).unwrap_or_else(|_| {
+                warn!("credentials variable not set - name={}", name);
+                String::new()
+            })
+        };
+        Credentials {
+            user: var(&self.user_var),
+            password: var(&self.password_var),
+        }
+    }
+}
+
+/// Credentials replaced at runtime, e.g. by a task renewing a short lived token
+///
+/// Clones share the value, keep one to [`RotatingCredentials::rotate`] and pass
+/// another to [`JenkinsBuilder::credentials`](crate::JenkinsBuilder::credentials).
+#[derive(Debug, Clone)]
+pub struct RotatingCredentials {
+    current: Arc<RwLock<Credentials>>,
+}
+
+impl RotatingCredentials {
+    pub fn new(initial: Credentials) -> RotatingCredentials {
+        RotatingCredentials {
+            current: Arc::new(RwLock::new(initial)),
+        }
+    }
+
+    /// Use `credentials` from the next request on
+    pub fn rotate(&self, credentials: Credentials) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = credentials;
+    }
+}
+
+impl CredentialsProvider for RotatingCredentials {
+    fn credentials(&self) -> Credentials {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
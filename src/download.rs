@@ -0,0 +1,240 @@
+//! Concurrent download of all artifacts of a build
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::sleep_until,
+};
+
+use crate::{join::try_join_all, Artifact, DownloadOptions, Error, Jenkins};
+
+/// Overall progress of [`ArtifactDownloader::download`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub files_total: usize,
+    pub files_done: usize,
+    pub bytes_done: u64,
+}
+
+type ArtifactFilter = Box<dyn Fn(&Artifact) -> bool + Send + Sync>;
+type ProgressHook = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
+/// Shared bandwidth limit across concurrent downloads
+pub(crate) struct RateLimiter {
+    bytes_per_sec: u64,
+    /// start time and bytes consumed since
+    state: Mutex<(Instant, u64)>,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> RateLimiter {
+        RateLimiter {
+            bytes_per_sec: bytes_per_sec.max(1),
+            state: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Wait until `bytes` more fit in the limit
+    pub(crate) async fn consume(&self, bytes: u64) {
+        let deadline = {
+            let mut state = self.state.lock().await;
+            state.1 += bytes;
+            state.0 + Duration::from_secs_f64(state.1 as f64 / self.bytes_per_sec as f64)
+        };
+        sleep_until(deadline.into()).await;
+    }
+}
+
+/// Downloads artifacts of a build concurrently, see [`Jenkins::artifact_downloader`]
+///
+/// ```no_run
+/// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+/// let files = cli
+///     .artifact_downloader()
+///     .parallelism(8)
+///     .bandwidth_limit(10 * 1024 * 1024)
+///     .filter(|a| a.file_name.ends_with(".jar"))
+///     .download("release", 42, "target/artifacts")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ArtifactDownloader<'a> {
+    jenkins: &'a Jenkins,
+    parallelism: usize,
+    bytes_per_sec: Option<u64>,
+    filter: Option<ArtifactFilter>,
+    on_progress: Option<ProgressHook>,
+}
+
+impl Jenkins {
+    /// Create [`ArtifactDownloader`] to download artifacts of a build concurrently
+    pub fn artifact_downloader(&self) -> ArtifactDownloader<'_> {
+        ArtifactDownloader {
+            jenkins: self,
+            parallelism: 4,
+            bytes_per_sec: None,
+            filter: None,
+            on_progress: None,
+        }
+    }
+}
+
+impl ArtifactDownloader<'_> {
+    /// Max number of concurrent downloads, defaults to 4
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Total bandwidth limit of all downloads in bytes per second
+    pub fn bandwidth_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+
+    /// Download only artifacts matching `filter`
+    pub fn filter(mut self, filter: impl Fn(&Artifact) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Download only artifacts whose relative path matches a glob pattern
+    pub fn glob(self, pattern: &str) -> Self {
+        let glob = crate::glob::Glob::new(pattern);
+        self.filter(move |a| glob.is_match(&a.relative_path))
+    }
+
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&DownloadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    /// Download artifacts into `dest_dir` keeping their relative paths
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `dest_dir` - destination directory, created if missing
+    ///
+    pub async fn download(
+        &self,
+        job: &str,
+        number: i32,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>> {
+        let dest_dir = dest_dir.as_ref();
+        let build = self.jenkins.get_build(job, number).await?;
+        let artifacts: Vec<Artifact> = build
+            .artifacts
+            .into_iter()
+            .filter(|a| self.filter.as_ref().is_none_or(|f| f(a)))
+            .collect();
+        let files_total = artifacts.len();
+        let files_done = Arc::new(AtomicUsize::new(0));
+        let bytes_done = Arc::new(AtomicU64::new(0));
+        let sem = Semaphore::new(self.parallelism);
+        let limiter = self.bytes_per_sec.map(RateLimiter::new);
+
+        let paths = try_join_all(artifacts.iter().map(|artifact| async {
+            let path = dest_path(dest_dir, &artifact.relative_path)?;
+            let _permit = sem.acquire().await?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::File::create(&path)
+                .await
+                .with_context(|| format!("create {}", path.display()))?;
+            let file_bytes = Arc::new(AtomicU64::new(0));
+            let opts = DownloadOptions {
+                on_progress: self.on_progress.as_ref().map(|hook| {
+                    let (hook, file_bytes, bytes_done, files_done) = (
+                        hook.clone(),
+                        file_bytes.clone(),
+                        bytes_done.clone(),
+                        files_done.clone(),
+                    );
+                    Box::new(move |written: u64, _total: Option<u64>| {
+                        let delta = written - file_bytes.swap(written, Ordering::Relaxed);
+                        hook(&DownloadProgress {
+                            files_total,
+                            // read when reporting, other files finish meanwhile
+                            files_done: files_done.load(Ordering::Relaxed),
+                            bytes_done: bytes_done.fetch_add(delta, Ordering::Relaxed) + delta,
+                        });
+                    }) as crate::ProgressFn
+                }),
+                ..DownloadOptions::default()
+            };
+            self.jenkins
+                .download_artifact_limited(
+                    job,
+                    number,
+                    &artifact.relative_path,
+                    &mut file,
+                    &opts,
+                    limiter.as_ref(),
+                )
+                .await?;
+            let done = files_done.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(hook) = &self.on_progress {
+                hook(&DownloadProgress {
+                    files_total,
+                    files_done: done,
+                    bytes_done: bytes_done.load(Ordering::Relaxed),
+                });
+            }
+            Ok::<_, anyhow::Error>(path)
+        }))
+        .await?;
+        info!(
+            "artifacts downloaded - job={}, number={}, files={}",
+            job, number, files_total
+        );
+        Ok(paths)
+    }
+}
+
+/// Join an artifact path to `dest_dir`, rejecting paths escaping it
+fn dest_path(dest_dir: &Path, relative_path: &str) -> Result<PathBuf> {
+    let relative = Path::new(relative_path);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!(Error::APIError(format!(
+            "unsafe artifact path: {}",
+            relative_path
+        )))
+    }
+    Ok(dest_dir.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dest_path_rejects_escapes() {
+        assert_eq!(
+            dest_path(Path::new("out"), "target/app.jar").unwrap(),
+            Path::new("out/target/app.jar")
+        );
+        assert!(dest_path(Path::new("out"), "../etc/passwd").is_err());
+        assert!(dest_path(Path::new("out"), "/etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Error type of the crate
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("API error: {0}")]
+    APIError(String),
+    #[error("Queue item not exists, maybe already running or finished")]
+    QueueItemNotExists,
+    #[error("Network error: {0}")]
+    NetworkError(reqwest::Error),
+    #[error("Checksum mismatch: expected {expected}, actual {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    /// response body doesn't match the model, e.g. a plugin returns `null` for a field
+    #[error("Deserialize error at {path}: {source}, body: {snippet}")]
+    Deserialize {
+        /// json path of the offending value, e.g. `$.builds[3].duration`
+        path: String,
+        /// body around the offending value, truncated
+        snippet: String,
+        source: serde_json::Error,
+    },
+    /// fields of the response the model doesn't know, only in strict mode
+    #[error("Unknown fields: {}", .0.join(", "))]
+    UnknownFields(Vec<String>),
+    #[error("{api} requires Jenkins {required}, server is {actual}")]
+    UnsupportedVersion {
+        api: &'static str,
+        required: String,
+        actual: String,
+    },
+    /// the client was shut down through [`Shutdown`](crate::shutdown::Shutdown)
+    #[error("Client shut down")]
+    Shutdown,
+}
+
+/// Bytes of body kept on each side of the offending value
+const SNIPPET_CONTEXT: usize = 120;
+
+impl Error {
+    /// Wrap a serde error with the location in `body` it points to
+    pub(crate) fn deserialize(body: &[u8], source: serde_json::Error) -> Error {
+        let body = String::from_utf8_lossy(body);
+        let offset = error_offset(&body, &source);
+        let mut start = offset.saturating_sub(SNIPPET_CONTEXT);
+        while !body.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (offset + SNIPPET_CONTEXT).min(body.len());
+        while !body.is_char_boundary(end) {
+            end += 1;
+        }
+        let snippet = format!(
+            "{}{}{}",
+            if start > 0 { "..." } else { "" },
+            &body[start..end],
+            if end < body.len() { "..." } else { "" }
+        );
+        Error::Deserialize {
+            path: json_path_at(&body[..offset]),
+            snippet,
+            source,
+        }
+    }
+}
+
+/// Byte offset of the 1-based line and column of a serde error
+fn error_offset(body: &str, err: &serde_json::Error) -> usize {
+    let line_start: usize = body
+        .split_inclusive('\n')
+        .take(err.line().saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let mut offset = (line_start + err.column().saturating_sub(1)).min(body.len());
+    while !body.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+enum PathSegment {
+    Key(Option<String>),
+    Index(usize),
+}
+
+/// Json path of the value being parsed at the end of `prefix`
+fn json_path_at(prefix: &str) -> String {
+    let mut stack: Vec<PathSegment> = Vec::new();
+    let mut chars = prefix.chars();
+    // last string of the current object, a key once followed by `:`
+    let mut last_string = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        c => s.push(c),
+                    }
+                }
+                last_string = Some(s);
+            }
+            ':' => {
+                if let Some(PathSegment::Key(key)) = stack.last_mut() {
+                    *key = last_string.take();
+                }
+            }
+            ',' => match stack.last_mut() {
+                Some(PathSegment::Key(key)) => *key = None,
+                Some(PathSegment::Index(i)) => *i += 1,
+                None => {}
+            },
+            '{' => stack.push(PathSegment::Key(None)),
+            '[' => stack.push(PathSegment::Index(0)),
+            '}' | ']' => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    stack
+        .iter()
+        .fold("$".to_owned(), |path, segment| match segment {
+            PathSegment::Key(Some(key)) => format!("{}.{}", path, key),
+            PathSegment::Key(None) => path,
+            PathSegment::Index(i) => format!("{}[{}]", path, i),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_error_path() {
+        #[derive(serde::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Build {
+            number: i32,
+            duration: i64,
+        }
+        #[derive(serde::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Builds {
+            builds: Vec<Build>,
+        }
+
+        let body = br#"{"builds":[{"number":2,"duration":5},
+            {"number":1,"url":"a,b]{","duration":null}]}"#;
+        let err = serde_json::from_slice::<Builds>(body).unwrap_err();
+        let Error::Deserialize { path, snippet, .. } = Error::deserialize(body, err) else {
+            panic!("not a deserialize error");
+        };
+        assert_eq!(path, "$.builds[1].duration");
+        assert!(snippet.contains(r#""duration":null"#));
+    }
+}
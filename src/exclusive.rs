@@ -0,0 +1,95 @@
+//! Client-side mutual exclusion of builds across a set of jobs
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use log::{info, trace};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{join::try_join_all, Jenkins, QueueItemRes};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JobActivityRes {
+    in_queue: bool,
+    last_build: Option<LastBuildRes>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastBuildRes {
+    building: bool,
+}
+
+/// Serializes triggers across a set of jobs, see [`Jenkins::exclusive`]
+///
+/// A trigger waits until no job of the group is building or queued, and
+/// triggers from the same group are serialized in this process, so at most
+/// one build of the group runs at a time unless builds are started elsewhere.
+///
+/// ```no_run
+/// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+/// let staging = cli.exclusive(&["deploy-api", "deploy-web", "db-migrate"]);
+/// staging.trigger("db-migrate", Default::default()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ExclusiveGroup<'a> {
+    jenkins: &'a Jenkins,
+    jobs: Vec<String>,
+    poll_interval: Duration,
+    lock: Mutex<()>,
+}
+
+impl Jenkins {
+    /// Create an [`ExclusiveGroup`] of jobs sharing an environment
+    pub fn exclusive(&self, jobs: &[&str]) -> ExclusiveGroup<'_> {
+        ExclusiveGroup {
+            jenkins: self,
+            jobs: jobs.iter().map(|j| (*j).to_owned()).collect(),
+            poll_interval: Duration::from_secs(5),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl ExclusiveGroup<'_> {
+    /// How often to check whether the group is idle, defaults to 5s
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// `true` if no job of the group is building or queued
+    pub async fn is_idle(&self) -> Result<bool> {
+        let activity = try_join_all(self.jobs.iter().map(|job| {
+            let url = format!(
+                "{}/job/{}/api/json?tree=inQueue,lastBuild[building]",
+                self.jenkins.url, job
+            );
+            async move { self.jenkins.get_json::<JobActivityRes>(&url).await }
+        }))
+        .await?;
+        Ok(activity
+            .iter()
+            .all(|a| !a.in_queue && !a.last_build.as_ref().is_some_and(|b| b.building)))
+    }
+
+    /// Wait until the group is idle, then trigger `job` and wait for it to start
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name, usually one of the group
+    /// * `params` - parameters to trigger a build
+    ///
+    pub async fn trigger(&self, job: &str, params: HashMap<&str, &str>) -> Result<QueueItemRes> {
+        // held until the build left the queue, so the next trigger sees it building
+        let _guard = self.lock.lock().await;
+        while !self.is_idle().await? {
+            trace!("exclusive group busy - jobs={:?}", self.jobs);
+            self.jenkins.pause(self.poll_interval).await?;
+        }
+        info!("exclusive group idle, triggering - job={}", job);
+        self.jenkins.build_with_parameter(job, params).await
+    }
+}
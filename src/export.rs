@@ -0,0 +1,303 @@
+//! Exporting build history as NDJSON or CSV, e.g. for loading into a data warehouse
+
+use std::{
+    collections::BTreeMap,
+    ops::{Bound, RangeBounds},
+};
+
+use anyhow::Result;
+use log::{info, trace};
+use serde::Deserialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{model::to_epoch_millis, Jenkins};
+
+/// Builds per `allBuilds` range request of [`Jenkins::export_builds`]
+const EXPORT_PAGE: usize = 100;
+
+/// Field of an exported build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Number,
+    /// `null`/empty while building
+    Result,
+    /// milliseconds
+    Duration,
+    /// start as epoch milliseconds
+    Timestamp,
+    Url,
+    /// parameters as a JSON object, password values are missing
+    Params,
+}
+
+impl Column {
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Number => "number",
+            Column::Result => "result",
+            Column::Duration => "duration",
+            Column::Timestamp => "timestamp",
+            Column::Url => "url",
+            Column::Params => "params",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    /// one JSON object per line
+    NdJson,
+    /// RFC 4180 with a header line
+    Csv,
+}
+
+/// Output of [`Jenkins::export_builds`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportFormat {
+    pub kind: ExportKind,
+    pub columns: Vec<Column>,
+}
+
+impl ExportFormat {
+    pub fn ndjson(columns: &[Column]) -> ExportFormat {
+        ExportFormat {
+            kind: ExportKind::NdJson,
+            columns: columns.to_vec(),
+        }
+    }
+
+    pub fn csv(columns: &[Column]) -> ExportFormat {
+        ExportFormat {
+            kind: ExportKind::Csv,
+            columns: columns.to_vec(),
+        }
+    }
+
+    fn header(&self) -> Option<String> {
+        (self.kind == ExportKind::Csv).then(|| {
+            let names: Vec<_> = self.columns.iter().map(Column::name).collect();
+            format!("{}\n", names.join(","))
+        })
+    }
+
+    fn row(&self, build: &ExportBuild) -> String {
+        let values = self.columns.iter().map(|c| (c.name(), build.value(*c)));
+        match self.kind {
+            // written by hand to keep the column order
+            ExportKind::NdJson => {
+                let fields: Vec<_> = values
+                    .map(|(name, value)| format!("\"{}\":{}", name, value))
+                    .collect();
+                format!("{{{}}}\n", fields.join(","))
+            }
+            ExportKind::Csv => {
+                let cells: Vec<_> = values
+                    .map(|(_, value)| match value {
+                        serde_json::Value::Null => String::new(),
+                        serde_json::Value::String(s) => csv_escape(&s),
+                        v => csv_escape(&v.to_string()),
+                    })
+                    .collect();
+                format!("{}\n", cells.join(","))
+            }
+        }
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportPage {
+    all_builds: Vec<ExportBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExportBuild {
+    number: i32,
+    url: String,
+    result: Option<String>,
+    #[serde(with = "crate::model::duration_millis")]
+    duration: std::time::Duration,
+    #[serde(with = "crate::model::epoch_millis")]
+    timestamp: std::time::SystemTime,
+    #[serde(default)]
+    actions: Vec<ExportAction>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ExportAction {
+    parameters: Vec<ExportParameter>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExportParameter {
+    name: String,
+    #[serde(default)]
+    value: serde_json::Value,
+}
+
+impl ExportBuild {
+    fn value(&self, column: Column) -> serde_json::Value {
+        match column {
+            Column::Number => self.number.into(),
+            Column::Result => self.result.clone().into(),
+            Column::Duration => (self.duration.as_millis() as u64).into(),
+            Column::Timestamp => to_epoch_millis(self.timestamp).into(),
+            Column::Url => self.url.clone().into(),
+            Column::Params => {
+                let params: BTreeMap<_, _> = self
+                    .actions
+                    .iter()
+                    .flat_map(|a| &a.parameters)
+                    .filter(|p| !p.value.is_null())
+                    .map(|p| (p.name.clone(), p.value.clone()))
+                    .collect();
+                serde_json::to_value(params).unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl Jenkins {
+    /// Write the builds of a job numbered within `range` to `writer`, newest first
+    ///
+    /// Pages through the full history, rows are written as each page arrives.
+    /// Returns the number of builds written.
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use jenkins_rs::export::{Column, ExportFormat};
+    ///
+    /// let mut file = tokio::fs::File::create("deploy.csv").await?;
+    /// let format = ExportFormat::csv(&[Column::Number, Column::Result, Column::Params]);
+    /// cli.export_builds("deploy", 100.., &format, &mut file).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `range` - build numbers, e.g. `..` for all
+    /// * `format` - NDJSON or CSV with the columns to export
+    /// * `writer` - destination, e.g. a [`tokio::fs::File`]
+    ///
+    pub async fn export_builds<W: AsyncWrite + Unpin>(
+        &self,
+        job: &str,
+        range: impl RangeBounds<i32>,
+        format: &ExportFormat,
+        writer: &mut W,
+    ) -> Result<usize> {
+        if let Some(header) = format.header() {
+            writer.write_all(header.as_bytes()).await?;
+        }
+        let mut written = 0;
+        let mut from = 0;
+        let mut lowest = i32::MAX;
+        loop {
+            let url = format!(
+                "{}/api/json?tree=allBuilds[number,url,result,duration,timestamp,actions[parameters[name,value]]]{{{},{}}}",
+                self.item_url(job),
+                from,
+                from + EXPORT_PAGE
+            );
+            let page: ExportPage = self.get_json(&url).await?;
+            let len = page.all_builds.len();
+            trace!("export page - job={}, from={}, builds={}", job, from, len);
+            let mut past_range = false;
+            // skip builds seen on the previous page, shifted by new builds
+            for build in &page.all_builds {
+                if build.number >= lowest {
+                    continue;
+                }
+                lowest = build.number;
+                past_range = match range.start_bound() {
+                    Bound::Included(start) => build.number < *start,
+                    Bound::Excluded(start) => build.number <= *start,
+                    Bound::Unbounded => false,
+                };
+                if past_range {
+                    break;
+                }
+                if range.contains(&build.number) {
+                    writer.write_all(format.row(build).as_bytes()).await?;
+                    written += 1;
+                }
+            }
+            from += len;
+            if len < EXPORT_PAGE || past_range {
+                break;
+            }
+        }
+        writer.flush().await?;
+        info!("export builds - job={}, builds={}", job, written);
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_rows() {
+        let build: ExportBuild = serde_json::from_str(
+            r#"{"number":7,"url":"http://ci/job/deploy/7/","result":"SUCCESS","duration":1500,"timestamp":1700000000000,
+                "actions":[{},{"parameters":[{"name":"ENV","value":"prod, eu"},{"name":"TOKEN"}]}]}"#,
+        )
+        .unwrap();
+        let columns = [
+            Column::Number,
+            Column::Result,
+            Column::Duration,
+            Column::Params,
+        ];
+        let csv = ExportFormat::csv(&columns);
+        assert_eq!(csv.header().unwrap(), "number,result,duration,params\n");
+        assert_eq!(
+            csv.row(&build),
+            "7,SUCCESS,1500,\"{\"\"ENV\"\":\"\"prod, eu\"\"}\"\n"
+        );
+        assert_eq!(
+            ExportFormat::ndjson(&columns).row(&build),
+            "{\"number\":7,\"result\":\"SUCCESS\",\"duration\":1500,\"params\":{\"ENV\":\"prod, eu\"}}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn export_folder_job() {
+        use crate::mock::{response, MockServer};
+
+        let server = MockServer::start(vec![response(
+            "200 OK",
+            &[],
+            r#"{"allBuilds":[{"number":3,"url":"u","result":"SUCCESS","duration":10,"timestamp":0,"actions":[]}]}"#,
+        )])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let mut out = Vec::new();
+        let written = cli
+            .export_builds(
+                "team/deploy",
+                ..,
+                &ExportFormat::ndjson(&[Column::Number]),
+                &mut out,
+            )
+            .await
+            .unwrap();
+        assert_eq!(written, 1);
+        assert_eq!(out, b"{\"number\":3}\n");
+        assert!(
+            server.requests()[0].starts_with("GET /job/team/job/deploy/api/json?tree=allBuilds")
+        );
+    }
+}
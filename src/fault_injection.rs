@@ -0,0 +1,174 @@
+//! Artificial Jenkins flakiness for testing retry and resume logic of consumers
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use jenkins_rs::fault_injection::FaultInjection;
+//!
+//! let cli = jenkins_rs::Jenkins::builder("http://localhost:8080", "bot", "token")
+//!     .fault_injection(FaultInjection {
+//!         server_error_rate: 0.1,
+//!         latency: Duration::from_secs(2),
+//!         latency_rate: 0.2,
+//!         truncate_rate: 0.05,
+//!         ..Default::default()
+//!     })
+//!     .build();
+//! ```
+
+use std::{sync::Mutex, time::Duration};
+
+use log::warn;
+use reqwest::{Method, Response, StatusCode, Url};
+use tokio::time::sleep;
+
+/// Probabilities of each fault per request, see
+/// [`JenkinsBuilder::fault_injection`](crate::JenkinsBuilder::fault_injection)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultInjection {
+    /// answer with a 500, 502, 503 or 504 without sending the request
+    pub server_error_rate: f64,
+    /// delay added before sending
+    pub latency: Duration,
+    pub latency_rate: f64,
+    /// cut the response body short at a random length
+    pub truncate_rate: f64,
+    /// same seed, same faults for the same sequence of requests
+    pub seed: u64,
+}
+
+impl Default for FaultInjection {
+    fn default() -> Self {
+        FaultInjection {
+            server_error_rate: 0.0,
+            latency: Duration::ZERO,
+            latency_rate: 0.0,
+            truncate_rate: 0.0,
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+const SERVER_ERRORS: [StatusCode; 4] = [
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+pub(crate) struct FaultInjector {
+    config: FaultInjection,
+    /// xorshift64 state
+    rng: Mutex<u64>,
+}
+
+impl FaultInjector {
+    pub(crate) fn new(config: FaultInjection) -> FaultInjector {
+        // xorshift never leaves zero
+        let seed = config.seed.max(1);
+        FaultInjector {
+            config,
+            rng: Mutex::new(seed),
+        }
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn roll(&self, rate: f64) -> bool {
+        rate > 0.0 && ((self.next() >> 11) as f64) < rate * (1u64 << 53) as f64
+    }
+
+    /// Delay the request, or replace it by a server error response
+    pub(crate) async fn before(&self, method: &Method, url: &Url) -> Option<Response> {
+        if self.roll(self.config.latency_rate) {
+            warn!(
+                "fault injection, latency - {} {}, latency={:?}",
+                method, url, self.config.latency
+            );
+            sleep(self.config.latency).await;
+        }
+        if !self.roll(self.config.server_error_rate) {
+            return None;
+        }
+        let status = SERVER_ERRORS[self.next() as usize % SERVER_ERRORS.len()];
+        warn!(
+            "fault injection, error - {} {}, status={}",
+            method, url, status
+        );
+        let mut res = http::Response::new("injected fault");
+        *res.status_mut() = status;
+        Some(res.into())
+    }
+
+    /// Cut the body of `res` short
+    pub(crate) async fn after(&self, res: Response) -> Result<Response, reqwest::Error> {
+        if !self.roll(self.config.truncate_rate) {
+            return Ok(res);
+        }
+        let (status, version, mut headers) = (res.status(), res.version(), res.headers().clone());
+        let url = res.url().clone();
+        let body = res.bytes().await?;
+        let len = if body.is_empty() {
+            0
+        } else {
+            self.next() as usize % body.len()
+        };
+        warn!(
+            "fault injection, truncated - url={}, len={}/{}",
+            url,
+            len,
+            body.len()
+        );
+        headers.remove(reqwest::header::CONTENT_LENGTH);
+        let mut truncated = http::Response::new(body.slice(..len));
+        *truncated.status_mut() = status;
+        *truncated.version_mut() = version;
+        *truncated.headers_mut() = headers;
+        Ok(truncated.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn inject_faults() {
+        let url: Url = "http://ci/api/json".parse().unwrap();
+        let always = FaultInjector::new(FaultInjection {
+            server_error_rate: 1.0,
+            truncate_rate: 1.0,
+            ..Default::default()
+        });
+        let res = always.before(&Method::GET, &url).await.unwrap();
+        assert!(res.status().is_server_error());
+        let res: Response = http::Response::new("{\"jobs\":[]}").into();
+        let body = always.after(res).await.unwrap().text().await.unwrap();
+        assert!(body.len() < 11 && "{\"jobs\":[]}".starts_with(&body));
+
+        let never = FaultInjector::new(FaultInjection::default());
+        assert!(never.before(&Method::GET, &url).await.is_none());
+
+        // half the requests fail, reproducibly
+        let sometimes = |seed| {
+            FaultInjector::new(FaultInjection {
+                server_error_rate: 0.5,
+                seed,
+                ..Default::default()
+            })
+        };
+        let (a, b) = (sometimes(7), sometimes(7));
+        let mut failed = 0;
+        for _ in 0..200 {
+            let fault = a.before(&Method::GET, &url).await.is_some();
+            assert_eq!(fault, b.before(&Method::GET, &url).await.is_some());
+            failed += fault as u32;
+        }
+        assert!((70..130).contains(&failed), "{}", failed);
+    }
+}
@@ -0,0 +1,216 @@
+//! Small glob matcher for filtering artifacts and jobs client-side
+//!
+//! Supports `*` (any run within a path segment), `**` as a whole segment
+//! (any number of segments, including none), `?` (one character except `/`)
+//! and `[...]` classes with ranges and `!`/`^` negation. `**` within a segment
+//! is a `*`.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    DoubleStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`
+    Any,
+    Pattern(Vec<Token>),
+}
+
+/// Compiled glob pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    segments: Vec<Segment>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    Token::DoubleStar
+                }
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => {
+                    let rest: String = chars.clone().collect();
+                    match parse_class(&rest) {
+                        Some((token, used)) => {
+                            for _ in 0..used {
+                                chars.next();
+                            }
+                            token
+                        }
+                        None => Token::Char('['),
+                    }
+                }
+                '\\' => Token::Char(chars.next().unwrap_or('\\')),
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+        }
+        let segments = tokens
+            .split(|t| *t == Token::Char('/'))
+            .map(|tokens| match tokens {
+                [Token::DoubleStar] => Segment::Any,
+                tokens => Segment::Pattern(
+                    tokens
+                        .iter()
+                        .map(|t| match t {
+                            Token::DoubleStar => Token::Star,
+                            t => t.clone(),
+                        })
+                        .collect(),
+                ),
+            })
+            .collect();
+        Glob { segments }
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        let parts: Vec<&str> = s.split('/').collect();
+        wildcard_match(
+            &self.segments,
+            &parts,
+            |segment| *segment == Segment::Any,
+            |segment, part| match segment {
+                Segment::Pattern(tokens) => {
+                    let chars: Vec<char> = part.chars().collect();
+                    wildcard_match(tokens, &chars, |t| *t == Token::Star, token_matches)
+                }
+                Segment::Any => unreachable!("handled as wildcard"),
+            },
+        )
+    }
+}
+
+/// Parse the class body after `[`, returns the token and chars consumed
+fn parse_class(rest: &str) -> Option<(Token, usize)> {
+    let chars: Vec<char> = rest.chars().collect();
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        first = false;
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&e| e != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Match `pattern` units against `text` units, where wildcards take any run
+/// of units and other pattern units take exactly one
+///
+/// Backtracks only to the last wildcard, in `O(pattern * text)` unit matches.
+fn wildcard_match<P, T>(
+    pattern: &[P],
+    text: &[T],
+    is_wildcard: impl Fn(&P) -> bool,
+    unit_matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // pattern index after the last wildcard and the text index it resumes at
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && is_wildcard(&pattern[p]) {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if p < pattern.len() && unit_matches(&pattern[p], &text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((wildcard_end, start)) = backtrack {
+            // let the wildcard take one more unit
+            p = wildcard_end;
+            t = start + 1;
+            backtrack = Some((wildcard_end, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(is_wildcard)
+}
+
+fn token_matches(token: &Token, &c: &char) -> bool {
+    match token {
+        Token::Char(t) => *t == c,
+        Token::Any => c != '/',
+        Token::Class { negated, ranges } => {
+            c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+        }
+        Token::Star | Token::DoubleStar => unreachable!("handled as wildcard"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        let jar = Glob::new("target/*.jar");
+        assert!(jar.is_match("target/app.jar"));
+        assert!(!jar.is_match("target/lib/dep.jar"));
+        assert!(!jar.is_match("target/app.war"));
+
+        let deep = Glob::new("**/*.xml");
+        assert!(deep.is_match("report.xml"));
+        assert!(deep.is_match("a/b/TEST-x.xml"));
+
+        assert!(Glob::new("deploy-*").is_match("deploy-prod"));
+        assert!(Glob::new("build-?").is_match("build-1"));
+        assert!(Glob::new("v[0-9].[!x]").is_match("v1.2"));
+        assert!(!Glob::new("v[0-9].[!x]").is_match("v1.x"));
+        assert!(Glob::new("a\\*").is_match("a*"));
+    }
+
+    #[test]
+    fn double_star_matches_whole_segments() {
+        let nested = Glob::new("a/**/b");
+        assert!(nested.is_match("a/b"));
+        assert!(nested.is_match("a/x/y/b"));
+        assert!(!nested.is_match("a/xb"));
+        assert!(!nested.is_match("ax/b"));
+
+        let reports = Glob::new("**/TEST-*.xml");
+        assert!(reports.is_match("TEST-a.xml"));
+        assert!(reports.is_match("x/y/TEST-a.xml"));
+        assert!(!reports.is_match("x/NOTTEST-a.xml"));
+
+        assert!(Glob::new("target/**").is_match("target/a/b.jar"));
+        // not a whole segment, same as `*`
+        assert!(Glob::new("a**.jar").is_match("app.jar"));
+        assert!(!Glob::new("a**.jar").is_match("a/b.jar"));
+    }
+
+    #[test]
+    fn pathological_patterns_are_fast() {
+        let path = format!("{}/c", vec!["a"; 40].join("/"));
+        assert!(!Glob::new(&"**/".repeat(20)).is_match(&format!("{}x", path)));
+        assert!(Glob::new(&format!("{}c", "**/".repeat(20))).is_match(&path));
+        let name = "a".repeat(60);
+        assert!(!Glob::new(&format!("{}b", "a*".repeat(20))).is_match(&name));
+    }
+}
@@ -0,0 +1,340 @@
+//! Local snapshot of jobs and their recent builds, kept up to date incrementally
+//!
+//! Each [`Indexer::refresh`] lists all jobs in one request and only fetches
+//! the builds of jobs whose `nextBuildNumber` moved or which had a build
+//! running. Jenkins doesn't send `Last-Modified` for the JSON API, so the
+//! build number is the change marker.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::RwLock,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::{info, trace};
+use serde::{Deserialize, Serialize};
+
+use crate::{client::FETCH_CONCURRENCY, BuildSummary, Jenkins};
+
+/// Builds kept per job unless set with [`Indexer::keep_builds`]
+const KEEP_BUILDS: usize = 20;
+
+/// Job in the snapshot with its most recent builds, newest first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexedJob {
+    pub name: String,
+    pub url: String,
+    pub next_build_number: i32,
+    pub builds: Vec<BuildSummary>,
+}
+
+/// Change found by [`Indexer::refresh`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexEvent {
+    JobAdded(String),
+    JobRemoved(String),
+    BuildStarted {
+        job: String,
+        number: i32,
+    },
+    BuildFinished {
+        job: String,
+        number: i32,
+        result: Option<String>,
+    },
+}
+
+/// Where the snapshot lives, [`MemoryStore`] or e.g. an embedded database
+pub trait IndexStore: Send + Sync {
+    fn job(&self, name: &str) -> Option<IndexedJob>;
+
+    fn put_job(&self, job: IndexedJob);
+
+    fn remove_job(&self, name: &str);
+
+    fn job_names(&self) -> Vec<String>;
+}
+
+/// Snapshot held in memory, lost on restart
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    jobs: RwLock<BTreeMap<String, IndexedJob>>,
+}
+
+impl IndexStore for MemoryStore {
+    fn job(&self, name: &str) -> Option<IndexedJob> {
+        let jobs = self.jobs.read().unwrap_or_else(|e| e.into_inner());
+        jobs.get(name).cloned()
+    }
+
+    fn put_job(&self, job: IndexedJob) {
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        jobs.insert(job.name.clone(), job);
+    }
+
+    fn remove_job(&self, name: &str) {
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        jobs.remove(name);
+    }
+
+    fn job_names(&self) -> Vec<String> {
+        let jobs = self.jobs.read().unwrap_or_else(|e| e.into_inner());
+        jobs.keys().cloned().collect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct JobsRes {
+    jobs: Vec<JobMarker>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JobMarker {
+    name: String,
+    url: String,
+    /// missing for folders
+    next_build_number: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BuildsRes {
+    builds: Vec<BuildSummary>,
+}
+
+/// Events turning the builds of `old` into those of `new`
+fn diff_builds(old: Option<&IndexedJob>, new: &IndexedJob) -> Vec<IndexEvent> {
+    let mut events = Vec::new();
+    if old.is_none() {
+        events.push(IndexEvent::JobAdded(new.name.clone()));
+    }
+    let known: BTreeMap<i32, &BuildSummary> = old
+        .map(|job| job.builds.iter().map(|b| (b.number, b)).collect())
+        .unwrap_or_default();
+    // oldest first, so started comes before finished
+    for build in new.builds.iter().rev() {
+        let before = known.get(&build.number);
+        // builds existing before the job was first indexed are not news
+        if before.is_none() && old.is_some() {
+            events.push(IndexEvent::BuildStarted {
+                job: new.name.clone(),
+                number: build.number,
+            });
+        }
+        let was_running = before.is_some_and(|b| b.building) || (before.is_none() && old.is_some());
+        if was_running && !build.building {
+            events.push(IndexEvent::BuildFinished {
+                job: new.name.clone(),
+                number: build.number,
+                result: build.result.clone(),
+            });
+        }
+    }
+    events
+}
+
+/// Keeps an [`IndexStore`] in sync with the top level jobs of Jenkins
+///
+/// ```no_run
+/// # async fn f(cli: jenkins_rs::Jenkins) -> anyhow::Result<()> {
+/// use jenkins_rs::indexer::{IndexEvent, Indexer, MemoryStore};
+///
+/// let indexer = Indexer::new(cli, MemoryStore::default());
+/// indexer
+///     .run(std::time::Duration::from_secs(30), |event| {
+///         if let IndexEvent::BuildFinished { job, number, result } = event {
+///             println!("{} #{} finished: {:?}", job, number, result);
+///         }
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Indexer<S = MemoryStore> {
+    jenkins: Jenkins,
+    store: S,
+    keep_builds: usize,
+    concurrency: usize,
+}
+
+impl<S: IndexStore> Indexer<S> {
+    pub fn new(jenkins: Jenkins, store: S) -> Indexer<S> {
+        Indexer {
+            jenkins,
+            store,
+            keep_builds: KEEP_BUILDS,
+            concurrency: FETCH_CONCURRENCY,
+        }
+    }
+
+    /// Builds kept per job, 20 by default
+    pub fn keep_builds(mut self, keep: usize) -> Self {
+        self.keep_builds = keep;
+        self
+    }
+
+    /// Max jobs fetched at once during a refresh, 8 by default
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Bring the snapshot up to date, returning what changed since the last refresh
+    ///
+    /// The first refresh reports every job as added without build events.
+    pub async fn refresh(&self) -> Result<Vec<IndexEvent>> {
+        let url = format!(
+            "{}/api/json?tree=jobs[name,url,nextBuildNumber]",
+            self.jenkins.url
+        );
+        let res: JobsRes = self.jenkins.get_json(&url).await?;
+        let jobs: Vec<(JobMarker, i32)> = res
+            .jobs
+            .into_iter()
+            .filter_map(|j| j.next_build_number.map(|n| (j, n)))
+            .collect();
+
+        let stale: Vec<_> = jobs
+            .iter()
+            .filter_map(|(job, next)| {
+                let old = self.store.job(&job.name);
+                let changed = old.as_ref().is_none_or(|old| {
+                    old.next_build_number != *next || old.builds.iter().any(|b| b.building)
+                });
+                changed.then_some((job, *next, old))
+            })
+            .collect();
+        let urls: Vec<&str> = stale.iter().map(|(job, _, _)| job.url.as_str()).collect();
+        let tree = format!(
+            "builds[number,url,building,result,duration,timestamp]{{0,{}}}",
+            self.keep_builds
+        );
+        let fetched: Vec<BuildsRes> = self
+            .jenkins
+            .fetch_many(&urls, &tree, self.concurrency)
+            .await?;
+        let updated = stale
+            .iter()
+            .zip(fetched)
+            .map(|((job, next, _), res)| IndexedJob {
+                name: job.name.clone(),
+                url: job.url.clone(),
+                next_build_number: *next,
+                builds: res.builds,
+            });
+
+        let mut events = Vec::new();
+        for ((_, _, old), new) in stale.iter().zip(updated) {
+            events.extend(diff_builds(old.as_ref(), &new));
+            self.store.put_job(new);
+        }
+        let current: HashSet<&str> = jobs.iter().map(|(j, _)| j.name.as_str()).collect();
+        for name in self.store.job_names() {
+            if !current.contains(name.as_str()) {
+                self.store.remove_job(&name);
+                events.push(IndexEvent::JobRemoved(name));
+            }
+        }
+        trace!(
+            "index refresh - jobs={}, fetched={}, events={}",
+            jobs.len(),
+            stale.len(),
+            events.len()
+        );
+        Ok(events)
+    }
+
+    /// Refresh every `interval` and pass each change to `on_event`
+    ///
+    /// Runs until a refresh fails or the client is shut down, see
+    /// [`Shutdown`](crate::shutdown::Shutdown).
+    pub async fn run(
+        &self,
+        interval: Duration,
+        mut on_event: impl FnMut(IndexEvent),
+    ) -> Result<()> {
+        info!("indexer started - interval={:?}", interval);
+        loop {
+            for event in self.refresh().await? {
+                on_event(event);
+            }
+            self.jenkins.pause(interval).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn diff_indexed_builds() {
+        let build = |number, building| BuildSummary {
+            number,
+            url: format!("http://ci/job/api/{}/", number),
+            building,
+            result: (!building).then(|| "SUCCESS".to_owned()),
+            duration: Duration::ZERO,
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
+        let job = |builds| IndexedJob {
+            name: "api".to_owned(),
+            url: "http://ci/job/api/".to_owned(),
+            next_build_number: 13,
+            builds,
+        };
+        let first = job(vec![build(11, true), build(10, false)]);
+        assert_eq!(
+            diff_builds(None, &first),
+            [IndexEvent::JobAdded("api".to_owned())]
+        );
+
+        let second = job(vec![build(13, true), build(12, false), build(11, false)]);
+        let finished = |number| IndexEvent::BuildFinished {
+            job: "api".to_owned(),
+            number,
+            result: Some("SUCCESS".to_owned()),
+        };
+        let started = |number| IndexEvent::BuildStarted {
+            job: "api".to_owned(),
+            number,
+        };
+        assert_eq!(
+            diff_builds(Some(&first), &second),
+            [finished(11), started(12), finished(12), started(13)]
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_fetches_stale_jobs() {
+        use crate::mock::{response, MockServer};
+
+        let builds = r#"{"builds":[{"number":4,"url":"u","building":false,"result":"SUCCESS","duration":10,"timestamp":0}]}"#;
+        let server = MockServer::start(vec![
+            response(
+                "200 OK",
+                &[],
+                r#"{"jobs":[{"name":"api","url":"job/api/","nextBuildNumber":5},{"name":"web","url":"job/web/","nextBuildNumber":5}]}"#,
+            ),
+            response("200 OK", &[], builds),
+            response("200 OK", &[], builds),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let indexer = Indexer::new(cli, MemoryStore::default()).concurrency(1);
+        let events = indexer.refresh().await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(indexer.store().job("web").unwrap().builds[0].number, 4);
+
+        let requests = server.requests();
+        assert!(requests[1].starts_with("GET /job/api/api/json?tree=builds%5B"));
+        assert!(requests[2].starts_with("GET /job/web/api/json?tree=builds%5B"));
+    }
+}
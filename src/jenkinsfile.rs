@@ -0,0 +1,454 @@
+//! Reading parameters out of pipeline scripts, for jobs that haven't run yet
+//!
+//! Jenkins only knows the parameters of a pipeline once a build ran its
+//! `properties([parameters([...])])` step or declarative `parameters {}`
+//! directive. [`parse_parameters`] finds them in the script itself.
+//!
+//! The script of a build that ran, e.g. a Jenkinsfile from SCM, is read from
+//! its replay page, see [`Jenkins::get_replay_script`].
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    job_config::{JobConfig, PipelineDefinition},
+    parameters::ParameterDefinition,
+    xml, Error, Jenkins,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(String),
+    Punct(char),
+}
+
+/// Split Groovy source into tokens, dropping comments and whitespace
+///
+/// Only as much Groovy as parameter definitions need: GStrings are kept
+/// verbatim and slashy strings come out as punctuation.
+fn tokenize(script: &str) -> Vec<Token> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' || c == '"' {
+            let triple = next == Some(c) && chars.get(i + 2) == Some(&c);
+            i += if triple { 3 } else { 1 };
+            let mut s = String::new();
+            while i < chars.len() {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    match chars[i + 1] {
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        e @ ('\\' | '\'' | '"' | '$') => s.push(e),
+                        e => {
+                            s.push('\\');
+                            s.push(e);
+                        }
+                    }
+                    i += 2;
+                    continue;
+                }
+                let closes = chars[i] == c
+                    && (!triple || chars.get(i + 1) == Some(&c) && chars.get(i + 2) == Some(&c));
+                if closes {
+                    i += if triple { 3 } else { 1 };
+                    break;
+                }
+                s.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token::Str(s));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Num(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn is_punct(token: Option<&Token>, c: char) -> bool {
+    token == Some(&Token::Punct(c))
+}
+
+/// Index of the `,` or closing bracket ending the expression starting at `i`
+fn skip_expr(tokens: &[Token], mut i: usize) -> usize {
+    let mut depth = 0;
+    while let Some(token) = tokens.get(i) {
+        match token {
+            Token::Punct('(' | '[' | '{') => depth += 1,
+            Token::Punct(')' | ']' | '}') if depth == 0 => break,
+            Token::Punct(')' | ']' | '}') => depth -= 1,
+            Token::Punct(',') if depth == 0 => break,
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Literal value at `i` and the index after it, `null` for any other expression
+fn parse_value(tokens: &[Token], i: usize) -> (Value, usize) {
+    let (value, next) = match tokens.get(i) {
+        Some(Token::Str(s)) => (Value::String(s.clone()), i + 1),
+        Some(Token::Ident(b)) if b == "true" || b == "false" => (Value::Bool(b == "true"), i + 1),
+        Some(Token::Num(n)) => (n.parse().unwrap_or(Value::Null), i + 1),
+        Some(Token::Punct('[')) => {
+            let mut items = Vec::new();
+            let mut j = i + 1;
+            while j < tokens.len() && !is_punct(tokens.get(j), ']') {
+                let (item, next) = parse_value(tokens, j);
+                items.push(item);
+                j = next;
+                if is_punct(tokens.get(j), ',') {
+                    j += 1;
+                }
+            }
+            (Value::Array(items), j + 1)
+        }
+        _ => (Value::Null, i),
+    };
+    let end = skip_expr(tokens, next);
+    if end == next {
+        (value, end)
+    } else {
+        // e.g. string concatenation or a method call
+        (Value::Null, end)
+    }
+}
+
+/// Named arguments of the call whose `(` is at `i`, and the index after its `)`
+fn parse_call(tokens: &[Token], i: usize) -> (Map<String, Value>, usize) {
+    let mut args = Map::new();
+    let mut j = i + 1;
+    while j < tokens.len() {
+        match (&tokens[j], tokens.get(j + 1)) {
+            (Token::Punct(')'), _) => return (args, j + 1),
+            (Token::Punct(','), _) => j += 1,
+            (Token::Ident(key) | Token::Str(key), Some(Token::Punct(':'))) => {
+                let (value, next) = parse_value(tokens, j + 2);
+                args.insert(key.clone(), value);
+                j = next;
+            }
+            _ => {
+                let next = skip_expr(tokens, j);
+                // stray closing bracket
+                j = if next == j { j + 1 } else { next };
+            }
+        }
+    }
+    (args, j)
+}
+
+/// Definition in the JSON shape of the Jenkins API for a parameter step
+fn definition(step: &str, mut args: Map<String, Value>) -> ParameterDefinition {
+    let class = match step {
+        "string" => "hudson.model.StringParameterDefinition",
+        "text" => "hudson.model.TextParameterDefinition",
+        "booleanParam" => "hudson.model.BooleanParameterDefinition",
+        "password" => "hudson.model.PasswordParameterDefinition",
+        "choice" => "hudson.model.ChoiceParameterDefinition",
+        "file" => "hudson.model.FileParameterDefinition",
+        "stashedFile" => "io.jenkins.plugins.file_parameters.StashedFileParameterDefinition",
+        "base64File" => "io.jenkins.plugins.file_parameters.Base64FileParameterDefinition",
+        "run" => "hudson.model.RunParameterDefinition",
+        "credentials" => "com.cloudbees.plugins.credentials.CredentialsParameterDefinition",
+        "gitParameter" => "net.uaznia.lukanus.hudson.plugins.gitparameter.GitParameterDefinition",
+        other => other,
+    };
+    // the git parameter takes `defaultValue` as is
+    if step != "gitParameter" {
+        if let Some(default) = args.remove("defaultValue") {
+            args.insert(
+                "defaultParameterValue".to_owned(),
+                json!({ "value": default }),
+            );
+        }
+    }
+    if let Some(Value::String(choices)) = args.get("choices") {
+        let choices: Vec<Value> = choices.lines().map(|c| c.into()).collect();
+        args.insert("choices".to_owned(), choices.into());
+    }
+    args.insert("_class".to_owned(), class.into());
+    ParameterDefinition::from_json(Value::Object(args))
+}
+
+/// Parameters declared in a pipeline script, in declaration order
+///
+/// Reads `parameters {}` of declarative pipelines and
+/// `properties([parameters([...])])` of scripted ones. Defaults that are not
+/// literals come out empty. Steps of plugins not modeled in
+/// [`ParameterDefinition`] are returned as `Unknown` with the step name as
+/// class and the arguments as `raw`.
+pub fn parse_parameters(script: &str) -> Vec<ParameterDefinition> {
+    let tokens = tokenize(script);
+    let mut params: Vec<ParameterDefinition> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let opens = matches!(tokens.get(i + 1), Some(Token::Punct('{' | '(')));
+        if tokens[i] != Token::Ident("parameters".to_owned()) || !opens {
+            i += 1;
+            continue;
+        }
+        let end = skip_expr(&tokens, i + 2);
+        let mut j = i + 2;
+        while j < end {
+            match (&tokens[j], tokens.get(j + 1)) {
+                (Token::Ident(step), Some(Token::Punct('('))) => {
+                    let (args, next) = parse_call(&tokens, j + 1);
+                    let definition = definition(step, args);
+                    let known = params.iter().any(|p| p.name() == definition.name());
+                    if definition.name().is_some() && !known {
+                        params.push(definition);
+                    }
+                    j = next;
+                }
+                _ => j += 1,
+            }
+        }
+        i = end;
+    }
+    params
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LastBuildRes {
+    last_build: Option<LastBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastBuild {
+    number: i32,
+}
+
+/// `name` and unescaped content of each `<textarea>` in `html`
+fn textareas(html: &str) -> Result<Vec<(String, String)>> {
+    let mut areas = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("<textarea") {
+        rest = &rest[start..];
+        let Some(open_end) = rest.find('>') else {
+            bail!("jenkinsfile: unterminated textarea")
+        };
+        let tag = &rest[..open_end];
+        let name = tag
+            .split_once("name=\"")
+            .and_then(|(_, n)| n.split_once('"'))
+            .map(|(n, _)| n.to_owned())
+            .unwrap_or_default();
+        rest = &rest[open_end + 1..];
+        let Some(close) = rest.find("</textarea>") else {
+            bail!("jenkinsfile: unterminated textarea")
+        };
+        areas.push((name, xml::unescape(&rest[..close])?));
+        rest = &rest[close..];
+    }
+    Ok(areas)
+}
+
+/// `mainScript` and the loaded scripts of a replay page
+fn replay_scripts(html: &str) -> Result<Vec<(String, String)>> {
+    let mut scripts: Vec<(String, String)> = textareas(html)?
+        .into_iter()
+        .map(|(name, script)| (name.trim_start_matches("_.").to_owned(), script))
+        .collect();
+    match scripts.iter().position(|(name, _)| name == "mainScript") {
+        Some(main) => {
+            let main = scripts.remove(main);
+            scripts.insert(0, main);
+            Ok(scripts)
+        }
+        None => bail!(Error::APIError("replay: main script not found".to_owned())),
+    }
+}
+
+impl Jenkins {
+    /// Scripts on the replay page of a pipeline build, `mainScript` first
+    async fn replay_scripts(&self, job: &str, number: i32) -> Result<Vec<(String, String)>> {
+        let url = format!("{}/job/{}/{}/replay/", self.url, job, number);
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get replay - job={}, number={}, res={:?}", job, number, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let html = res.text().await.map_err(Error::NetworkError)?;
+        replay_scripts(&html)
+    }
+
+    /// Get the pipeline script a build ran, as shown on its replay page
+    ///
+    /// Needs the Replay permission on the job.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_replay_script(&self, job: &str, number: i32) -> Result<String> {
+        let mut scripts = self.replay_scripts(job, number).await?;
+        Ok(scripts.swap_remove(0).1)
+    }
+
+    /// Get the scripts a build loaded with the `load` step, by name, e.g. `Script1`
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    ///
+    pub async fn get_replay_loaded_scripts(
+        &self,
+        job: &str,
+        number: i32,
+    ) -> Result<BTreeMap<String, String>> {
+        let scripts = self.replay_scripts(job, number).await?;
+        Ok(scripts.into_iter().skip(1).collect())
+    }
+
+    /// Parameters of a job, read from its pipeline script when Jenkins has
+    /// none yet
+    ///
+    /// Falls back to [`parse_parameters`] on the inline script of the job,
+    /// or on the Jenkinsfile of its last build when it comes from SCM.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn discover_parameters(&self, job: &str) -> Result<Vec<ParameterDefinition>> {
+        let defined = self.get_parameter_definitions(job).await?;
+        if !defined.is_empty() {
+            return Ok(defined);
+        }
+        let script = match self.get_job_config_model(job).await? {
+            JobConfig::Pipeline(pipeline) => match pipeline.definition {
+                PipelineDefinition::Script { script, .. } => script,
+                PipelineDefinition::Scm { .. } => {
+                    let url = format!("{}/job/{}/api/json?tree=lastBuild[number]", self.url, job);
+                    let res: LastBuildRes = self.get_json(&url).await?;
+                    match res.last_build {
+                        Some(build) => self.get_replay_script(job, build.number).await?,
+                        // the Jenkinsfile is in SCM, out of reach
+                        None => return Ok(defined),
+                    }
+                }
+            },
+            _ => return Ok(defined),
+        };
+        let params = parse_parameters(&script);
+        info!(
+            "parameters from script - job={}, params={}",
+            job,
+            params.len()
+        );
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_script_parameters() {
+        let declarative = r#"
+            pipeline {
+                agent any
+                parameters {
+                    // where to deploy
+                    string(name: 'ENV', defaultValue: 'staging', description: "target")
+                    booleanParam(name: 'DRY_RUN', defaultValue: true)
+                    choice(name: 'REGION', choices: ['eu', 'us'])
+                    text(name: 'NOTES', defaultValue: "${env.BUILD_TAG} notes")
+                    /* extendedChoice(name: 'OLD') */
+                    gitParameter(name: 'TAG', type: 'PT_TAG', defaultValue: 'v1')
+                    activeChoice(name: 'HOST', choiceType: 'PT_SINGLE_SELECT', script: groovyScript())
+                }
+                stages { stage('Deploy') { steps { sh "deploy ${params.ENV}" } } }
+            }"#;
+        let params = parse_parameters(declarative);
+        let names: Vec<_> = params.iter().map(|p| p.name().unwrap()).collect();
+        assert_eq!(names, ["ENV", "DRY_RUN", "REGION", "NOTES", "TAG", "HOST"]);
+        match &params[0] {
+            ParameterDefinition::String(p) => {
+                assert_eq!(p.default.as_deref(), Some("staging"));
+                assert_eq!(p.description.as_deref(), Some("target"));
+            }
+            p => panic!("{:?}", p),
+        }
+        assert!(matches!(&params[1], ParameterDefinition::Boolean(p) if p.default == Some(true)));
+        assert!(matches!(&params[2], ParameterDefinition::Choice(p) if p.choices == ["eu", "us"]));
+        assert!(matches!(&params[4], ParameterDefinition::Git(p) if p.kind == "PT_TAG"));
+        assert!(
+            matches!(&params[5], ParameterDefinition::Unknown { class, .. } if class == "activeChoice")
+        );
+
+        let scripted = "properties([\n\
+            disableConcurrentBuilds(),\n\
+            parameters([choice(name: 'ENV', choices: 'dev\\nprod', description: ''), \
+            credentials(name: 'KEY', credentialType: 'Username with password', required: true)])\n\
+            ])\nnode { echo 'hi' }";
+        let params = parse_parameters(scripted);
+        assert!(
+            matches!(&params[0], ParameterDefinition::Choice(p) if p.choices == ["dev", "prod"])
+        );
+        assert!(matches!(&params[1], ParameterDefinition::Credentials(p) if p.required));
+    }
+
+    #[test]
+    fn scrape_replay_scripts() {
+        let html = r#"<form method="post" action="run">
+            <textarea name="_.mainScript" class="workflow-editor">node {
+  load &apos;ci/deploy.groovy&apos;
+  echo &quot;a &amp;&amp; b &lt; c&quot;
+}</textarea>
+            <textarea name="_.Script1" class="workflow-editor">return this</textarea>
+            </form>"#;
+        let scripts = replay_scripts(html).unwrap();
+        assert_eq!(
+            scripts[0],
+            (
+                "mainScript".to_owned(),
+                "node {\n  load 'ci/deploy.groovy'\n  echo \"a && b < c\"\n}".to_owned()
+            )
+        );
+        assert_eq!(scripts[1], ("Script1".to_owned(), "return this".to_owned()));
+        assert!(replay_scripts("<html>Not a pipeline</html>").is_err());
+    }
+}
@@ -0,0 +1,1051 @@
+//! Jobs and folders: configuration, triggers, permissions and listing
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+#[cfg(feature = "job-config")]
+use crate::job_config;
+#[cfg(feature = "extras")]
+use crate::Extras;
+use crate::{
+    glob,
+    join::{try_join_all, BoxFuture},
+    xml, Error, FolderRes, Jenkins, JobNode, JobRes,
+};
+
+#[derive(Deserialize, Debug)]
+struct JobStatsRes {
+    builds: Vec<JobStatsBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JobStatsBuild {
+    number: i32,
+    #[serde(default)]
+    building: bool,
+    result: Option<String>,
+    #[serde(with = "crate::model::duration_millis")]
+    duration: Duration,
+}
+
+/// Statistics of recent builds, see [`Jenkins::job_stats`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobStats {
+    /// finished builds looked at
+    pub builds: usize,
+    /// share of `SUCCESS` builds, `0.0` without builds
+    pub success_rate: f64,
+    pub mean_duration: Duration,
+    pub p50_duration: Duration,
+    pub p90_duration: Duration,
+    pub p95_duration: Duration,
+    /// unsuccessful builds since the last successful one
+    pub failure_streak: usize,
+    pub last_success: Option<i32>,
+}
+
+impl JobStats {
+    /// `builds` ordered newest first
+    fn new(builds: &[JobStatsBuild]) -> JobStats {
+        let finished: Vec<&JobStatsBuild> = builds.iter().filter(|b| !b.building).collect();
+        if finished.is_empty() {
+            return JobStats::default();
+        }
+        let is_success = |b: &JobStatsBuild| b.result.as_deref() == Some("SUCCESS");
+        let mut durations: Vec<Duration> = finished.iter().map(|b| b.duration).collect();
+        durations.sort_unstable();
+        // nearest rank
+        let percentile = |p: usize| durations[(durations.len() * p).div_ceil(100).max(1) - 1];
+        JobStats {
+            builds: finished.len(),
+            success_rate: finished.iter().filter(|b| is_success(b)).count() as f64
+                / finished.len() as f64,
+            mean_duration: durations.iter().sum::<Duration>() / durations.len() as u32,
+            p50_duration: percentile(50),
+            p90_duration: percentile(90),
+            p95_duration: percentile(95),
+            failure_streak: finished.iter().take_while(|b| !is_success(b)).count(),
+            last_success: finished.iter().find(|b| is_success(b)).map(|b| b.number),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct JobListEntry {
+    #[serde(flatten)]
+    job: JobRes,
+    /// only present on folders
+    jobs: Option<Vec<serde::de::IgnoredAny>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JobListRes {
+    jobs: Vec<JobListEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatrixAxis {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatrixConfiguration {
+    /// axis values formatted as `AXIS1=a,AXIS2=b`
+    pub name: String,
+    pub url: String,
+    pub color: Option<String>,
+}
+
+impl MatrixConfiguration {
+    pub fn combination(&self) -> Result<Combination> {
+        self.name.parse()
+    }
+}
+
+/// See [`Jenkins::list_matrix_configurations`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MatrixJobRes {
+    pub axes: Vec<MatrixAxis>,
+    pub active_configurations: Vec<MatrixConfiguration>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+/// Axis values identifying a matrix configuration, in axis order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Combination(pub Vec<(String, String)>);
+
+impl fmt::Display for Combination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (axis, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}={}", axis, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Combination {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.split(',')
+            .map(|pair| match pair.split_once('=') {
+                Some((axis, value)) => Ok((axis.to_owned(), value.to_owned())),
+                None => bail!(Error::APIError(format!("invalid combination: {}", s))),
+            })
+            .collect::<Result<_>>()
+            .map(Combination)
+    }
+}
+
+/// See [`Jenkins::validate_jenkinsfile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JenkinsfileValidation {
+    pub success: bool,
+    /// diagnostics like `WorkflowScript: 3: Unknown stage section "foo" @ line 3, column 5.`
+    pub errors: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConverterRes<T> {
+    data: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct ValidationData {
+    result: String,
+    #[serde(default)]
+    errors: Vec<ValidationError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ValidationError {
+    error: ValidationMessages,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ValidationMessages {
+    One(String),
+    Many(Vec<String>),
+}
+
+const FOLDER_CONFIG: &str =
+    "<?xml version='1.1' encoding='UTF-8'?>\n<com.cloudbees.hudson.plugins.folder.Folder/>";
+
+/// Options of [`Jenkins::create_pipeline_job`]
+#[cfg(feature = "job-config")]
+#[derive(Debug, Clone)]
+pub struct PipelineJobOptions {
+    pub description: String,
+    /// run the script in Groovy sandbox, defaults to `true`
+    pub sandbox: bool,
+    pub disabled: bool,
+}
+
+#[cfg(feature = "job-config")]
+impl Default for PipelineJobOptions {
+    fn default() -> Self {
+        PipelineJobOptions {
+            description: String::new(),
+            sandbox: true,
+            disabled: false,
+        }
+    }
+}
+
+const TIMER_TRIGGER: &str = "hudson.triggers.TimerTrigger";
+
+const SCM_TRIGGER: &str = "hudson.triggers.SCMTrigger";
+
+const PIPELINE_TRIGGERS_PROPERTY: &str =
+    "org.jenkinsci.plugins.workflow.job.properties.PipelineTriggersJobProperty";
+
+/// Build trigger in a job's `config.xml`, see [`Jenkins::get_triggers`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// "Build periodically"
+    Timer { spec: String },
+    /// "Poll SCM"
+    Scm {
+        spec: String,
+        ignore_post_commit_hooks: bool,
+    },
+    /// trigger of other plugins, kept as raw xml
+    Other(xml::Element),
+}
+
+impl Trigger {
+    fn from_element(e: &xml::Element) -> Trigger {
+        match e.name.as_str() {
+            TIMER_TRIGGER => Trigger::Timer {
+                spec: e.child_text("spec").unwrap_or_default(),
+            },
+            SCM_TRIGGER => Trigger::Scm {
+                spec: e.child_text("spec").unwrap_or_default(),
+                ignore_post_commit_hooks: e.child_text("ignorePostCommitHooks").as_deref()
+                    == Some("true"),
+            },
+            _ => Trigger::Other(e.clone()),
+        }
+    }
+
+    fn to_element(&self) -> xml::Element {
+        match self {
+            Trigger::Timer { spec } => {
+                let mut e = xml::Element::new(TIMER_TRIGGER);
+                e.push(xml::Element::with_text("spec", spec));
+                e
+            }
+            Trigger::Scm {
+                spec,
+                ignore_post_commit_hooks,
+            } => {
+                let mut e = xml::Element::new(SCM_TRIGGER);
+                e.push(xml::Element::with_text("spec", spec));
+                e.push(xml::Element::with_text(
+                    "ignorePostCommitHooks",
+                    &ignore_post_commit_hooks.to_string(),
+                ));
+                e
+            }
+            Trigger::Other(e) => e.clone(),
+        }
+    }
+
+    /// Parse triggers of a freestyle (`<project>`) or pipeline (`<flow-definition>`) config
+    pub fn parse_all(root: &xml::Element) -> Vec<Trigger> {
+        root.child("triggers")
+            .or_else(|| root.path(&["properties", PIPELINE_TRIGGERS_PROPERTY, "triggers"]))
+            .map(|t| t.elements().map(Trigger::from_element).collect())
+            .unwrap_or_default()
+    }
+
+    /// Replace triggers of a freestyle or pipeline config in place
+    pub fn replace_all(root: &mut xml::Element, triggers: &[Trigger]) {
+        if triggers.is_empty() && Trigger::parse_all(root).is_empty() {
+            return;
+        }
+        let container = if root.name == "flow-definition" {
+            root.child_or_insert("properties")
+                .child_or_insert(PIPELINE_TRIGGERS_PROPERTY)
+                .child_or_insert("triggers")
+        } else {
+            root.child_or_insert("triggers")
+        };
+        container.children = triggers
+            .iter()
+            .map(|t| xml::Node::Element(t.to_element()))
+            .collect();
+    }
+}
+
+/// Upstream/downstream relations of jobs, see [`Jenkins::get_dependency_graph`]
+///
+/// Jobs are keyed by full name, e.g. `folder/job`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// upstream job to its downstream jobs, every job has an entry
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    fn new(jobs: Vec<DependencyJob>) -> DependencyGraph {
+        let mut graph = DependencyGraph::default();
+        let mut pending = jobs;
+        while let Some(job) = pending.pop() {
+            if FOLDER_CLASSES.contains(&job.class.as_str()) {
+                pending.extend(job.jobs);
+                continue;
+            }
+            graph.edges.entry(job.full_name.clone()).or_default();
+            for down in job.downstream_projects {
+                graph.add_edge(&job.full_name, &down.full_name);
+            }
+            for up in job.upstream_projects {
+                graph.add_edge(&up.full_name, &job.full_name);
+            }
+            pending.extend(job.jobs);
+        }
+        graph
+    }
+
+    fn add_edge(&mut self, upstream: &str, downstream: &str) {
+        self.edges
+            .entry(upstream.to_owned())
+            .or_default()
+            .insert(downstream.to_owned());
+        self.edges.entry(downstream.to_owned()).or_default();
+    }
+
+    /// Jobs triggered directly by `job`
+    pub fn downstream(&self, job: &str) -> impl Iterator<Item = &str> {
+        self.edges
+            .get(job)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Jobs triggering `job` directly
+    pub fn upstream<'a>(&'a self, job: &'a str) -> impl Iterator<Item = &'a str> {
+        self.edges
+            .iter()
+            .filter(move |(_, down)| down.contains(job))
+            .map(|(up, _)| up.as_str())
+    }
+
+    /// Jobs without upstream
+    pub fn roots(&self) -> impl Iterator<Item = &str> {
+        self.edges
+            .keys()
+            .filter(|job| self.upstream(job).next().is_none())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct DependencyJobsRes {
+    jobs: Vec<DependencyJob>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DependencyJob {
+    #[serde(rename = "_class", default)]
+    class: String,
+    full_name: String,
+    #[serde(default)]
+    downstream_projects: Vec<DependencyProject>,
+    #[serde(default)]
+    upstream_projects: Vec<DependencyProject>,
+    /// children of folders
+    #[serde(default)]
+    jobs: Vec<DependencyJob>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DependencyProject {
+    full_name: String,
+}
+
+/// Item groups holding jobs, not part of the dependency graph themselves
+const FOLDER_CLASSES: &[&str] = &[
+    "com.cloudbees.hudson.plugins.folder.Folder",
+    "jenkins.branch.OrganizationFolder",
+    "org.jenkinsci.plugins.workflow.multibranch.WorkflowMultiBranchProject",
+];
+
+/// Folder levels searched by [`Jenkins::get_dependency_graph`]
+const DEPENDENCY_FOLDER_DEPTH: usize = 3;
+
+/// Full name `a/b` of an item given by full name or in the `a/job/b` form the
+/// job name arguments take
+pub(crate) fn full_name(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let job_form = segments.len() > 1
+        && segments.len() % 2 == 1
+        && segments.iter().skip(1).step_by(2).all(|s| *s == "job");
+    if job_form {
+        segments
+            .into_iter()
+            .step_by(2)
+            .collect::<Vec<_>>()
+            .join("/")
+    } else {
+        segments.join("/")
+    }
+}
+
+impl Jenkins {
+    /// List axes and configurations of a matrix (multi-configuration) job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn list_matrix_configurations(&self, job: &str) -> Result<MatrixJobRes> {
+        let url = format!(
+            "{}/job/{}/api/json?tree=axes[name,values],activeConfigurations[name,url,color]",
+            self.url, job
+        );
+        self.get_json(&url).await
+    }
+
+    /// Validate a declarative Jenkinsfile with pipeline-model-definition plugin
+    ///
+    /// ## Arguments
+    ///
+    /// * `content` - Jenkinsfile content
+    ///
+    pub async fn validate_jenkinsfile(&self, content: &str) -> Result<JenkinsfileValidation> {
+        let url = format!("{}/pipeline-model-converter/validateJenkinsfile", self.url);
+        let res = self
+            .send(self.post(&url).form(&[("jenkinsfile", content)]))
+            .await?;
+        if !res.status().is_success() {
+            warn!("validateJenkinsfile - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let res: ConverterRes<ValidationData> = self
+            .json_body(res)
+            .await
+            .context("parse validateJenkinsfile payload as json")?;
+        Ok(JenkinsfileValidation {
+            success: res.data.result == "success",
+            errors: res
+                .data
+                .errors
+                .into_iter()
+                .flat_map(|e| match e.error {
+                    ValidationMessages::One(msg) => vec![msg],
+                    ValidationMessages::Many(msgs) => msgs,
+                })
+                .collect(),
+        })
+    }
+
+    /// Get `config.xml` of a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn get_job_config(&self, job: &str) -> Result<String> {
+        let url = format!("{}/job/{}/config.xml", self.url, job);
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("Get {}: res={:?}", url, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    /// Replace `config.xml` of a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `config` - full `config.xml` content
+    ///
+    pub async fn update_job_config(&self, job: &str, config: &str) -> Result<()> {
+        let url = format!("{}/job/{}/config.xml", self.url, job);
+        let res = self.send(self.post_xml(&url, config)).await?;
+        if !res.status().is_success() {
+            warn!("update config - job={}, res={:?}", job, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("update config - job={}", job);
+        Ok(())
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Create a job from a typed config
+    ///
+    /// ## Arguments
+    ///
+    /// * `name` - job name
+    /// * `config` - e.g. [`job_config::PipelineJob::new`]
+    ///
+    pub async fn create_job(&self, name: &str, config: &job_config::JobConfig) -> Result<()> {
+        self.create_item(&self.url, name, &config.to_xml()).await
+    }
+
+    pub(crate) async fn create_item(
+        &self,
+        parent_url: &str,
+        name: &str,
+        config: &str,
+    ) -> Result<()> {
+        let url = format!("{}/createItem", parent_url);
+        let req = self.post_xml(&url, config).query(&[("name", name)]);
+        let res = self.send(req).await?;
+        if !res.status().is_success() {
+            warn!("createItem - url={}, name={}, res={:?}", url, name, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("createItem - url={}, name={}", url, name);
+        Ok(())
+    }
+
+    /// Create a view from its `config.xml`, e.g. one exported from another controller
+    ///
+    /// ## Arguments
+    ///
+    /// * `name` - view name
+    /// * `config` - full `config.xml` content
+    ///
+    pub async fn create_view(&self, name: &str, config: &str) -> Result<()> {
+        let url = format!("{}/createView", self.url);
+        let req = self.post_xml(&url, config).query(&[("name", name)]);
+        let res = self.send(req).await?;
+        if !res.status().is_success() {
+            warn!("createView - name={}, res={:?}", name, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("createView - name={}", name);
+        Ok(())
+    }
+
+    /// Get a folder and the jobs in it
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `team/service`
+    ///
+    pub async fn get_folder(&self, path: &str) -> Result<FolderRes> {
+        let url = format!(
+            "{}/api/json?tree=name,url,description,jobs[name,url,color,_class]",
+            self.item_url(path)
+        );
+        self.get_json(&url).await
+    }
+
+    /// Create a folder, creating missing parent folders as well
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `team/service`
+    ///
+    pub async fn create_folder(&self, path: &str) -> Result<()> {
+        let mut parent = String::new();
+        for name in path.split('/').filter(|s| !s.is_empty()) {
+            let current = if parent.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            if !self.item_exists(&current).await? {
+                let parent_url = if parent.is_empty() {
+                    self.url.to_string()
+                } else {
+                    self.item_url(&parent)
+                };
+                self.create_item(&parent_url, name, FOLDER_CONFIG).await?;
+            }
+            parent = current;
+        }
+        Ok(())
+    }
+
+    /// Delete a folder with everything in it
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `team/service`
+    ///
+    pub async fn delete_folder(&self, path: &str) -> Result<()> {
+        let url = format!("{}/doDelete", self.item_url(path));
+        let res = self.send(self.post(&url)).await?;
+        // jenkins redirects to the parent after delete
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("doDelete - path={}, res={:?}", path, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("doDelete - path={}", path);
+        Ok(())
+    }
+
+    pub(crate) async fn item_exists(&self, path: &str) -> Result<bool> {
+        let url = format!("{}/api/json?tree=name", self.item_url(path));
+        let res = self.send(self.get(&url)).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => bail!(Error::APIError(format!("http status: {}", status))),
+        }
+    }
+
+    /// Url of an item by slash separated path, e.g. `a/b` to `{url}/job/a/job/b`,
+    /// see [`full_name`] for the accepted forms
+    pub(crate) fn item_url(&self, path: &str) -> String {
+        full_name(path)
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .fold(self.url.to_string(), |url, name| {
+                format!("{}/job/{}", url, name)
+            })
+    }
+
+    /// Whether `url`, e.g. the `task.url` of a queue item, points to the item
+    /// at `path`
+    ///
+    /// Only paths are compared, Jenkins may know itself under another host
+    /// than the client.
+    pub(crate) fn is_item_url(&self, url: &str, path: &str) -> bool {
+        let path_of = |url: &str| {
+            Url::parse(url)
+                .ok()
+                .map(|u| u.path().trim_end_matches('/').to_owned())
+        };
+        path_of(url).is_some_and(|p| Some(p) == path_of(&self.item_url(path)))
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Create a pipeline job running an inline Jenkinsfile
+    ///
+    /// Use [`Jenkins::create_job`] with [`job_config::PipelineDefinition::Scm`] to load
+    /// the Jenkinsfile from SCM instead.
+    ///
+    /// ## Arguments
+    ///
+    /// * `name` - job name
+    /// * `jenkinsfile` - pipeline script
+    ///
+    pub async fn create_pipeline_job(
+        &self,
+        name: &str,
+        jenkinsfile: &str,
+        opts: PipelineJobOptions,
+    ) -> Result<()> {
+        let mut job = job_config::PipelineJob::new(job_config::PipelineDefinition::Script {
+            script: jenkinsfile.to_owned(),
+            sandbox: opts.sandbox,
+        });
+        job.description = opts.description;
+        job.disabled = opts.disabled;
+        self.create_job(name, &job_config::JobConfig::Pipeline(job))
+            .await
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Get typed `config.xml` of a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn get_job_config_model(&self, job: &str) -> Result<job_config::JobConfig> {
+        job_config::JobConfig::parse(&self.get_job_config(job).await?)
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Replace `config.xml` of a job with a typed config
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `config` - usually from [`Jenkins::get_job_config_model`] then modified
+    ///
+    pub async fn update_job_config_model(
+        &self,
+        job: &str,
+        config: &job_config::JobConfig,
+    ) -> Result<()> {
+        self.update_job_config(job, &config.to_xml()).await
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Allow or disallow concurrent builds of a freestyle or pipeline job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `concurrent` - `false` to run one build at a time
+    ///
+    pub async fn set_concurrent_build(&self, job: &str, concurrent: bool) -> Result<()> {
+        let mut config = self.get_job_config_model(job).await?;
+        match &mut config {
+            job_config::JobConfig::Freestyle(c) => c.concurrent_build = concurrent,
+            job_config::JobConfig::Pipeline(c) => c.concurrent_build = concurrent,
+            _ => bail!(Error::APIError(format!(
+                "concurrent build not supported by job type: {}",
+                job
+            ))),
+        }
+        self.update_job_config_model(job, &config).await
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Get notification plugin endpoints of a freestyle or pipeline job
+    pub async fn get_notification_endpoints(
+        &self,
+        job: &str,
+    ) -> Result<Vec<job_config::NotificationEndpoint>> {
+        match self.get_job_config_model(job).await? {
+            job_config::JobConfig::Freestyle(c) => Ok(c.notifications),
+            job_config::JobConfig::Pipeline(c) => Ok(c.notifications),
+            _ => bail!(Error::APIError(format!(
+                "notifications not supported by job type: {}",
+                job
+            ))),
+        }
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Replace notification plugin endpoints of a freestyle or pipeline job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `endpoints` - new endpoints, empty to remove the property
+    ///
+    pub async fn set_notification_endpoints(
+        &self,
+        job: &str,
+        endpoints: Vec<job_config::NotificationEndpoint>,
+    ) -> Result<()> {
+        let mut config = self.get_job_config_model(job).await?;
+        match &mut config {
+            job_config::JobConfig::Freestyle(c) => c.notifications = endpoints,
+            job_config::JobConfig::Pipeline(c) => c.notifications = endpoints,
+            _ => bail!(Error::APIError(format!(
+                "notifications not supported by job type: {}",
+                job
+            ))),
+        }
+        self.update_job_config_model(job, &config).await
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Get project-based matrix authorization entries of a job
+    pub async fn get_job_permissions(&self, job: &str) -> Result<Vec<job_config::PermissionEntry>> {
+        match self.get_job_config_model(job).await? {
+            job_config::JobConfig::Freestyle(c) => Ok(c.permissions),
+            job_config::JobConfig::Pipeline(c) => Ok(c.permissions),
+            _ => bail!(Error::APIError(format!(
+                "matrix authorization not supported by job type: {}",
+                job
+            ))),
+        }
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Grant permissions on a job to a user, keeping existing entries
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `sid` - user id, use [`Jenkins::grant_entries`] for groups
+    /// * `permissions` - permission ids, e.g. `hudson.model.Item.Build`
+    ///
+    pub async fn grant(&self, job: &str, sid: &str, permissions: &[&str]) -> Result<()> {
+        let entries: Vec<_> = permissions
+            .iter()
+            .map(|p| job_config::PermissionEntry::user(p, sid))
+            .collect();
+        self.grant_entries(job, &entries).await
+    }
+
+    #[cfg(feature = "job-config")]
+    /// Add matrix authorization entries to a job, keeping existing entries
+    pub async fn grant_entries(
+        &self,
+        job: &str,
+        entries: &[job_config::PermissionEntry],
+    ) -> Result<()> {
+        let mut config = self.get_job_config_model(job).await?;
+        let permissions = match &mut config {
+            job_config::JobConfig::Freestyle(c) => &mut c.permissions,
+            job_config::JobConfig::Pipeline(c) => &mut c.permissions,
+            _ => bail!(Error::APIError(format!(
+                "matrix authorization not supported by job type: {}",
+                job
+            ))),
+        };
+        for entry in entries {
+            if !permissions.contains(entry) {
+                permissions.push(entry.clone());
+            }
+        }
+        self.update_job_config_model(job, &config).await
+    }
+
+    /// Get cron/SCM triggers configured on a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn get_triggers(&self, job: &str) -> Result<Vec<Trigger>> {
+        let doc = xml::Document::parse(&self.get_job_config(job).await?)?;
+        Ok(Trigger::parse_all(&doc.root))
+    }
+
+    /// Replace triggers of a job, keeping the rest of its configuration
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `triggers` - new triggers, [`Trigger::Other`] entries are written back as is
+    ///
+    pub async fn set_triggers(&self, job: &str, triggers: &[Trigger]) -> Result<()> {
+        let mut doc = xml::Document::parse(&self.get_job_config(job).await?)?;
+        Trigger::replace_all(&mut doc.root, triggers);
+        self.update_job_config(job, &doc.to_string()).await
+    }
+
+    /// List top level jobs whose name matches a glob pattern
+    ///
+    /// ## Arguments
+    ///
+    /// * `pattern` - glob pattern, e.g. `deploy-*`
+    ///
+    pub async fn list_jobs_matching(&self, pattern: &str) -> Result<Vec<JobRes>> {
+        let glob = glob::Glob::new(pattern);
+        let url = format!(
+            "{}/api/json?tree=jobs[name,url,color,_class,jobs[name]]",
+            self.url
+        );
+        let list: JobListRes = self.get_json(&url).await?;
+        Ok(list
+            .jobs
+            .into_iter()
+            .map(|entry| entry.job)
+            .filter(|job| glob.is_match(&job.name))
+            .collect())
+    }
+
+    /// Build the upstream/downstream graph of all jobs from `upstreamProjects` and
+    /// `downstreamProjects`, descending up to three folder levels
+    pub async fn get_dependency_graph(&self) -> Result<DependencyGraph> {
+        let fields = "_class,fullName,upstreamProjects[fullName],downstreamProjects[fullName]";
+        let tree = (0..DEPENDENCY_FOLDER_DEPTH).fold(format!("jobs[{}]", fields), |inner, _| {
+            format!("jobs[{},{}]", fields, inner)
+        });
+        let url = format!("{}/api/json?tree={}", self.url, tree);
+        let res: DependencyJobsRes = self.get_json(&url).await?;
+        let graph = DependencyGraph::new(res.jobs);
+        info!("dependency graph - jobs={}", graph.edges.len());
+        Ok(graph)
+    }
+
+    /// Walk the job tree concurrently, descending into folders
+    ///
+    /// ## Arguments
+    ///
+    /// * `depth` - how many folder levels to descend, `0` lists top level jobs only
+    /// * `concurrency` - max number of concurrent requests
+    ///
+    pub async fn crawl_jobs(&self, depth: usize, concurrency: usize) -> Result<Vec<JobNode>> {
+        let sem = Semaphore::new(concurrency.max(1));
+        self.crawl_folder(format!("{}/", self.url), depth, &sem)
+            .await
+    }
+
+    pub(crate) fn crawl_folder<'a>(
+        &'a self,
+        folder_url: String,
+        depth: usize,
+        sem: &'a Semaphore,
+    ) -> BoxFuture<'a, Result<Vec<JobNode>>> {
+        Box::pin(async move {
+            let list: JobListRes = {
+                let _permit = sem.acquire().await?;
+                let url = format!(
+                    "{}api/json?tree=jobs[name,url,color,_class,jobs[name]]",
+                    folder_url
+                );
+                self.get_json(&url).await?
+            };
+            try_join_all(list.jobs.into_iter().map(|entry| async move {
+                let children = match entry.jobs {
+                    Some(_) if depth > 0 => {
+                        self.crawl_folder(entry.job.url.clone(), depth - 1, sem)
+                            .await?
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(JobNode {
+                    job: entry.job,
+                    children,
+                })
+            }))
+            .await
+        })
+    }
+
+    /// Compute success rate, durations and failure streak over recent builds
+    ///
+    /// Running builds are ignored.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `last_n` - number of recent builds to look at
+    ///
+    pub async fn job_stats(&self, job: &str, last_n: usize) -> Result<JobStats> {
+        let url = format!(
+            "{}/job/{}/api/json?tree=builds[number,building,result,duration]{{0,{}}}",
+            self.url, job, last_n
+        );
+        let res: JobStatsRes = self.get_json(&url).await?;
+        Ok(JobStats::new(&res.builds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_list_detects_folders() {
+        let list: JobListRes = serde_json::from_str(
+            r#"{"jobs":[
+                {"_class":"hudson.model.FreeStyleProject","name":"a","url":"u/a/","color":"blue"},
+                {"_class":"com.cloudbees.hudson.plugins.folder.Folder","name":"f","url":"u/f/","jobs":[]}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(list.jobs[0].jobs.is_none());
+        assert!(list.jobs[1].jobs.is_some());
+        assert_eq!(list.jobs[1].job.name, "f");
+    }
+
+    #[test]
+    fn combination_roundtrip() {
+        let c: Combination = "OS=linux,JDK=17".parse().unwrap();
+        assert_eq!(c.0[1], ("JDK".to_owned(), "17".to_owned()));
+        assert_eq!(c.to_string(), "OS=linux,JDK=17");
+        assert!("OS".parse::<Combination>().is_err());
+    }
+
+    #[test]
+    fn pipeline_triggers() {
+        let mut doc = xml::Document::parse(
+            "<flow-definition><properties>\
+            <org.jenkinsci.plugins.workflow.job.properties.PipelineTriggersJobProperty><triggers>\
+            <hudson.triggers.TimerTrigger><spec>H 2 * * *</spec></hudson.triggers.TimerTrigger>\
+            </triggers></org.jenkinsci.plugins.workflow.job.properties.PipelineTriggersJobProperty>\
+            </properties></flow-definition>",
+        )
+        .unwrap();
+        let triggers = Trigger::parse_all(&doc.root);
+        assert_eq!(
+            triggers,
+            vec![Trigger::Timer {
+                spec: "H 2 * * *".to_owned()
+            }]
+        );
+        Trigger::replace_all(
+            &mut doc.root,
+            &[Trigger::Scm {
+                spec: "H/5 * * * *".to_owned(),
+                ignore_post_commit_hooks: false,
+            }],
+        );
+        assert!(matches!(
+            Trigger::parse_all(&doc.root)[..],
+            [Trigger::Scm { .. }]
+        ));
+    }
+
+    #[test]
+    fn compute_job_stats() {
+        let res: JobStatsRes = serde_json::from_str(
+            r#"{"builds":[
+                {"number":6,"building":true,"result":null,"duration":0},
+                {"number":5,"result":"FAILURE","duration":400},
+                {"number":4,"result":"UNSTABLE","duration":300},
+                {"number":3,"result":"SUCCESS","duration":200},
+                {"number":2,"result":"SUCCESS","duration":100}]}"#,
+        )
+        .unwrap();
+        let stats = JobStats::new(&res.builds);
+        assert_eq!(stats.builds, 4);
+        assert_eq!(stats.success_rate, 0.5);
+        assert_eq!(stats.mean_duration, Duration::from_millis(250));
+        assert_eq!(stats.p50_duration, Duration::from_millis(200));
+        assert_eq!(stats.p95_duration, Duration::from_millis(400));
+        assert_eq!(stats.failure_streak, 2);
+        assert_eq!(stats.last_success, Some(3));
+    }
+
+    #[test]
+    fn dependency_graph_from_jobs() {
+        let res: DependencyJobsRes = serde_json::from_str(
+            r#"{"jobs":[
+                {"fullName":"build","downstreamProjects":[{"fullName":"test"}],"upstreamProjects":[]},
+                {"_class":"com.cloudbees.hudson.plugins.folder.Folder","fullName":"apps","jobs":[
+                    {"fullName":"apps/deploy","upstreamProjects":[{"fullName":"test"}]}]},
+                {"fullName":"test","downstreamProjects":[{"fullName":"apps/deploy"}],
+                 "upstreamProjects":[{"fullName":"build"}]}]}"#,
+        )
+        .unwrap();
+        let graph = DependencyGraph::new(res.jobs);
+        assert_eq!(
+            graph.downstream("test").collect::<Vec<_>>(),
+            ["apps/deploy"]
+        );
+        assert_eq!(graph.upstream("test").collect::<Vec<_>>(), ["build"]);
+        assert_eq!(graph.roots().collect::<Vec<_>>(), ["build"]);
+        assert!(graph.downstream("apps").next().is_none());
+    }
+
+    #[test]
+    fn full_name_of_either_form() {
+        assert_eq!(full_name("deploy"), "deploy");
+        assert_eq!(full_name("team/deploy"), "team/deploy");
+        assert_eq!(full_name("team/job/deploy"), "team/deploy");
+        assert_eq!(full_name("/a/job/b/job/c/"), "a/b/c");
+        let cli = Jenkins::new("http://ci", "bot", "token");
+        assert_eq!(cli.item_url("a/job/b"), cli.item_url("a/b"));
+    }
+
+    #[test]
+    fn item_url_matches_folder_path() {
+        let cli = Jenkins::new("http://ci.example.com/jenkins", "bot", "token");
+        let task = "https://jenkins.internal/jenkins/job/apps/job/my%20api/";
+        assert!(cli.is_item_url(task, "apps/my api"));
+        assert!(!cli.is_item_url(task, "apps/my api-2"));
+        assert!(!cli.is_item_url(task, "my api"));
+    }
+}
@@ -0,0 +1,959 @@
+//! Typed models of common job `config.xml` formats
+//!
+//! Each model keeps the parsed document it was read from, so elements that are
+//! not modeled here (plugin properties, publishers, ...) are written back unchanged.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    xml::{Document, Element, Node},
+    Error, Trigger,
+};
+
+const FREESTYLE: &str = "project";
+const PIPELINE: &str = "flow-definition";
+const MULTIBRANCH: &str = "org.jenkinsci.plugins.workflow.multibranch.WorkflowMultiBranchProject";
+const BRANCH_PROJECT_FACTORY: &str =
+    "org.jenkinsci.plugins.workflow.multibranch.WorkflowBranchProjectFactory";
+const CPS_FLOW_DEFINITION: &str = "org.jenkinsci.plugins.workflow.cps.CpsFlowDefinition";
+const CPS_SCM_FLOW_DEFINITION: &str = "org.jenkinsci.plugins.workflow.cps.CpsScmFlowDefinition";
+const SHELL: &str = "hudson.tasks.Shell";
+const THROTTLE_PROPERTY: &str = "hudson.plugins.throttleconcurrents.ThrottleJobProperty";
+const NOTIFICATION_PROPERTY: &str =
+    "com.tikal.hudson.plugins.notification.HudsonNotificationProperty";
+const NOTIFICATION_ENDPOINT: &str = "com.tikal.hudson.plugins.notification.Endpoint";
+const MATRIX_AUTH_PROPERTY: &str = "hudson.security.AuthorizationMatrixProperty";
+const DISABLE_CONCURRENT_PROPERTY: &str =
+    "org.jenkinsci.plugins.workflow.job.properties.DisableConcurrentBuildsJobProperty";
+
+/// Job configuration, see [`crate::Jenkins::get_job_config_model`]
+#[derive(Debug, Clone)]
+pub enum JobConfig {
+    Freestyle(FreestyleProject),
+    Pipeline(PipelineJob),
+    Multibranch(MultibranchProject),
+    /// other job types, kept as raw xml
+    Other(Document),
+}
+
+impl JobConfig {
+    pub fn parse(config: &str) -> Result<JobConfig> {
+        let doc = Document::parse(config)?;
+        Ok(match doc.root.name.as_str() {
+            FREESTYLE => JobConfig::Freestyle(FreestyleProject::from_doc(doc)),
+            PIPELINE => JobConfig::Pipeline(PipelineJob::from_doc(doc)?),
+            MULTIBRANCH => JobConfig::Multibranch(MultibranchProject::from_doc(doc)),
+            _ => JobConfig::Other(doc),
+        })
+    }
+
+    pub fn to_xml(&self) -> String {
+        match self {
+            JobConfig::Freestyle(c) => c.to_doc().to_string(),
+            JobConfig::Pipeline(c) => c.to_doc().to_string(),
+            JobConfig::Multibranch(c) => c.to_doc().to_string(),
+            JobConfig::Other(doc) => doc.to_string(),
+        }
+    }
+}
+
+fn empty_doc(root: &str) -> Document {
+    Document {
+        declaration: Some("xml version='1.1' encoding='UTF-8'".to_owned()),
+        root: Element::new(root),
+    }
+}
+
+fn bool_text(e: &Element, name: &str) -> bool {
+    e.child_text(name).as_deref() == Some("true")
+}
+
+/// `<project>` config of freestyle jobs
+#[derive(Debug, Clone)]
+pub struct FreestyleProject {
+    pub description: String,
+    pub disabled: bool,
+    pub concurrent_build: bool,
+    /// label expression restricting where the job can run
+    pub assigned_node: Option<String>,
+    pub triggers: Vec<Trigger>,
+    /// commands of "Execute shell" build steps, other steps are kept as is
+    pub shell_steps: Vec<String>,
+    pub throttle: Option<ThrottleProperty>,
+    /// project-based matrix authorization entries
+    pub permissions: Vec<PermissionEntry>,
+    /// endpoints of the notification plugin
+    pub notifications: Vec<NotificationEndpoint>,
+    doc: Document,
+}
+
+impl Default for FreestyleProject {
+    fn default() -> Self {
+        FreestyleProject::from_doc(empty_doc(FREESTYLE))
+    }
+}
+
+impl FreestyleProject {
+    fn from_doc(doc: Document) -> FreestyleProject {
+        let root = &doc.root;
+        FreestyleProject {
+            description: root.child_text("description").unwrap_or_default(),
+            disabled: bool_text(root, "disabled"),
+            concurrent_build: bool_text(root, "concurrentBuild"),
+            assigned_node: root.child_text("assignedNode"),
+            triggers: Trigger::parse_all(root),
+            shell_steps: root
+                .child("builders")
+                .map(|b| {
+                    b.elements()
+                        .filter(|e| e.name == SHELL)
+                        .map(|e| e.child_text("command").unwrap_or_default())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            throttle: ThrottleProperty::read(root),
+            permissions: PermissionEntry::read_all(root),
+            notifications: NotificationEndpoint::read_all(root),
+            doc,
+        }
+    }
+
+    /// Write back the fields that differ from the parsed document, in place
+    fn to_doc(&self) -> Document {
+        let mut doc = self.doc.clone();
+        // a new job gets every field
+        let fresh = doc.root.children.is_empty();
+        let original = FreestyleProject::from_doc(self.doc.clone());
+        let root = &mut doc.root;
+        if fresh || self.description != original.description {
+            root.set_child_text("description", &self.description);
+        }
+        if fresh || self.disabled != original.disabled {
+            root.set_child_text("disabled", &self.disabled.to_string());
+        }
+        if fresh || self.concurrent_build != original.concurrent_build {
+            root.set_child_text("concurrentBuild", &self.concurrent_build.to_string());
+        }
+        if fresh || self.assigned_node != original.assigned_node {
+            match &self.assigned_node {
+                Some(label) => {
+                    root.set_child_text("assignedNode", label);
+                    root.set_child_text("canRoam", "false");
+                }
+                None => {
+                    root.remove_children("assignedNode");
+                    root.set_child_text("canRoam", "true");
+                }
+            }
+        }
+        if self.triggers != original.triggers {
+            Trigger::replace_all(root, &self.triggers);
+        }
+        if self.shell_steps != original.shell_steps {
+            write_shell_steps(root.child_or_insert("builders"), &self.shell_steps);
+        }
+        if self.throttle != original.throttle {
+            ThrottleProperty::write(root, self.throttle.as_ref());
+        }
+        if self.permissions != original.permissions {
+            PermissionEntry::write_all(root, &self.permissions);
+        }
+        if self.notifications != original.notifications {
+            NotificationEndpoint::write_all(root, &self.notifications);
+        }
+        doc
+    }
+}
+
+/// Replace the commands of "Execute shell" steps by position, keeping the
+/// order of the other builders
+fn write_shell_steps(builders: &mut Element, commands: &[String]) {
+    let positions: Vec<usize> = builders
+        .children
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| matches!(n, Node::Element(e) if e.name == SHELL))
+        .map(|(i, _)| i)
+        .collect();
+    for (position, command) in positions.iter().zip(commands) {
+        if let Node::Element(shell) = &mut builders.children[*position] {
+            if shell.child_text("command").as_deref() != Some(command) {
+                shell.set_child_text("command", command);
+            }
+        }
+    }
+    // back to front, keeping the earlier positions valid
+    for position in positions.iter().skip(commands.len()).rev() {
+        builders.children.remove(*position);
+    }
+    // new steps go after the last shell step
+    let at = positions.last().map_or(builders.children.len(), |p| p + 1);
+    let added = commands.iter().skip(positions.len()).map(|command| {
+        let mut shell = Element::new(SHELL);
+        shell.push(Element::with_text("command", command));
+        Node::Element(shell)
+    });
+    builders.children.splice(at..at, added);
+}
+
+/// Where a pipeline job gets its script from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineDefinition {
+    /// inline script
+    Script { script: String, sandbox: bool },
+    /// Jenkinsfile from SCM, `scm` is kept as raw xml
+    Scm { script_path: String, scm: Element },
+}
+
+/// `scm` element of the git plugin for [`PipelineDefinition::Scm`]
+///
+/// ## Arguments
+///
+/// * `url` - repository url
+/// * `branch` - branch specifier, e.g. `*/main`
+/// * `credentials_id` - id of credentials to clone with
+///
+pub fn git_scm(url: &str, branch: &str, credentials_id: Option<&str>) -> Element {
+    let mut remote = Element::new("hudson.plugins.git.UserRemoteConfig");
+    remote.push(Element::with_text("url", url));
+    if let Some(id) = credentials_id {
+        remote.push(Element::with_text("credentialsId", id));
+    }
+    let mut remotes = Element::new("userRemoteConfigs");
+    remotes.push(remote);
+    let mut spec = Element::new("hudson.plugins.git.BranchSpec");
+    spec.push(Element::with_text("name", branch));
+    let mut branches = Element::new("branches");
+    branches.push(spec);
+
+    let mut scm = Element::new("scm");
+    scm.set_attr("class", "hudson.plugins.git.GitSCM");
+    scm.push(Element::with_text("configVersion", "2"));
+    scm.push(remotes);
+    scm.push(branches);
+    scm
+}
+
+/// `<flow-definition>` config of pipeline jobs
+#[derive(Debug, Clone)]
+pub struct PipelineJob {
+    pub description: String,
+    pub disabled: bool,
+    /// `false` when `disableConcurrentBuilds()` is set
+    pub concurrent_build: bool,
+    pub definition: PipelineDefinition,
+    pub triggers: Vec<Trigger>,
+    pub throttle: Option<ThrottleProperty>,
+    /// project-based matrix authorization entries
+    pub permissions: Vec<PermissionEntry>,
+    /// endpoints of the notification plugin
+    pub notifications: Vec<NotificationEndpoint>,
+    doc: Document,
+}
+
+impl PipelineJob {
+    /// New pipeline job config
+    pub fn new(definition: PipelineDefinition) -> PipelineJob {
+        PipelineJob {
+            description: String::new(),
+            disabled: false,
+            concurrent_build: true,
+            definition,
+            triggers: Vec::new(),
+            throttle: None,
+            permissions: Vec::new(),
+            notifications: Vec::new(),
+            doc: empty_doc(PIPELINE),
+        }
+    }
+
+    fn from_doc(doc: Document) -> Result<PipelineJob> {
+        let root = &doc.root;
+        let definition = match root.child("definition") {
+            Some(d) if d.attr("class") == Some(CPS_FLOW_DEFINITION) => PipelineDefinition::Script {
+                script: d.child_text("script").unwrap_or_default(),
+                sandbox: bool_text(d, "sandbox"),
+            },
+            Some(d) if d.attr("class") == Some(CPS_SCM_FLOW_DEFINITION) => {
+                PipelineDefinition::Scm {
+                    script_path: d
+                        .child_text("scriptPath")
+                        .unwrap_or_else(|| "Jenkinsfile".to_owned()),
+                    scm: d
+                        .child("scm")
+                        .cloned()
+                        .unwrap_or_else(|| Element::new("scm")),
+                }
+            }
+            Some(d) => bail!(Error::APIError(format!(
+                "unsupported pipeline definition: {:?}",
+                d.attr("class")
+            ))),
+            None => bail!(Error::APIError("pipeline definition missing".to_owned())),
+        };
+        Ok(PipelineJob {
+            description: root.child_text("description").unwrap_or_default(),
+            disabled: bool_text(root, "disabled"),
+            concurrent_build: root
+                .path(&["properties", DISABLE_CONCURRENT_PROPERTY])
+                .is_none(),
+            definition,
+            triggers: Trigger::parse_all(root),
+            throttle: ThrottleProperty::read(root),
+            permissions: PermissionEntry::read_all(root),
+            notifications: NotificationEndpoint::read_all(root),
+            doc,
+        })
+    }
+
+    /// Write back the fields that differ from the parsed document, in place
+    fn to_doc(&self) -> Document {
+        let mut doc = self.doc.clone();
+        // `None` for a new job, which gets every field
+        let original = PipelineJob::from_doc(self.doc.clone()).ok();
+        let changed =
+            |differs: &dyn Fn(&PipelineJob) -> bool| original.as_ref().is_none_or(differs);
+        let root = &mut doc.root;
+        if changed(&|o| o.description != self.description) {
+            root.set_child_text("description", &self.description);
+        }
+        if changed(&|o| o.disabled != self.disabled) {
+            root.set_child_text("disabled", &self.disabled.to_string());
+        }
+        if changed(&|o| o.triggers != self.triggers) {
+            Trigger::replace_all(root, &self.triggers);
+        }
+        if changed(&|o| o.throttle != self.throttle) {
+            ThrottleProperty::write(root, self.throttle.as_ref());
+        }
+        if changed(&|o| o.permissions != self.permissions) {
+            PermissionEntry::write_all(root, &self.permissions);
+        }
+        if changed(&|o| o.notifications != self.notifications) {
+            NotificationEndpoint::write_all(root, &self.notifications);
+        }
+        let has_disable_concurrent = root
+            .path(&["properties", DISABLE_CONCURRENT_PROPERTY])
+            .is_some();
+        if self.concurrent_build && has_disable_concurrent {
+            root.child_or_insert("properties")
+                .remove_children(DISABLE_CONCURRENT_PROPERTY);
+        } else if !self.concurrent_build && !has_disable_concurrent {
+            root.child_or_insert("properties")
+                .push(Element::new(DISABLE_CONCURRENT_PROPERTY));
+        }
+        if changed(&|o| o.definition != self.definition) {
+            self.write_definition(root);
+        }
+        doc
+    }
+
+    /// Update `<definition>` in place when its kind is unchanged, keeping
+    /// children not modeled here such as `lightweight`
+    fn write_definition(&self, root: &mut Element) {
+        let class = match &self.definition {
+            PipelineDefinition::Script { .. } => CPS_FLOW_DEFINITION,
+            PipelineDefinition::Scm { .. } => CPS_SCM_FLOW_DEFINITION,
+        };
+        let same_kind = root
+            .child("definition")
+            .is_some_and(|d| d.attr("class") == Some(class));
+        if !same_kind {
+            let mut definition = Element::new("definition");
+            definition.set_attr("class", class);
+            match &self.definition {
+                PipelineDefinition::Script { script, sandbox } => {
+                    definition.push(Element::with_text("script", script));
+                    definition.push(Element::with_text("sandbox", &sandbox.to_string()));
+                }
+                PipelineDefinition::Scm { script_path, scm } => {
+                    definition.push(scm.clone());
+                    definition.push(Element::with_text("scriptPath", script_path));
+                    definition.push(Element::with_text("lightweight", "true"));
+                }
+            }
+            match root.child_mut("definition") {
+                Some(d) => *d = definition,
+                None => root.push(definition),
+            }
+            return;
+        }
+        let definition = root.child_or_insert("definition");
+        match &self.definition {
+            PipelineDefinition::Script { script, sandbox } => {
+                definition.set_child_text("script", script);
+                definition.set_child_text("sandbox", &sandbox.to_string());
+            }
+            PipelineDefinition::Scm { script_path, scm } => {
+                match definition.child_mut("scm") {
+                    Some(e) if e != scm => *e = scm.clone(),
+                    Some(_) => {}
+                    None => definition.push(scm.clone()),
+                }
+                definition.set_child_text("scriptPath", script_path);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleOption {
+    /// throttle this project alone
+    Project,
+    /// throttle as part of one or more categories
+    Category,
+}
+
+/// Job property of the throttle-concurrent-builds plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThrottleProperty {
+    pub enabled: bool,
+    pub option: ThrottleOption,
+    /// `0` means unlimited
+    pub max_concurrent_per_node: u32,
+    /// `0` means unlimited
+    pub max_concurrent_total: u32,
+    pub categories: Vec<String>,
+}
+
+impl ThrottleProperty {
+    fn read(root: &Element) -> Option<ThrottleProperty> {
+        let e = root.path(&["properties", THROTTLE_PROPERTY])?;
+        let number = |name| {
+            e.child_text(name)
+                .and_then(|t| t.trim().parse().ok())
+                .unwrap_or(0)
+        };
+        Some(ThrottleProperty {
+            enabled: bool_text(e, "throttleEnabled"),
+            option: match e.child_text("throttleOption").as_deref() {
+                Some("category") => ThrottleOption::Category,
+                _ => ThrottleOption::Project,
+            },
+            max_concurrent_per_node: number("maxConcurrentPerNode"),
+            max_concurrent_total: number("maxConcurrentTotal"),
+            categories: e
+                .child("categories")
+                .map(|c| c.elements().map(|s| s.text()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Update the property in place, keeping fields not modeled here
+    fn write(root: &mut Element, throttle: Option<&ThrottleProperty>) {
+        let Some(throttle) = throttle else {
+            if let Some(properties) = root.child_mut("properties") {
+                properties.remove_children(THROTTLE_PROPERTY);
+            }
+            return;
+        };
+        let e = root
+            .child_or_insert("properties")
+            .child_or_insert(THROTTLE_PROPERTY);
+        e.set_child_text(
+            "maxConcurrentPerNode",
+            &throttle.max_concurrent_per_node.to_string(),
+        );
+        e.set_child_text(
+            "maxConcurrentTotal",
+            &throttle.max_concurrent_total.to_string(),
+        );
+        let categories = e.child_or_insert("categories");
+        categories.set_attr("class", "java.util.concurrent.CopyOnWriteArrayList");
+        categories.children.clear();
+        for category in &throttle.categories {
+            categories.push(Element::with_text("string", category));
+        }
+        e.set_child_text("throttleEnabled", &throttle.enabled.to_string());
+        e.set_child_text(
+            "throttleOption",
+            match throttle.option {
+                ThrottleOption::Project => "project",
+                ThrottleOption::Category => "category",
+            },
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationProtocol {
+    Http,
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationFormat {
+    Json,
+    Xml,
+}
+
+/// Build phases an endpoint is notified about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationEvent {
+    All,
+    Started,
+    Completed,
+    Finalized,
+}
+
+/// Endpoint of the notification plugin job property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationEndpoint {
+    pub protocol: NotificationProtocol,
+    pub format: NotificationFormat,
+    pub url: String,
+    pub event: NotificationEvent,
+    pub timeout_millis: u32,
+    /// lines of build log sent with the payload, `-1` for all
+    pub log_lines: i32,
+    pub retries: u32,
+}
+
+impl NotificationEndpoint {
+    /// JSON over HTTP endpoint notified on all events
+    pub fn http(url: &str) -> NotificationEndpoint {
+        NotificationEndpoint {
+            protocol: NotificationProtocol::Http,
+            format: NotificationFormat::Json,
+            url: url.to_owned(),
+            event: NotificationEvent::All,
+            timeout_millis: 30000,
+            log_lines: 0,
+            retries: 0,
+        }
+    }
+
+    fn read_all(root: &Element) -> Vec<NotificationEndpoint> {
+        let Some(endpoints) = root.path(&["properties", NOTIFICATION_PROPERTY, "endpoints"]) else {
+            return Vec::new();
+        };
+        endpoints
+            .elements()
+            .filter(|e| e.name == NOTIFICATION_ENDPOINT)
+            .map(|e| {
+                let number = |name| {
+                    e.child_text(name)
+                        .and_then(|t| t.trim().parse().ok())
+                        .unwrap_or(0)
+                };
+                NotificationEndpoint {
+                    protocol: match e.child_text("protocol").as_deref() {
+                        Some("TCP") => NotificationProtocol::Tcp,
+                        Some("UDP") => NotificationProtocol::Udp,
+                        _ => NotificationProtocol::Http,
+                    },
+                    format: match e.child_text("format").as_deref() {
+                        Some("XML") => NotificationFormat::Xml,
+                        _ => NotificationFormat::Json,
+                    },
+                    // older plugin versions store the url directly
+                    url: e
+                        .path(&["urlInfo", "urlOrId"])
+                        .map(|u| u.text())
+                        .or_else(|| e.child_text("url"))
+                        .unwrap_or_default(),
+                    event: match e.child_text("event").as_deref() {
+                        Some("started") => NotificationEvent::Started,
+                        Some("completed") => NotificationEvent::Completed,
+                        Some("finalized") => NotificationEvent::Finalized,
+                        _ => NotificationEvent::All,
+                    },
+                    timeout_millis: number("timeout") as u32,
+                    log_lines: number("loglines"),
+                    retries: number("retries") as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the endpoints, removing the property when there are none
+    fn write_all(root: &mut Element, endpoints: &[NotificationEndpoint]) {
+        if endpoints.is_empty() {
+            if let Some(properties) = root.child_mut("properties") {
+                properties.remove_children(NOTIFICATION_PROPERTY);
+            }
+            return;
+        }
+        let e = root
+            .child_or_insert("properties")
+            .child_or_insert(NOTIFICATION_PROPERTY)
+            .child_or_insert("endpoints");
+        e.children.clear();
+        for endpoint in endpoints {
+            let mut element = Element::new(NOTIFICATION_ENDPOINT);
+            let protocol = match endpoint.protocol {
+                NotificationProtocol::Http => "HTTP",
+                NotificationProtocol::Tcp => "TCP",
+                NotificationProtocol::Udp => "UDP",
+            };
+            element.push(Element::with_text("protocol", protocol));
+            let format = match endpoint.format {
+                NotificationFormat::Json => "JSON",
+                NotificationFormat::Xml => "XML",
+            };
+            element.push(Element::with_text("format", format));
+            let mut url_info = Element::new("urlInfo");
+            url_info.push(Element::with_text("urlOrId", &endpoint.url));
+            url_info.push(Element::with_text("urlType", "PUBLIC"));
+            element.push(url_info);
+            let event = match endpoint.event {
+                NotificationEvent::All => "all",
+                NotificationEvent::Started => "started",
+                NotificationEvent::Completed => "completed",
+                NotificationEvent::Finalized => "finalized",
+            };
+            element.push(Element::with_text("event", event));
+            element.push(Element::with_text(
+                "timeout",
+                &endpoint.timeout_millis.to_string(),
+            ));
+            element.push(Element::with_text(
+                "loglines",
+                &endpoint.log_lines.to_string(),
+            ));
+            element.push(Element::with_text("retries", &endpoint.retries.to_string()));
+            e.push(element);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidKind {
+    User,
+    Group,
+}
+
+/// Matrix authorization entry, `USER:hudson.model.Item.Build:alice` or
+/// `hudson.model.Item.Build:alice` before matrix-auth 3.0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionEntry {
+    /// `None` for the ambiguous format of older matrix-auth versions
+    pub kind: Option<SidKind>,
+    /// permission id, e.g. `hudson.model.Item.Build`
+    pub permission: String,
+    pub sid: String,
+}
+
+impl PermissionEntry {
+    pub fn user(permission: &str, sid: &str) -> PermissionEntry {
+        PermissionEntry {
+            kind: Some(SidKind::User),
+            permission: permission.to_owned(),
+            sid: sid.to_owned(),
+        }
+    }
+
+    pub fn group(permission: &str, sid: &str) -> PermissionEntry {
+        PermissionEntry {
+            kind: Some(SidKind::Group),
+            permission: permission.to_owned(),
+            sid: sid.to_owned(),
+        }
+    }
+
+    fn read_all(root: &Element) -> Vec<PermissionEntry> {
+        root.path(&["properties", MATRIX_AUTH_PROPERTY])
+            .map(|p| {
+                p.elements()
+                    .filter(|e| e.name == "permission")
+                    .filter_map(|e| e.text().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Replace entries in place, keeping the inheritance strategy
+    fn write_all(root: &mut Element, entries: &[PermissionEntry]) {
+        if entries.is_empty() && root.path(&["properties", MATRIX_AUTH_PROPERTY]).is_none() {
+            return;
+        }
+        let property = root
+            .child_or_insert("properties")
+            .child_or_insert(MATRIX_AUTH_PROPERTY);
+        property.remove_children("permission");
+        for entry in entries {
+            property.push(Element::with_text("permission", &entry.to_string()));
+        }
+    }
+}
+
+impl fmt::Display for PermissionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(SidKind::User) => f.write_str("USER:")?,
+            Some(SidKind::Group) => f.write_str("GROUP:")?,
+            None => {}
+        }
+        write!(f, "{}:{}", self.permission, self.sid)
+    }
+}
+
+impl FromStr for PermissionEntry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, rest) = match s.split_once(':') {
+            Some(("USER", rest)) => (Some(SidKind::User), rest),
+            Some(("GROUP", rest)) => (Some(SidKind::Group), rest),
+            _ => (None, s),
+        };
+        match rest.split_once(':') {
+            Some((permission, sid)) => Ok(PermissionEntry {
+                kind,
+                permission: permission.to_owned(),
+                sid: sid.to_owned(),
+            }),
+            None => bail!(Error::APIError(format!("invalid permission entry: {}", s))),
+        }
+    }
+}
+
+/// Multibranch pipeline config
+#[derive(Debug, Clone)]
+pub struct MultibranchProject {
+    pub description: String,
+    pub display_name: Option<String>,
+    /// Jenkinsfile path in each branch
+    pub script_path: String,
+    doc: Document,
+}
+
+impl MultibranchProject {
+    fn from_doc(doc: Document) -> MultibranchProject {
+        let root = &doc.root;
+        MultibranchProject {
+            description: root.child_text("description").unwrap_or_default(),
+            display_name: root.child_text("displayName"),
+            script_path: root
+                .path(&["factory", "scriptPath"])
+                .map(|e| e.text())
+                .unwrap_or_else(|| "Jenkinsfile".to_owned()),
+            doc,
+        }
+    }
+
+    /// Write back the fields that differ from the parsed document, in place
+    fn to_doc(&self) -> Document {
+        let mut doc = self.doc.clone();
+        let original = MultibranchProject::from_doc(self.doc.clone());
+        let root = &mut doc.root;
+        if self.description != original.description {
+            root.set_child_text("description", &self.description);
+        }
+        if self.display_name != original.display_name {
+            match &self.display_name {
+                Some(name) => root.set_child_text("displayName", name),
+                None => root.remove_children("displayName"),
+            }
+        }
+        if self.script_path != original.script_path {
+            if root.child("factory").is_none() {
+                let mut factory = Element::new("factory");
+                factory.set_attr("class", BRANCH_PROJECT_FACTORY);
+                root.push(factory);
+            }
+            root.child_or_insert("factory")
+                .set_child_text("scriptPath", &self.script_path);
+        }
+        doc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freestyle_roundtrip_keeps_unknown_elements() {
+        let src = "<?xml version='1.1' encoding='UTF-8'?>\n<project>\
+            <description>old</description><disabled>false</disabled>\
+            <builders><hudson.tasks.Shell><command>make</command></hudson.tasks.Shell>\
+            <hudson.tasks.Maven><targets>install</targets></hudson.tasks.Maven></builders>\
+            <publishers><hudson.tasks.Mailer plugin=\"mailer@1\"/></publishers></project>";
+        let JobConfig::Freestyle(mut job) = JobConfig::parse(src).unwrap() else {
+            panic!("expected freestyle project")
+        };
+        assert_eq!(job.shell_steps, vec!["make"]);
+        job.description = "new".to_owned();
+        job.shell_steps.push("make test".to_owned());
+        let xml = JobConfig::Freestyle(job).to_xml();
+        assert!(xml.contains("<description>new</description>"));
+        assert!(xml.contains("<hudson.tasks.Maven><targets>install</targets></hudson.tasks.Maven>"));
+        assert!(xml.contains("<hudson.tasks.Mailer plugin=\"mailer@1\"/>"));
+        assert!(xml.contains("<command>make test</command>"));
+    }
+
+    #[test]
+    fn pipeline_script_definition() {
+        let job = PipelineJob::new(PipelineDefinition::Script {
+            script: "pipeline { agent any }".to_owned(),
+            sandbox: true,
+        });
+        let JobConfig::Pipeline(parsed) =
+            JobConfig::parse(&JobConfig::Pipeline(job).to_xml()).unwrap()
+        else {
+            panic!("expected pipeline job")
+        };
+        assert!(parsed.concurrent_build);
+        assert_eq!(
+            parsed.definition,
+            PipelineDefinition::Script {
+                script: "pipeline { agent any }".to_owned(),
+                sandbox: true
+            }
+        );
+    }
+
+    #[test]
+    fn pipeline_concurrency_and_throttle() {
+        let mut job = PipelineJob::new(PipelineDefinition::Script {
+            script: String::new(),
+            sandbox: true,
+        });
+        job.concurrent_build = false;
+        job.throttle = Some(ThrottleProperty {
+            enabled: true,
+            option: ThrottleOption::Category,
+            max_concurrent_per_node: 1,
+            max_concurrent_total: 2,
+            categories: vec!["deploy".to_owned()],
+        });
+        let JobConfig::Pipeline(parsed) =
+            JobConfig::parse(&JobConfig::Pipeline(job.clone()).to_xml()).unwrap()
+        else {
+            panic!("expected pipeline job")
+        };
+        assert!(!parsed.concurrent_build);
+        assert_eq!(parsed.throttle, job.throttle);
+    }
+
+    #[test]
+    fn permission_entry_formats() {
+        let typed: PermissionEntry = "GROUP:hudson.model.Item.Build:devs".parse().unwrap();
+        assert_eq!(
+            typed,
+            PermissionEntry::group("hudson.model.Item.Build", "devs")
+        );
+        let legacy: PermissionEntry = "hudson.model.Item.Read:alice".parse().unwrap();
+        assert_eq!(legacy.kind, None);
+        assert_eq!(legacy.to_string(), "hudson.model.Item.Read:alice");
+    }
+
+    #[test]
+    fn notification_endpoints_roundtrip() {
+        let mut job = FreestyleProject {
+            notifications: vec![
+                NotificationEndpoint::http("https://hooks.example.com/jenkins"),
+                NotificationEndpoint {
+                    event: NotificationEvent::Completed,
+                    log_lines: 50,
+                    ..NotificationEndpoint::http("https://ci-bot.example.com")
+                },
+            ],
+            ..Default::default()
+        };
+        let JobConfig::Freestyle(parsed) =
+            JobConfig::parse(&JobConfig::Freestyle(job.clone()).to_xml()).unwrap()
+        else {
+            panic!("expected freestyle job")
+        };
+        assert_eq!(parsed.notifications, job.notifications);
+
+        job.notifications.clear();
+        let xml = JobConfig::Freestyle(job).to_xml();
+        assert!(!xml.contains(NOTIFICATION_PROPERTY));
+    }
+
+    #[test]
+    fn freestyle_roundtrip_is_byte_exact() {
+        let src = "<?xml version='1.1' encoding='UTF-8'?>\n<project>\
+            <description>nightly</description><canRoam>true</canRoam><disabled>false</disabled>\
+            <builders><hudson.tasks.Shell><command>make</command></hudson.tasks.Shell>\
+            <hudson.tasks.Maven><targets>install</targets></hudson.tasks.Maven>\
+            <hudson.tasks.Shell><command>make test</command><configuredLocalRules/></hudson.tasks.Shell>\
+            <hudson.tasks.BatchFile><command>dir</command></hudson.tasks.BatchFile></builders>\
+            <publishers/></project>";
+        let parse = |xml: &str| match JobConfig::parse(xml).unwrap() {
+            JobConfig::Freestyle(job) => job,
+            _ => panic!("expected freestyle project"),
+        };
+        let job = parse(src);
+        assert_eq!(job.shell_steps, vec!["make", "make test"]);
+        assert_eq!(JobConfig::Freestyle(job.clone()).to_xml(), src);
+
+        // replaced in place, the Maven step stays between the shell steps
+        let mut edited = job.clone();
+        edited.shell_steps[1] = "make check".to_owned();
+        assert_eq!(
+            JobConfig::Freestyle(edited).to_xml(),
+            src.replace("make test", "make check")
+        );
+
+        let mut fewer = job.clone();
+        fewer.shell_steps.pop();
+        assert_eq!(
+            JobConfig::Freestyle(fewer).to_xml(),
+            src.replace(
+                "<hudson.tasks.Shell><command>make test</command><configuredLocalRules/></hudson.tasks.Shell>",
+                ""
+            )
+        );
+
+        let mut more = job;
+        more.shell_steps.push("make dist".to_owned());
+        assert_eq!(
+            JobConfig::Freestyle(more).to_xml(),
+            src.replace(
+                "<configuredLocalRules/></hudson.tasks.Shell>",
+                "<configuredLocalRules/></hudson.tasks.Shell>\
+                <hudson.tasks.Shell><command>make dist</command></hudson.tasks.Shell>"
+            )
+        );
+    }
+
+    #[test]
+    fn pipeline_roundtrip_keeps_definition() {
+        let src = "<?xml version='1.1' encoding='UTF-8'?>\n<flow-definition plugin=\"workflow-job@1400\">\
+            <description/><keepDependencies>false</keepDependencies><properties/>\
+            <definition class=\"org.jenkinsci.plugins.workflow.cps.CpsScmFlowDefinition\" plugin=\"workflow-cps@3900\">\
+            <scm class=\"hudson.plugins.git.GitSCM\"><configVersion>2</configVersion></scm>\
+            <scriptPath>Jenkinsfile</scriptPath><lightweight>false</lightweight></definition>\
+            <disabled>false</disabled></flow-definition>";
+        let JobConfig::Pipeline(job) = JobConfig::parse(src).unwrap() else {
+            panic!("expected pipeline job")
+        };
+        assert_eq!(JobConfig::Pipeline(job.clone()).to_xml(), src);
+
+        let mut edited = job;
+        let PipelineDefinition::Scm { script_path, .. } = &mut edited.definition else {
+            panic!("expected scm definition")
+        };
+        *script_path = "ci/Jenkinsfile".to_owned();
+        assert_eq!(
+            JobConfig::Pipeline(edited).to_xml(),
+            src.replace("<scriptPath>Jenkinsfile", "<scriptPath>ci/Jenkinsfile")
+        );
+    }
+
+    #[test]
+    fn multibranch_writes_changed_fields() {
+        let src = "<?xml version='1.1' encoding='UTF-8'?>\n<org.jenkinsci.plugins.workflow.multibranch.WorkflowMultiBranchProject>\
+            <properties/><sources/></org.jenkinsci.plugins.workflow.multibranch.WorkflowMultiBranchProject>";
+        let JobConfig::Multibranch(mut job) = JobConfig::parse(src).unwrap() else {
+            panic!("expected multibranch project")
+        };
+        assert_eq!(job.script_path, "Jenkinsfile");
+        assert_eq!(JobConfig::Multibranch(job.clone()).to_xml(), src);
+
+        job.script_path = "ci/Jenkinsfile".to_owned();
+        let xml = JobConfig::Multibranch(job).to_xml();
+        assert!(!xml.contains("<description"));
+        assert!(xml.contains(
+            "<factory class=\"org.jenkinsci.plugins.workflow.multibranch.WorkflowBranchProjectFactory\">\
+            <scriptPath>ci/Jenkinsfile</scriptPath></factory>"
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+//! Concurrent futures without `futures-util`, which only the `stream` feature pulls in
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Boxed future for trait methods and recursive async functions, the same
+/// type as `futures_util::future::BoxFuture`
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Run all futures concurrently, the results in their order
+///
+/// The first error is returned right away, dropping the other futures.
+pub(crate) fn try_join_all<I, T, E>(futures: I) -> TryJoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Result<T, E>>,
+{
+    let futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let done = futures.iter().map(|_| None).collect();
+    TryJoinAll { futures, done }
+}
+
+/// Future of [`try_join_all`], named after the futures alone so that it does
+/// not capture the iterator, like `futures_util::future::TryJoinAll`
+pub(crate) struct TryJoinAll<F: Future> {
+    futures: Vec<Pin<Box<F>>>,
+    done: Vec<Option<F::Output>>,
+}
+
+// the futures are boxed and the results never pinned
+impl<F: Future> Unpin for TryJoinAll<F> {}
+
+impl<F, T, E> Future for TryJoinAll<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = Result<Vec<T>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut pending = false;
+        for (future, slot) in this.futures.iter_mut().zip(this.done.iter_mut()) {
+            if slot.is_some() {
+                continue;
+            }
+            match future.as_mut().poll(cx) {
+                Poll::Ready(Ok(t)) => *slot = Some(Ok(t)),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(this
+            .done
+            .iter_mut()
+            .filter_map(|slot| slot.take().and_then(Result::ok))
+            .collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn join_in_order_and_fail_fast() {
+        let sleep = |ms: u64| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            Ok::<_, String>(ms)
+        };
+        assert_eq!(
+            try_join_all([sleep(30), sleep(10), sleep(20)]).await,
+            Ok(vec![30, 10, 20])
+        );
+        let fail = try_join_all([
+            Box::pin(sleep(1000)) as BoxFuture<'_, _>,
+            Box::pin(async { Err("failed".to_owned()) }),
+        ]);
+        let res = tokio::time::timeout(Duration::from_millis(500), fail).await;
+        assert_eq!(res.unwrap(), Err("failed".to_owned()));
+    }
+}
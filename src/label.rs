@@ -0,0 +1,209 @@
+//! Jenkins label expressions, e.g. `linux && !docker || (gpu && cuda11)`
+//!
+//! Operators from highest to lowest precedence: `!`, `&&`, `||`, `->` and
+//! `<->`. Labels containing operator characters or spaces are quoted with `"`.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Result};
+
+/// Parsed label expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelExpr {
+    Label(String),
+    Not(Box<LabelExpr>),
+    And(Box<LabelExpr>, Box<LabelExpr>),
+    Or(Box<LabelExpr>, Box<LabelExpr>),
+    Implies(Box<LabelExpr>, Box<LabelExpr>),
+    Iff(Box<LabelExpr>, Box<LabelExpr>),
+}
+
+impl LabelExpr {
+    /// Whether a node with `labels` satisfies the expression
+    pub fn matches(&self, labels: &[&str]) -> bool {
+        match self {
+            LabelExpr::Label(l) => labels.contains(&l.as_str()),
+            LabelExpr::Not(e) => !e.matches(labels),
+            LabelExpr::And(a, b) => a.matches(labels) && b.matches(labels),
+            LabelExpr::Or(a, b) => a.matches(labels) || b.matches(labels),
+            LabelExpr::Implies(a, b) => !a.matches(labels) || b.matches(labels),
+            LabelExpr::Iff(a, b) => a.matches(labels) == b.matches(labels),
+        }
+    }
+}
+
+/// Whether a node with `node_labels` can run builds restricted to `expr`
+///
+/// Jenkins also assigns each node its own name as a label, include it in
+/// `node_labels` to match expressions naming a node.
+pub fn node_matches(node_labels: &[&str], expr: &LabelExpr) -> bool {
+    expr.matches(node_labels)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Label(String),
+    Not,
+    And,
+    Or,
+    Implies,
+    Iff,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        let (token, len) = match c {
+            '!' => (Token::Not, 1),
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '&' if rest.starts_with("&&") => (Token::And, 2),
+            '|' if rest.starts_with("||") => (Token::Or, 2),
+            '-' if rest.starts_with("->") => (Token::Implies, 2),
+            '<' if rest.starts_with("<->") => (Token::Iff, 3),
+            '"' => {
+                let Some(end) = rest[1..].find('"') else {
+                    bail!("label: unterminated quote in `{}`", s)
+                };
+                (Token::Label(rest[1..end + 1].to_owned()), end + 2)
+            }
+            '&' | '|' | '<' => bail!("label: unexpected `{}` in `{}`", c, s),
+            _ => {
+                let end = rest
+                    .char_indices()
+                    .find(|&(i, c)| {
+                        c.is_whitespace()
+                            || "!()&|\"".contains(c)
+                            || rest[i..].starts_with("->")
+                            || rest[i..].starts_with("<->")
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                (Token::Label(rest[..end].to_owned()), end)
+            }
+        };
+        tokens.push(token);
+        rest = &rest[len..];
+    }
+}
+
+/// Precedence climbing over the binary operators
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    src: &'a str,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<LabelExpr> {
+        const LEVELS: [Token; 4] = [Token::Iff, Token::Implies, Token::Or, Token::And];
+        let Some(op) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while self.peek() == Some(op) {
+            self.pos += 1;
+            let rhs = Box::new(self.binary(level + 1)?);
+            let lhs_box = Box::new(lhs);
+            lhs = match op {
+                Token::Iff => LabelExpr::Iff(lhs_box, rhs),
+                Token::Implies => LabelExpr::Implies(lhs_box, rhs),
+                Token::Or => LabelExpr::Or(lhs_box, rhs),
+                _ => LabelExpr::And(lhs_box, rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<LabelExpr> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Not) => Ok(LabelExpr::Not(Box::new(self.unary()?))),
+            Some(Token::Label(l)) => Ok(LabelExpr::Label(l)),
+            Some(Token::Open) => {
+                let e = self.binary(0)?;
+                if self.peek() != Some(&Token::Close) {
+                    bail!("label: missing `)` in `{}`", self.src)
+                }
+                self.pos += 1;
+                Ok(e)
+            }
+            _ => bail!("label: expected label in `{}`", self.src),
+        }
+    }
+}
+
+impl FromStr for LabelExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            src: s,
+        };
+        let expr = parser.binary(0)?;
+        if parser.pos != tokens.len() {
+            bail!("label: unexpected token in `{}`", s)
+        }
+        Ok(expr)
+    }
+}
+
+impl fmt::Display for LabelExpr {
+    /// Fully parenthesized, parses back to the same expression
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelExpr::Label(l)
+                if l.is_empty()
+                    || l.contains(|c: char| c.is_whitespace() || "!()&|<\"".contains(c))
+                    || l.contains("->") =>
+            {
+                write!(f, "\"{}\"", l)
+            }
+            LabelExpr::Label(l) => f.write_str(l),
+            LabelExpr::Not(e) => write!(f, "!{}", e),
+            LabelExpr::And(a, b) => write!(f, "({} && {})", a, b),
+            LabelExpr::Or(a, b) => write!(f, "({} || {})", a, b),
+            LabelExpr::Implies(a, b) => write!(f, "({} -> {})", a, b),
+            LabelExpr::Iff(a, b) => write!(f, "({} <-> {})", a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_match_labels() {
+        let expr: LabelExpr = "linux && !docker || (gpu && cuda11)".parse().unwrap();
+        assert_eq!(expr.to_string(), "((linux && !docker) || (gpu && cuda11))");
+        assert!(node_matches(&["linux", "x86-64"], &expr));
+        assert!(!node_matches(&["linux", "docker"], &expr));
+        assert!(node_matches(&["windows", "gpu", "cuda11"], &expr));
+
+        let expr: LabelExpr = r#"linux-arm64 -> "build farm""#.parse().unwrap();
+        assert!(node_matches(&["windows"], &expr));
+        assert!(!node_matches(&["linux-arm64"], &expr));
+        assert!(node_matches(&["linux-arm64", "build farm"], &expr));
+        assert_eq!(expr.to_string(), r#"(linux-arm64 -> "build farm")"#);
+        assert_eq!(expr.to_string().parse::<LabelExpr>().unwrap(), expr);
+
+        assert!("linux &&".parse::<LabelExpr>().is_err());
+        assert!("(linux".parse::<LabelExpr>().is_err());
+        assert!("linux docker".parse::<LabelExpr>().is_err());
+    }
+}
@@ -1,172 +1,81 @@
-use std::{collections::HashMap, time::Duration};
-
-use anyhow::{bail, Context, Result};
-use log::{error, info, trace, warn};
-use reqwest::RequestBuilder;
-use serde::Deserialize;
-use tokio::time::sleep;
-
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("API error: {0}")]
-    APIError(String),
-    #[error("Queue item not exists, maybe already running or finished")]
-    QueueItemNotExists,
-    #[error("Network error: {0}")]
-    NetworkError(reqwest::Error),
-}
-
-/// [Jenkins : Remote access API](https://wiki.jenkins.io/display/JENKINS/Remote+access+API)
-///
-pub struct Jenkins {
-    hc: reqwest::Client,
-    url: String,
-    user: String,
-    password: String,
-}
-
-impl Jenkins {
-    /// Create Jenkins instance
-    ///
-    /// ## Arguments
-    ///
-    /// * `password` - password or api token of user
-    ///
-    pub fn new(url: &str, user: &str, password: &str) -> Jenkins {
-        let hc = reqwest::Client::builder()
-            .connect_timeout(Duration::from_secs(3))
-            .build()
-            .expect("failed to init http client");
-        Jenkins {
-            hc,
-            url: url.to_owned(),
-            user: user.to_owned(),
-            password: password.to_owned(),
-        }
-    }
-
-    pub fn get_url(&self) -> &str {
-        &self.url
-    }
-
-    fn post(&self, url: &str) -> RequestBuilder {
-        self.hc
-            .post(url)
-            .basic_auth(&self.user, Some(&self.password))
-    }
-
-    fn get(&self, url: &str) -> RequestBuilder {
-        self.hc
-            .get(url)
-            .basic_auth(&self.user, Some(&self.password))
-    }
-
-    /// Poll from new build queue item url until build number available
-    ///
-    /// [reference](https://docs.cloudbees.com/docs/cloudbees-ci-kb/latest/client-and-managed-controllers/get-build-number-with-rest-api)
-    ///
-    /// ## Arguments
-    ///
-    /// * `queue_item_url` - `location` field in `build`/`buildWithParameters` response header
-    ///
-    pub async fn poll_queue_item(
-        &self,
-        queue_item_url: &str,
-    ) -> Result<QueueItemRes, anyhow::Error> {
-        let queue_url = format!("{}api/json", queue_item_url);
-        loop {
-            sleep(Duration::from_secs(3)).await;
-            match self.get(&queue_url).send().await {
-                Ok(queue_res) => {
-                    info!("queue_res={:?}", queue_res);
-                    if queue_res.status().is_client_error() {
-                        bail!(Error::QueueItemNotExists)
-                    }
-                    let qi_res: QueueItemRes = queue_res
-                        .json()
-                        .await
-                        .context("parse queue item payload as json")?;
-                    if qi_res.executable.is_some() {
-                        info!("Get {}: body={:?}", queue_url, qi_res);
-                        return Ok(qi_res);
-                    } else {
-                        trace!("Get {}: body={:?}", queue_url, qi_res);
-                    }
-                }
-                Err(err) => {
-                    error!("Get {}: err={:?}", queue_url, err);
-                    // Err(err)
-                    bail!(Error::NetworkError(err));
-                }
-            }
-        }
-    }
-
-    /// [Parameterized Build](https://wiki.jenkins.io/display/JENKINS/Parameterized-Build.html)
-    ///
-    /// ## Arguments
-    ///
-    /// * `job` - job name
-    /// * `params` - parameters to trigger a build
-    ///
-    pub async fn build_with_parameter(
-        &self,
-        job: &str,
-        params: HashMap<&str, &str>,
-    ) -> Result<QueueItemRes> {
-        let url = format!("{}/job/{}/buildWithParameters", self.url, job);
-        match self.post(&url).form(&params).send().await {
-            Ok(res) => {
-                if res.status().is_success() {
-                    info!("buildWithParameters - job={}, res={:?}", job, res);
-                    if let Some(location) = res.headers().get("location") {
-                        let queue_url = location.to_str().expect("location header");
-                        Ok(self.poll_queue_item(queue_url).await?)
-                    } else {
-                        bail!(Error::APIError("location header not available".to_owned()))
-                    }
-                } else {
-                    warn!("buildWithParameters - job={}, res={:?}", job, res);
-                    bail!(Error::APIError(format!("http status: {}", res.status())))
-                }
-            }
-            Err(err) => {
-                error!("buildWithParameters - job={}, err={:?}", job, err);
-                bail!(err)
-            }
-        }
-    }
-}
-
-#[derive(Deserialize, Debug)]
-pub struct QueueItemExecutable {
-    pub number: i32,
-    pub url: String,
-}
-#[derive(Deserialize, Debug)]
-pub struct QueueItemRes {
-    pub why: Option<String>,
-    pub executable: Option<QueueItemExecutable>,
-}
-
+pub mod action;
+pub mod admin;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "blueocean")]
+pub mod blueocean;
+pub mod build;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod client;
+#[cfg(feature = "commit-status")]
+pub mod commit_status;
+#[cfg(feature = "console")]
+pub mod console;
+#[cfg(feature = "credentials")]
+pub mod credentials;
+#[cfg(feature = "download")]
+pub mod download;
+pub mod error;
+#[cfg(feature = "exclusive")]
+pub mod exclusive;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+pub mod glob;
+#[cfg(feature = "indexer")]
+pub mod indexer;
+#[cfg(feature = "jenkinsfile")]
+pub mod jenkinsfile;
+pub mod job;
+#[cfg(feature = "job-config")]
+pub mod job_config;
+mod join;
+pub mod label;
+#[cfg(feature = "plugins-ext")]
+pub mod metrics;
 #[cfg(test)]
-mod tests {
-    use super::*;
-
-    // #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-    #[tokio::test]
-    async fn build_with_parameter() {
-        let _ = env_logger::builder().is_test(true).try_init();
+mod mock;
+pub mod model;
+#[cfg(feature = "multibranch")]
+pub mod multibranch;
+pub mod node;
+#[cfg(feature = "plugins-ext")]
+pub mod notification;
+pub mod parameters;
+#[cfg(feature = "plugins-ext")]
+pub mod plugins;
+pub mod prelude;
+pub mod queue;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+#[cfg(any(feature = "vault", feature = "aws-secrets"))]
+pub mod secrets;
+pub mod shutdown;
+#[cfg(feature = "sse")]
+pub mod sse;
+mod strict;
+#[cfg(feature = "otel")]
+pub mod trace;
+pub mod version;
+#[cfg(feature = "workspace")]
+pub mod workspace;
+pub mod xml;
 
-        let cli = Jenkins::new(
-            "https://jenkins.domain.com",
-            "jenkins-user",
-            "jenkins-token",
-        );
-        let params = HashMap::from([("HostLimit", "xxx"), ("Module", "ansible.builtin.ping")]);
-        let res = cli
-            .build_with_parameter("ansible-global-adhoc", params)
-            .await;
-        println!("{:?}", res);
-    }
-}
+// everything used to live in the crate root, keep those paths working
+pub use admin::*;
+#[cfg(feature = "blueocean")]
+pub use blueocean::*;
+pub use build::*;
+pub use client::*;
+pub use error::Error;
+pub use job::*;
+pub use join::BoxFuture;
+pub use model::*;
+pub use node::*;
+#[cfg(feature = "plugins-ext")]
+pub use plugins::*;
+pub use queue::*;
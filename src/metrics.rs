@@ -0,0 +1,285 @@
+//! Controller metrics of the [Metrics plugin](https://plugins.jenkins.io/metrics/)
+//! and the [Prometheus plugin](https://plugins.jenkins.io/prometheus/)
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Jenkins};
+
+/// Dropwizard metrics registry, see [`Jenkins::get_metrics`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MetricsRes {
+    pub version: String,
+    pub gauges: BTreeMap<String, Gauge>,
+    pub counters: BTreeMap<String, Counter>,
+    pub histograms: BTreeMap<String, Histogram>,
+    pub meters: BTreeMap<String, Meter>,
+    pub timers: BTreeMap<String, Timer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Gauge {
+    /// usually a number, some gauges report strings or lists
+    pub value: serde_json::Value,
+}
+
+impl Gauge {
+    pub fn as_f64(&self) -> Option<f64> {
+        self.value.as_f64()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub count: i64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub p98: f64,
+    pub p99: f64,
+    pub p999: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Meter {
+    pub count: i64,
+    pub m1_rate: f64,
+    pub m5_rate: f64,
+    pub m15_rate: f64,
+    pub mean_rate: f64,
+    /// e.g. `events/minute`
+    pub units: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Timer {
+    #[serde(flatten)]
+    pub histogram: Histogram,
+    pub m1_rate: f64,
+    pub m5_rate: f64,
+    pub m15_rate: f64,
+    pub mean_rate: f64,
+    /// unit of the histogram values, e.g. `seconds`
+    pub duration_units: Option<String>,
+    pub rate_units: Option<String>,
+}
+
+/// Result of one health check, see [`Jenkins::healthcheck`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub healthy: bool,
+    pub message: Option<String>,
+}
+
+/// One sample of a Prometheus metric, see [`Jenkins::get_prometheus_metrics`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrometheusSample {
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+    /// milliseconds since epoch, if exposed
+    pub timestamp: Option<i64>,
+}
+
+/// Parse the Prometheus text exposition format into samples by metric name
+///
+/// `# HELP`/`# TYPE` comments are skipped, histogram and summary series keep
+/// their `_bucket`/`_sum`/`_count` suffixed names.
+pub fn parse_prometheus(text: &str) -> Result<BTreeMap<String, Vec<PrometheusSample>>> {
+    let mut metrics: BTreeMap<String, Vec<PrometheusSample>> = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, labels, rest) =
+            parse_series(line).with_context(|| format!("prometheus line {}: {}", i + 1, line))?;
+        let mut fields = rest.split_whitespace();
+        let value = match fields.next() {
+            Some("+Inf") => f64::INFINITY,
+            Some("-Inf") => f64::NEG_INFINITY,
+            Some(v) => v
+                .parse()
+                .with_context(|| format!("prometheus line {}: bad value {}", i + 1, v))?,
+            None => bail!("prometheus line {}: missing value", i + 1),
+        };
+        let timestamp = fields.next().and_then(|t| t.parse().ok());
+        metrics.entry(name).or_default().push(PrometheusSample {
+            labels,
+            value,
+            timestamp,
+        });
+    }
+    Ok(metrics)
+}
+
+/// Split `name{k="v",...} rest` into its parts
+fn parse_series(line: &str) -> Result<(String, BTreeMap<String, String>, &str)> {
+    let end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let name = line[..end].to_owned();
+    let mut labels = BTreeMap::new();
+    let mut rest = &line[end..];
+    if let Some(body) = rest.strip_prefix('{') {
+        let mut chars = body.char_indices();
+        let mut key = String::new();
+        loop {
+            let Some((i, c)) = chars.next() else {
+                bail!("unterminated labels")
+            };
+            match c {
+                '}' => {
+                    rest = &body[i + 1..];
+                    break;
+                }
+                ',' | ' ' => {}
+                '=' => {
+                    if chars.next().map(|(_, c)| c) != Some('"') {
+                        bail!("expected quoted label value")
+                    }
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, 'n')) => value.push('\n'),
+                                Some((_, c)) => value.push(c),
+                                None => bail!("unterminated label value"),
+                            },
+                            Some((_, c)) => value.push(c),
+                            None => bail!("unterminated label value"),
+                        }
+                    }
+                    labels.insert(std::mem::take(&mut key), value);
+                }
+                c => key.push(c),
+            }
+        }
+    }
+    Ok((name, labels, rest))
+}
+
+impl Jenkins {
+    /// Get controller metrics
+    ///
+    /// ## Arguments
+    ///
+    /// * `api_key` - access key configured for the Metrics plugin
+    ///
+    pub async fn get_metrics(&self, api_key: &str) -> Result<MetricsRes> {
+        let url = format!("{}/metrics/{}/metrics", self.url, api_key);
+        self.get_json(&url).await
+    }
+
+    /// Run the Metrics plugin health checks, e.g. `disk-space`, `plugins`, `thread-deadlock`
+    ///
+    /// ## Arguments
+    ///
+    /// * `api_key` - access key configured for the Metrics plugin
+    ///
+    pub async fn healthcheck(&self, api_key: &str) -> Result<BTreeMap<String, HealthCheck>> {
+        let url = format!("{}/metrics/{}/healthcheck", self.url, api_key);
+        let res = self.send(self.get(&url)).await?;
+        // unhealthy checks are reported with 500 and the same body
+        if !(res.status().is_success() || res.status() == StatusCode::INTERNAL_SERVER_ERROR) {
+            warn!("healthcheck - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        self.json_body(res)
+            .await
+            .context("parse healthcheck payload as json")
+    }
+
+    /// Get the samples exported by the Prometheus plugin, by metric name
+    pub async fn get_prometheus_metrics(&self) -> Result<BTreeMap<String, Vec<PrometheusSample>>> {
+        let url = format!("{}/prometheus/", self.url);
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("prometheus metrics - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let text = res.text().await.map_err(Error::NetworkError)?;
+        parse_prometheus(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_metrics() {
+        let res: MetricsRes = serde_json::from_str(
+            r#"{"version":"4.0.0",
+                "gauges":{"jenkins.executor.count.value":{"value":8},"vm.name":{"value":"OpenJDK"}},
+                "counters":{"http.activeRequests":{"count":2}},
+                "meters":{"http.responseCodes.ok":{"count":10,"m15_rate":0.1,"m1_rate":0.2,"m5_rate":0.3,"mean_rate":0.4,"units":"events/second"}},
+                "timers":{"jenkins.job.queuing.duration":{"count":3,"max":1.5,"mean":1.0,"min":0.5,
+                    "p50":1.0,"p75":1.2,"p95":1.5,"p98":1.5,"p99":1.5,"p999":1.5,"stddev":0.4,
+                    "m15_rate":0.0,"m1_rate":0.0,"m5_rate":0.0,"mean_rate":0.01,
+                    "duration_units":"seconds","rate_units":"calls/second"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            res.gauges["jenkins.executor.count.value"].as_f64(),
+            Some(8.0)
+        );
+        assert_eq!(res.gauges["vm.name"].as_f64(), None);
+        assert_eq!(res.counters["http.activeRequests"].count, 2);
+        assert_eq!(
+            res.timers["jenkins.job.queuing.duration"].histogram.p95,
+            1.5
+        );
+        assert!(res.histograms.is_empty());
+    }
+
+    #[test]
+    fn parse_prometheus_text() {
+        let metrics = parse_prometheus(
+            "# HELP default_jenkins_executors_available Executors Available\n\
+             # TYPE default_jenkins_executors_available gauge\n\
+             default_jenkins_executors_available 7.0\n\
+             jenkins_job_duration_milliseconds_summary_count{jenkins_job=\"a \\\"b\\\"\",repo=\"NA\",} 3.0 1700000000000\n\
+             http_requests_bucket{le=\"+Inf\"} +Inf\n",
+        )
+        .unwrap();
+        assert_eq!(metrics["default_jenkins_executors_available"][0].value, 7.0);
+        let summary = &metrics["jenkins_job_duration_milliseconds_summary_count"][0];
+        assert_eq!(summary.labels["jenkins_job"], "a \"b\"");
+        assert_eq!(summary.labels["repo"], "NA");
+        assert_eq!(summary.timestamp, Some(1700000000000));
+        assert!(metrics["http_requests_bucket"][0].value.is_infinite());
+        assert!(parse_prometheus("bad{le=\"1\" 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_prometheus_metrics() {
+        use crate::mock::{response, MockServer};
+
+        let server = MockServer::start(vec![response(
+            "200 OK",
+            &[("Content-Type", "text/plain; version=0.0.4")],
+            "# TYPE default_jenkins_executors_available gauge\n\
+             default_jenkins_executors_available 7.0\n",
+        )])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let metrics = cli.get_prometheus_metrics().await.unwrap();
+        assert_eq!(metrics["default_jenkins_executors_available"][0].value, 7.0);
+        assert!(server.requests()[0].starts_with("GET /prometheus/ "));
+    }
+}
@@ -0,0 +1,386 @@
+//! Response types shared across the API areas
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{Action, CauseAction, FromAction},
+    BlockReason, BuildHandle,
+};
+
+/// Point in time a model stands for, e.g. when a build started or an item
+/// was queued
+///
+/// Durations and times of the models are `std` types, converted to `chrono`
+/// or `time` ones with the features of the same name.
+pub trait Timestamped {
+    fn time(&self) -> SystemTime;
+
+    #[cfg(feature = "chrono")]
+    fn to_chrono(&self) -> chrono::DateTime<chrono::Utc> {
+        self.time().into()
+    }
+
+    #[cfg(feature = "time")]
+    fn to_offset_date_time(&self) -> ::time::OffsetDateTime {
+        self.time().into()
+    }
+}
+
+/// Response fields not modelled by the typed structs, kept with the `extras` feature
+#[cfg(feature = "extras")]
+pub type Extras = serde_json::Map<String, serde_json::Value>;
+
+impl QueueItemRes {
+    /// Handle of the build once the queue item became executable
+    pub fn build_handle(&self, job: &str) -> Option<BuildHandle> {
+        self.executable.as_ref().map(|e| BuildHandle {
+            job: job.to_owned(),
+            number: e.number,
+            url: e.url.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueueItemExecutable {
+    pub number: i32,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueueItemRes {
+    pub why: Option<String>,
+    pub executable: Option<QueueItemExecutable>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobRes {
+    pub name: String,
+    pub url: String,
+    #[serde(rename = "_class")]
+    pub class: String,
+    /// `None` for folders
+    pub color: Option<String>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+/// Job with its children when it is a folder, see [`Jenkins::crawl_jobs`](crate::Jenkins::crawl_jobs)
+#[derive(Debug)]
+pub struct JobNode {
+    pub job: JobRes,
+    pub children: Vec<JobNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Artifact {
+    pub display_path: Option<String>,
+    pub file_name: String,
+    pub relative_path: String,
+}
+
+/// See [`Jenkins::get_folder`](crate::Jenkins::get_folder)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FolderRes {
+    pub name: String,
+    pub url: String,
+    pub description: Option<String>,
+    pub jobs: Vec<JobRes>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueueTask {
+    pub name: String,
+    pub url: Option<String>,
+}
+
+/// Item in the build queue, see [`Jenkins::get_queue`](crate::Jenkins::get_queue)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueItem {
+    pub id: i64,
+    pub why: Option<String>,
+    #[serde(default)]
+    pub blocked: bool,
+    #[serde(default)]
+    pub buildable: bool,
+    #[serde(default)]
+    pub stuck: bool,
+    #[serde(with = "epoch_millis")]
+    pub in_queue_since: SystemTime,
+    pub task: QueueTask,
+    /// parameters as `\nNAME=value` lines
+    #[serde(default)]
+    pub params: String,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+impl Timestamped for QueueItem {
+    fn time(&self) -> SystemTime {
+        self.in_queue_since
+    }
+}
+
+impl QueueItem {
+    pub fn block_reason(&self) -> BlockReason {
+        BlockReason::classify(self.why.as_deref().unwrap_or_default())
+    }
+
+    pub fn in_queue_since_millis(&self) -> i64 {
+        to_epoch_millis(self.in_queue_since)
+    }
+}
+
+/// Node state, see [`Jenkins::get_node`](crate::Jenkins::get_node)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputerRes {
+    pub display_name: String,
+    /// no build running
+    pub idle: bool,
+    pub offline: bool,
+    /// put offline by a user, see [`Jenkins::set_temporarily_offline`](crate::Jenkins::set_temporarily_offline)
+    pub temporarily_offline: bool,
+    pub offline_cause_reason: Option<String>,
+    pub num_executors: i32,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+/// See [`Jenkins::get_executors`](crate::Jenkins::get_executors)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutorRes {
+    pub number: i32,
+    pub idle: bool,
+    pub current_executable: Option<QueueItemExecutable>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuildRes {
+    pub number: i32,
+    pub url: String,
+    pub building: bool,
+    pub result: Option<String>,
+    /// zero while building
+    #[serde(with = "duration_millis")]
+    pub duration: Duration,
+    /// start of the build
+    #[serde(with = "epoch_millis")]
+    pub timestamp: SystemTime,
+    /// node the build ran on, `""` for the controller, missing for pipelines,
+    /// see [`PipelineStage::exec_node`](crate::PipelineStage::exec_node)
+    #[serde(rename = "builtOn", default, skip_serializing_if = "Option::is_none")]
+    pub built_on: Option<String>,
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// see [`BuildRes::action`]
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    /// fields not modelled above
+    #[cfg(feature = "extras")]
+    #[serde(flatten)]
+    pub extra: Extras,
+}
+
+/// Why a build was started, see [`BuildRes::causes`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildCause {
+    /// e.g. `hudson.model.Cause$UserIdCause`, `hudson.model.Cause$RemoteCause`
+    #[serde(rename = "_class")]
+    pub class: Option<String>,
+    pub short_description: String,
+    pub user_id: Option<String>,
+    pub user_name: Option<String>,
+    pub upstream_project: Option<String>,
+    pub upstream_build: Option<i32>,
+    /// remote address of a `RemoteCause`
+    pub addr: Option<String>,
+    /// `cause` text of a `RemoteCause`
+    pub note: Option<String>,
+}
+
+impl Timestamped for BuildRes {
+    fn time(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+impl BuildRes {
+    /// First action of type `T`
+    ///
+    /// ```no_run
+    /// # async fn f(cli: &jenkins_rs::Jenkins) -> anyhow::Result<()> {
+    /// use jenkins_rs::action::TestResultAction;
+    /// let build = cli.get_build("deploy", 42).await?;
+    /// if let Some(tests) = build.action::<TestResultAction>() {
+    ///     println!("{} of {} tests failed", tests.fail_count, tests.total_count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn action<T: FromAction>(&self) -> Option<&T> {
+        self.actions.iter().find_map(T::from_action)
+    }
+
+    /// Causes of the `CauseAction`, e.g. user, upstream build or remote trigger
+    pub fn causes(&self) -> &[BuildCause] {
+        self.action::<CauseAction>()
+            .map(|a| a.causes.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn duration_millis(&self) -> i64 {
+        self.duration.as_millis() as i64
+    }
+
+    pub fn timestamp_millis(&self) -> i64 {
+        to_epoch_millis(self.timestamp)
+    }
+}
+
+/// Year, month and day of days since epoch, Howard Hinnant's algorithm
+#[cfg(any(feature = "scheduler", feature = "aws-secrets"))]
+pub(crate) fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        yoe + era * 400 + i64::from(month <= 2),
+        month as u32,
+        day as u32,
+    )
+}
+
+pub(crate) fn from_epoch_millis(millis: i64) -> SystemTime {
+    if millis >= 0 {
+        UNIX_EPOCH + Duration::from_millis(millis as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs())
+    }
+}
+
+pub(crate) fn to_epoch_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// `SystemTime` as Jenkins epoch millis
+pub(crate) mod epoch_millis {
+    use std::time::SystemTime;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        super::to_epoch_millis(*time).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        Ok(super::from_epoch_millis(i64::deserialize(deserializer)?))
+    }
+}
+
+/// `Duration` as Jenkins millis, negative values clamp to zero
+pub(crate) mod duration_millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        (d.as_millis() as i64).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let millis = i64::deserialize(deserializer)?;
+        Ok(Duration::from_millis(millis.max(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_causes_from_actions() {
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":3,"url":"u","building":false,"result":"SUCCESS","duration":1,"timestamp":2,
+                "actions":[{"_class":"hudson.model.CauseAction","causes":[
+                    {"_class":"hudson.model.Cause$RemoteCause","shortDescription":"Started by remote host 10.0.0.1 with note: release 1.4",
+                     "addr":"10.0.0.1","note":"release 1.4"}]},{},null]}"#,
+        )
+        .unwrap();
+        assert_eq!(build.causes().len(), 1);
+        assert_eq!(build.causes()[0].note.as_deref(), Some("release 1.4"));
+    }
+
+    #[cfg(feature = "extras")]
+    #[test]
+    fn keep_extra_fields() {
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":3,"url":"http://ci/job/a/3/","building":false,"result":"SUCCESS",
+                "duration":10,"timestamp":1,"keepLog":true,"actions":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(build.extra["keepLog"], serde_json::Value::Bool(true));
+        assert!(!build.extra.contains_key("actions"));
+    }
+
+    #[test]
+    fn build_res_roundtrip() {
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":3,"url":"http://ci/job/a/3/","building":false,"result":"SUCCESS",
+                "duration":10,"timestamp":1700000000250,"actions":[{},{"_class":"hudson.model.CauseAction",
+                "causes":[{"_class":"hudson.model.Cause$UserIdCause",
+                "shortDescription":"Started by jarod","userId":"jarod"}]}]}"#,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&build).unwrap();
+        assert_eq!(serde_json::from_str::<BuildRes>(&json).unwrap(), build);
+        assert_eq!(build.causes()[0].user_id.as_deref(), Some("jarod"));
+        assert_eq!(build.duration, Duration::from_millis(10));
+        assert_eq!(build.timestamp_millis(), 1_700_000_000_250);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["timestamp"], 1_700_000_000_250i64);
+    }
+
+    #[cfg(all(feature = "chrono", feature = "time"))]
+    #[test]
+    fn timestamp_conversions() {
+        let build: BuildRes = serde_json::from_str(
+            r#"{"number":3,"url":"u","building":false,"result":"SUCCESS","duration":1,
+                "timestamp":1700000000250,"actions":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(build.to_chrono().timestamp_millis(), 1_700_000_000_250);
+        assert_eq!(
+            build.to_offset_date_time().unix_timestamp_nanos(),
+            1_700_000_000_250_000_000
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Organization folders and multibranch projects
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::{Error, Jenkins, QueueItemHandle};
+
+/// How long [`Jenkins::build_pull_request`] waits for indexing to create a missing PR job
+const PR_INDEXING_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Repository discovered by an organization folder scan,
+/// see [`Jenkins::list_organization_repositories`]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationRepository {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub url: String,
+    /// usually `org.jenkinsci.plugins.workflow.multibranch.WorkflowMultiBranchProject`
+    #[serde(rename = "_class")]
+    pub class: String,
+    pub description: Option<String>,
+    /// branch and pull request jobs of the repository
+    #[serde(default, deserialize_with = "count")]
+    pub jobs: usize,
+}
+
+fn count<'de, D: serde::Deserializer<'de>>(d: D) -> Result<usize, D::Error> {
+    Ok(Vec::<serde::de::IgnoredAny>::deserialize(d)?.len())
+}
+
+/// Branch or pull request job of a multibranch project, see [`Jenkins::list_branches`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchJob {
+    /// job name, e.g. `main` or `PR-42`
+    pub name: String,
+    pub display_name: Option<String>,
+    pub url: String,
+    pub color: Option<String>,
+    pub kind: BranchKind,
+    pub metadata: ScmMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchKind {
+    Branch,
+    /// `PR-{n}` (GitHub, Bitbucket) or `MR-{n}` (GitLab) jobs
+    PullRequest {
+        number: u32,
+    },
+}
+
+impl BranchKind {
+    fn from_name(name: &str) -> BranchKind {
+        name.strip_prefix("PR-")
+            .or_else(|| name.strip_prefix("MR-"))
+            .and_then(|n| n.parse().ok())
+            .map_or(BranchKind::Branch, |number| BranchKind::PullRequest {
+                number,
+            })
+    }
+}
+
+/// SCM head metadata contributed by the branch source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScmMetadata {
+    /// e.g. pull request title
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// e.g. pull request url on the SCM server
+    pub url: Option<String>,
+    /// pull request author id
+    pub author: Option<String>,
+    pub author_display_name: Option<String>,
+    pub author_email: Option<String>,
+    /// branch a pull request merges into
+    pub target_branch: Option<String>,
+    /// default branch of the repository
+    pub primary: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BranchJobRes {
+    name: String,
+    display_name: Option<String>,
+    url: String,
+    color: Option<String>,
+    #[serde(default)]
+    actions: Vec<ScmMetadataAction>,
+    #[serde(default)]
+    property: Vec<BranchPropertyRes>,
+    last_build: Option<LastBuildRes>,
+}
+
+/// `BranchJobProperty` of the SCM head the job was created for
+#[derive(Deserialize, Debug)]
+struct BranchPropertyRes {
+    branch: Option<ScmBranchRes>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ScmBranchRes {
+    head: Option<ScmHeadRes>,
+}
+
+/// `target` is set for a `ChangeRequestSCMHead`
+#[derive(Deserialize, Debug)]
+struct ScmHeadRes {
+    target: Option<ScmHeadName>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ScmHeadName {
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PullRequestRes {
+    /// branch name, or the target head of some branch sources
+    target: Option<serde_json::Value>,
+}
+
+impl PullRequestRes {
+    fn target(self) -> Option<String> {
+        match self.target? {
+            serde_json::Value::String(name) => Some(name),
+            target => target["name"].as_str().map(str::to_owned),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct LastBuildRes {
+    #[serde(default)]
+    actions: Vec<BuildParametersAction>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BuildParametersAction {
+    #[serde(default)]
+    parameters: Vec<NameValue>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NameValue {
+    name: String,
+    value: Option<serde_json::Value>,
+}
+
+/// Union of `ObjectMetadataAction`, `ContributorMetadataAction` and
+/// `PrimaryInstanceMetadataAction` fields
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ScmMetadataAction {
+    #[serde(rename = "_class")]
+    class: Option<String>,
+    object_display_name: Option<String>,
+    object_description: Option<String>,
+    object_url: Option<String>,
+    contributor: Option<String>,
+    contributor_display_name: Option<String>,
+    contributor_email: Option<String>,
+    pull_request: Option<PullRequestRes>,
+}
+
+impl From<BranchJobRes> for BranchJob {
+    fn from(res: BranchJobRes) -> BranchJob {
+        let mut metadata = ScmMetadata::default();
+        for action in res.actions {
+            metadata.display_name = metadata.display_name.or(action.object_display_name);
+            metadata.description = metadata.description.or(action.object_description);
+            metadata.url = metadata.url.or(action.object_url);
+            metadata.author = metadata.author.or(action.contributor);
+            metadata.author_display_name = metadata
+                .author_display_name
+                .or(action.contributor_display_name);
+            metadata.author_email = metadata.author_email.or(action.contributor_email);
+            metadata.target_branch = metadata
+                .target_branch
+                .or(action.pull_request.and_then(PullRequestRes::target));
+            metadata.primary |= action.class.as_deref()
+                == Some("jenkins.scm.api.metadata.PrimaryInstanceMetadataAction");
+        }
+        // the head the job was created for, else what the branch source or
+        // the last build reports
+        let head_target = res
+            .property
+            .into_iter()
+            .filter_map(|p| p.branch?.head?.target?.name)
+            .next();
+        let change_target = res
+            .last_build
+            .into_iter()
+            .flat_map(|b| b.actions)
+            .flat_map(|a| a.parameters)
+            .find(|p| p.name == "CHANGE_TARGET")
+            .and_then(|p| p.value?.as_str().map(str::to_owned));
+        metadata.target_branch = head_target.or(metadata.target_branch).or(change_target);
+        BranchJob {
+            kind: BranchKind::from_name(&res.name),
+            name: res.name,
+            display_name: res.display_name,
+            url: res.url,
+            color: res.color,
+            metadata,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct BranchesRes {
+    #[serde(default)]
+    jobs: Vec<BranchJobRes>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RepositoriesRes {
+    #[serde(default)]
+    jobs: Vec<OrganizationRepository>,
+}
+
+impl Jenkins {
+    /// Schedule a scan of an organization folder (or indexing of a multibranch project)
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `github/my-org`
+    ///
+    pub async fn scan_organization_folder(&self, path: &str) -> Result<()> {
+        let url = format!("{}/build?delay=0", self.item_url(path));
+        let res = self.send(self.post(&url)).await?;
+        // jenkins redirects to the folder page after scheduling
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("scan organization folder - path={}, res={:?}", path, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("scan organization folder - path={}", path);
+        Ok(())
+    }
+
+    /// Get log of the last organization folder scan or multibranch indexing
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `github/my-org`
+    ///
+    pub async fn get_scan_log(&self, path: &str) -> Result<String> {
+        let url = format!("{}/computation/consoleText", self.item_url(path));
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get scan log - path={}, res={:?}", path, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    /// List repositories discovered by an organization folder
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - folder path, e.g. `github/my-org`
+    ///
+    pub async fn list_organization_repositories(
+        &self,
+        path: &str,
+    ) -> Result<Vec<OrganizationRepository>> {
+        let url = format!(
+            "{}/api/json?tree=jobs[name,displayName,url,_class,description,jobs[name]]",
+            self.item_url(path)
+        );
+        let res: RepositoriesRes = self.get_json(&url).await?;
+        Ok(res.jobs)
+    }
+
+    /// List branch and pull request jobs of a multibranch project
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - multibranch project path, e.g. `github/my-org/my-repo`
+    ///
+    pub async fn list_branches(&self, path: &str) -> Result<Vec<BranchJob>> {
+        let url = format!(
+            "{}/api/json?tree=jobs[name,displayName,url,color,actions[_class,objectDisplayName,objectDescription,objectUrl,contributor,contributorDisplayName,contributorEmail,pullRequest[target]],property[branch[head[target[name]]]],lastBuild[actions[parameters[name,value]]]]",
+            self.item_url(path)
+        );
+        let res: BranchesRes = self.get_json(&url).await?;
+        Ok(res.jobs.into_iter().map(BranchJob::from).collect())
+    }
+
+    /// Find the job of a pull request in a multibranch project
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - multibranch project path
+    /// * `number` - pull request number
+    ///
+    pub async fn find_pull_request(&self, path: &str, number: u32) -> Result<Option<BranchJob>> {
+        let branches = self.list_branches(path).await?;
+        Ok(branches
+            .into_iter()
+            .find(|b| b.kind == BranchKind::PullRequest { number }))
+    }
+
+    /// Trigger a build of a pull request job by pull request number
+    ///
+    /// If the `PR-{n}` job doesn't exist yet, branch indexing is triggered and
+    /// the job is polled for until it shows up.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - multibranch project path, e.g. `github/my-org/my-repo`
+    /// * `number` - pull request number
+    /// * `params` - parameters to trigger a build, may be empty
+    ///
+    pub async fn build_pull_request(
+        &self,
+        path: &str,
+        number: u32,
+        params: HashMap<&str, &str>,
+    ) -> Result<QueueItemHandle> {
+        let branch = match self.find_pull_request(path, number).await? {
+            Some(branch) => branch,
+            None => {
+                info!(
+                    "pull request job missing, indexing - path={}, pr={}",
+                    path, number
+                );
+                self.scan_organization_folder(path).await?;
+                let deadline = Instant::now() + PR_INDEXING_TIMEOUT;
+                loop {
+                    self.pause(Duration::from_secs(5)).await?;
+                    if let Some(branch) = self.find_pull_request(path, number).await? {
+                        break branch;
+                    }
+                    if Instant::now() >= deadline {
+                        bail!(Error::APIError(format!(
+                            "no job for pull request {} in {} after indexing",
+                            number, path
+                        )))
+                    }
+                }
+            }
+        };
+        let action = if params.is_empty() {
+            "build"
+        } else {
+            "buildWithParameters"
+        };
+        let mut url = Url::parse(&self.item_url(path))?;
+        url.path_segments_mut()
+            .map_err(|_| Error::APIError(format!("invalid jenkins url: {}", self.url)))?
+            .extend(["job", &branch.name, action]);
+        // `a/job/b` form accepted by the job name arguments of other methods
+        let job = format!("{}/job/{}", path.replace('/', "/job/"), branch.name);
+        self.trigger(&job, action, self.post(url.as_str()).form(&params))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_job_metadata() {
+        let res: BranchesRes = serde_json::from_str(
+            r#"{"jobs":[
+                {"name":"main","url":"u/main","color":"blue","actions":[
+                    {"_class":"jenkins.scm.api.metadata.PrimaryInstanceMetadataAction"},
+                    {"_class":"hudson.model.ParametersDefinitionProperty"}]},
+                {"name":"PR-42","displayName":"PR-42","url":"u/PR-42","actions":[
+                    {"_class":"jenkins.scm.api.metadata.ObjectMetadataAction",
+                     "objectDisplayName":"Fix flaky test","objectUrl":"https://github.com/o/r/pull/42"},
+                    {"_class":"jenkins.scm.api.metadata.ContributorMetadataAction",
+                     "contributor":"octocat","contributorEmail":"octocat@example.com"}],
+                 "property":[{"_class":"org.jenkinsci.plugins.workflow.multibranch.BranchJobProperty",
+                     "branch":{"head":{"name":"PR-42","target":{"name":"main"}}}}]},
+                {"name":"PR-43","url":"u/PR-43","actions":[
+                    {"pullRequest":{"target":"release"}}]},
+                {"name":"PR-44","url":"u/PR-44","actions":[],
+                 "lastBuild":{"actions":[{},{"_class":"hudson.model.ParametersAction",
+                     "parameters":[{"name":"CHANGE_TARGET","value":"develop"}]}]}}
+            ]}"#,
+        )
+        .unwrap();
+        let branches: Vec<BranchJob> = res.jobs.into_iter().map(BranchJob::from).collect();
+        assert_eq!(branches[0].kind, BranchKind::Branch);
+        assert!(branches[0].metadata.primary);
+        assert_eq!(branches[1].kind, BranchKind::PullRequest { number: 42 });
+        assert_eq!(branches[1].metadata.author.as_deref(), Some("octocat"));
+        assert_eq!(
+            branches[1].metadata.display_name.as_deref(),
+            Some("Fix flaky test")
+        );
+        assert_eq!(branches[0].metadata.target_branch, None);
+        assert_eq!(branches[1].metadata.target_branch.as_deref(), Some("main"));
+        assert_eq!(
+            branches[2].metadata.target_branch.as_deref(),
+            Some("release")
+        );
+        assert_eq!(
+            branches[3].metadata.target_branch.as_deref(),
+            Some("develop")
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Nodes, executors and clouds
+
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{groovy_string, model::duration_millis, ComputerRes, Error, ExecutorRes, Jenkins};
+
+const CLOUDS_SCRIPT: &str = r#"
+def j = jenkins.model.Jenkins.get()
+def provisioners = [[null, j.unlabeledNodeProvisioner]] + j.labels.collect { [it.name, it.nodeProvisioner] }
+println groovy.json.JsonOutput.toJson([
+    clouds: j.clouds.collect { [name: it.name, class: it.class.name] },
+    pendingLaunches: provisioners.collectMany { l, p ->
+        p.pendingLaunches.collect { [displayName: it.displayName, label: l, numExecutors: it.numExecutors] }
+    },
+])
+"#;
+
+/// See [`Jenkins::get_clouds`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudsRes {
+    pub clouds: Vec<Cloud>,
+    /// agents being provisioned by clouds
+    pub pending_launches: Vec<PlannedNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cloud {
+    pub name: String,
+    /// e.g. `org.csanchez.jenkins.plugins.kubernetes.KubernetesCloud`
+    pub class: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedNode {
+    pub display_name: String,
+    /// `None` for unlabeled provisioning
+    pub label: Option<String>,
+    pub num_executors: i32,
+}
+
+/// Agents waited for by [`Jenkins::provision_node`] before giving up on them
+const PROVISION_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Deserialize, Debug)]
+struct ProvisionRes {
+    nodes: Vec<PlannedNode>,
+    errors: Vec<String>,
+}
+
+/// Node with the results of the node monitors, see [`Jenkins::get_node_monitors`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeMonitors {
+    pub display_name: String,
+    pub offline: bool,
+    #[serde(default)]
+    pub monitor_data: MonitorData,
+}
+
+/// Results of the node monitors, `None` if not measured yet or the monitor is disabled
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MonitorData {
+    /// free space of the node's root directory
+    #[serde(rename = "hudson.node_monitors.DiskSpaceMonitor")]
+    pub disk_space: Option<DiskSpace>,
+    #[serde(rename = "hudson.node_monitors.TemporarySpaceMonitor")]
+    pub temporary_space: Option<DiskSpace>,
+    #[serde(rename = "hudson.node_monitors.SwapSpaceMonitor")]
+    pub swap_space: Option<SwapSpace>,
+    #[serde(rename = "hudson.node_monitors.ResponseTimeMonitor")]
+    pub response_time: Option<ResponseTime>,
+    #[serde(rename = "hudson.node_monitors.ClockMonitor")]
+    pub clock_difference: Option<ClockDifference>,
+    /// e.g. `Linux (amd64)`
+    #[serde(rename = "hudson.node_monitors.ArchitectureMonitor")]
+    pub architecture: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub path: String,
+    /// free bytes
+    pub size: u64,
+}
+
+/// Memory in bytes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapSpace {
+    pub available_physical_memory: i64,
+    pub available_swap_space: i64,
+    pub total_physical_memory: i64,
+    pub total_swap_space: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResponseTime {
+    /// average round trip of the last pings
+    #[serde(with = "duration_millis")]
+    pub average: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClockDifference {
+    /// millis the node's clock is ahead of the controller, negative if behind
+    pub diff: i64,
+}
+
+/// Limits of [`Jenkins::unhealthy_nodes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthThresholds {
+    pub min_disk_space: u64,
+    pub min_temporary_space: u64,
+    pub max_response_time: Duration,
+    pub max_clock_difference: Duration,
+}
+
+impl Default for HealthThresholds {
+    /// Jenkins' own defaults for disk space, 1 GiB
+    fn default() -> Self {
+        HealthThresholds {
+            min_disk_space: 1 << 30,
+            min_temporary_space: 1 << 30,
+            max_response_time: Duration::from_secs(5),
+            max_clock_difference: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Threshold a node crossed, see [`Jenkins::unhealthy_nodes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeProblem {
+    Offline,
+    LowDiskSpace {
+        free: u64,
+    },
+    LowTemporarySpace {
+        free: u64,
+    },
+    SlowResponse(Duration),
+    /// millis ahead of the controller, negative if behind
+    ClockDifference(i64),
+}
+
+impl NodeMonitors {
+    pub fn problems(&self, thresholds: &HealthThresholds) -> Vec<NodeProblem> {
+        let data = &self.monitor_data;
+        let mut problems = Vec::new();
+        if self.offline {
+            problems.push(NodeProblem::Offline);
+        }
+        if let Some(disk) = data
+            .disk_space
+            .as_ref()
+            .filter(|d| d.size < thresholds.min_disk_space)
+        {
+            problems.push(NodeProblem::LowDiskSpace { free: disk.size });
+        }
+        if let Some(tmp) = data
+            .temporary_space
+            .as_ref()
+            .filter(|d| d.size < thresholds.min_temporary_space)
+        {
+            problems.push(NodeProblem::LowTemporarySpace { free: tmp.size });
+        }
+        if let Some(rt) = data
+            .response_time
+            .as_ref()
+            .filter(|rt| rt.average > thresholds.max_response_time)
+        {
+            problems.push(NodeProblem::SlowResponse(rt.average));
+        }
+        if let Some(clock) = data.clock_difference.as_ref().filter(|c| {
+            u128::from(c.diff.unsigned_abs()) > thresholds.max_clock_difference.as_millis()
+        }) {
+            problems.push(NodeProblem::ClockDifference(clock.diff));
+        }
+        problems
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ComputersRes {
+    computer: Vec<NodeMonitors>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExecutorsRes {
+    #[serde(default)]
+    executors: Vec<ExecutorRes>,
+    /// flyweight executors running pipeline builds
+    #[serde(default)]
+    one_off_executors: Vec<ExecutorRes>,
+}
+
+impl Jenkins {
+    /// Get configured clouds and agents being provisioned
+    pub async fn get_clouds(&self) -> Result<CloudsRes> {
+        let out = self.run_script(CLOUDS_SCRIPT).await?;
+        serde_json::from_str(out.trim()).with_context(|| format!("parse clouds: {}", out))
+    }
+
+    /// Ask a cloud to provision agents for a label, e.g. to pre-warm capacity
+    ///
+    /// The cloud is asked directly rather than through the `NodeProvisioner`
+    /// of the label, so the agents don't show up in
+    /// [`CloudsRes::pending_launches`]. Instead the call waits until they are
+    /// launched and added to Jenkins, up to 10 minutes, and fails if any of
+    /// them could not be. Agents launched before a failure stay added.
+    ///
+    /// ## Arguments
+    ///
+    /// * `cloud` - cloud name
+    /// * `label` - label the agents must satisfy, `None` for any
+    /// * `executors` - executors needed
+    ///
+    pub async fn provision_node(
+        &self,
+        cloud: &str,
+        label: Option<&str>,
+        executors: i32,
+    ) -> Result<Vec<PlannedNode>> {
+        let script = format!(
+            r#"
+def j = jenkins.model.Jenkins.get()
+def cloud = j.getCloud({cloud})
+if (cloud == null) {{ throw new IllegalArgumentException('no such cloud: ' + {cloud}) }}
+def labelName = {label}
+def label = labelName == null ? null : j.getLabel(labelName)
+def planned = cloud.provision(new hudson.slaves.Cloud.CloudState(label, 0), {executors})
+def nodes = []
+def errors = planned ? [] : ['cloud planned no agents']
+planned.each {{ p ->
+    try {{
+        j.addNode(p.future.get({timeout}, java.util.concurrent.TimeUnit.SECONDS))
+        nodes << [displayName: p.displayName, label: labelName, numExecutors: p.numExecutors]
+    }} catch (e) {{
+        p.future.cancel(true)
+        errors << p.displayName + ': ' + e
+    }}
+}}
+println groovy.json.JsonOutput.toJson([nodes: nodes, errors: errors])
+"#,
+            cloud = groovy_string(cloud),
+            label = label
+                .map(groovy_string)
+                .unwrap_or_else(|| "null".to_owned()),
+            executors = executors,
+            timeout = PROVISION_TIMEOUT.as_secs(),
+        );
+        let out = self.run_script(&script).await?;
+        info!(
+            "provision - cloud={}, label={:?}, out={}",
+            cloud, label, out
+        );
+        let res: ProvisionRes = serde_json::from_str(out.trim())
+            .with_context(|| format!("parse provisioned nodes: {}", out))?;
+        if !res.errors.is_empty() {
+            warn!(
+                "provision failed - cloud={}, added={:?}, errors={:?}",
+                cloud, res.nodes, res.errors
+            );
+            bail!(Error::APIError(format!(
+                "provision on {} failed: {}",
+                cloud,
+                res.errors.join("; ")
+            )))
+        }
+        Ok(res.nodes)
+    }
+
+    /// Get state of a node
+    ///
+    /// ## Arguments
+    ///
+    /// * `node` - node name, `(built-in)` for the controller
+    ///
+    pub async fn get_node(&self, node: &str) -> Result<ComputerRes> {
+        let url = format!(
+            "{}/computer/{}/api/json?tree=displayName,idle,offline,temporarilyOffline,offlineCauseReason,numExecutors",
+            self.url, node
+        );
+        self.get_json(&url).await
+    }
+
+    /// Get `config.xml` of a node
+    pub async fn get_node_config(&self, node: &str) -> Result<String> {
+        let url = format!("{}/computer/{}/config.xml", self.url, node);
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get node config - node={}, res={:?}", node, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    /// Replace `config.xml` of a node, e.g. to change its labels or executors
+    ///
+    /// ## Arguments
+    ///
+    /// * `node` - node name
+    /// * `config` - full `config.xml` content
+    ///
+    pub async fn update_node_config(&self, node: &str, config: &str) -> Result<()> {
+        let url = format!("{}/computer/{}/config.xml", self.url, node);
+        let res = self.send(self.post_xml(&url, config)).await?;
+        if !res.status().is_success() {
+            warn!("update node config - node={}, res={:?}", node, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("update node config - node={}", node);
+        Ok(())
+    }
+
+    /// Why a node is offline, `None` if online
+    pub async fn get_offline_cause(&self, node: &str) -> Result<Option<String>> {
+        let computer = self.get_node(node).await?;
+        Ok(computer
+            .offline
+            .then(|| computer.offline_cause_reason.unwrap_or_default()))
+    }
+
+    pub(crate) async fn toggle_offline(&self, node: &str, reason: &str) -> Result<()> {
+        let url = format!("{}/computer/{}/toggleOffline", self.url, node);
+        let res = self
+            .send(self.post(&url).form(&[("offlineMessage", reason)]))
+            .await?;
+        if !(res.status().is_success() || res.status().is_redirection()) {
+            warn!("toggleOffline - node={}, res={:?}", node, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("toggleOffline - node={}, reason={}", node, reason);
+        Ok(())
+    }
+
+    /// Stop scheduling builds on a node, running builds continue
+    pub async fn set_temporarily_offline(&self, node: &str, reason: &str) -> Result<()> {
+        if self.get_node(node).await?.temporarily_offline {
+            return Ok(());
+        }
+        self.toggle_offline(node, reason).await
+    }
+
+    /// Undo [`Jenkins::set_temporarily_offline`]
+    pub async fn bring_online(&self, node: &str) -> Result<()> {
+        if !self.get_node(node).await?.temporarily_offline {
+            return Ok(());
+        }
+        self.toggle_offline(node, "").await
+    }
+
+    /// Mark a node temporarily offline then wait until its running builds finished
+    ///
+    /// ## Arguments
+    ///
+    /// * `node` - node name
+    /// * `reason` - offline message shown in Jenkins
+    /// * `timeout` - give up waiting after this long, the node stays offline
+    ///
+    pub async fn drain_node(&self, node: &str, reason: &str, timeout: Duration) -> Result<()> {
+        self.set_temporarily_offline(node, reason).await?;
+        let start = Instant::now();
+        while !self.get_node(node).await?.idle {
+            if start.elapsed() > timeout {
+                bail!(Error::APIError(format!("timeout draining node {}", node)))
+            }
+            self.pause(Duration::from_secs(3)).await?;
+        }
+        info!("node drained - node={}", node);
+        Ok(())
+    }
+
+    /// Get the node monitor results of all nodes
+    pub async fn get_node_monitors(&self) -> Result<Vec<NodeMonitors>> {
+        let url = format!(
+            "{}/computer/api/json?tree=computer[displayName,offline,monitorData[*]]",
+            self.url
+        );
+        let res: ComputersRes = self.get_json(&url).await?;
+        Ok(res.computer)
+    }
+
+    /// Nodes crossing one of `thresholds`, with the problems found
+    pub async fn unhealthy_nodes(
+        &self,
+        thresholds: &HealthThresholds,
+    ) -> Result<Vec<(NodeMonitors, Vec<NodeProblem>)>> {
+        let nodes = self.get_node_monitors().await?;
+        let unhealthy: Vec<_> = nodes
+            .into_iter()
+            .map(|n| {
+                let problems = n.problems(thresholds);
+                (n, problems)
+            })
+            .filter(|(_, problems)| !problems.is_empty())
+            .collect();
+        info!("unhealthy nodes - count={}", unhealthy.len());
+        Ok(unhealthy)
+    }
+
+    /// List executors of a node with the builds they run
+    pub async fn get_executors(&self, node: &str) -> Result<Vec<ExecutorRes>> {
+        let url = format!(
+            "{}/computer/{}/api/json?tree=executors[number,idle,currentExecutable[number,url]],oneOffExecutors[number,idle,currentExecutable[number,url]]",
+            self.url, node
+        );
+        let res: ExecutorsRes = self.get_json(&url).await?;
+        Ok(res
+            .executors
+            .into_iter()
+            .chain(res.one_off_executors)
+            .collect())
+    }
+
+    /// Interrupt the build running on an executor, for builds whose own `/stop` hangs
+    ///
+    /// ## Arguments
+    ///
+    /// * `node` - node name
+    /// * `executor_index` - `number` of [`ExecutorRes`]
+    ///
+    pub async fn stop_executor(&self, node: &str, executor_index: i32) -> Result<()> {
+        self.post_manage(&format!(
+            "computer/{}/executors/{}/stop",
+            node, executor_index
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_monitor_problems() {
+        let res: ComputersRes = serde_json::from_str(
+            r#"{"computer":[
+                {"displayName":"agent-1","offline":false,"monitorData":{
+                    "hudson.node_monitors.DiskSpaceMonitor":{"_class":"hudson.node_monitors.DiskSpaceMonitorDescriptor$DiskSpace","timestamp":1,"path":"/home/jenkins","size":52428800},
+                    "hudson.node_monitors.TemporarySpaceMonitor":{"path":"/tmp","size":8589934592},
+                    "hudson.node_monitors.ResponseTimeMonitor":{"timestamp":1,"average":120},
+                    "hudson.node_monitors.ClockMonitor":{"diff":-15000},
+                    "hudson.node_monitors.SwapSpaceMonitor":null,
+                    "hudson.node_monitors.ArchitectureMonitor":"Linux (amd64)"}},
+                {"displayName":"agent-2","offline":true,"monitorData":{}}]}"#,
+        )
+        .unwrap();
+        let thresholds = HealthThresholds::default();
+        assert_eq!(
+            res.computer[0].problems(&thresholds),
+            [
+                NodeProblem::LowDiskSpace { free: 52428800 },
+                NodeProblem::ClockDifference(-15000)
+            ]
+        );
+        assert_eq!(
+            res.computer[0]
+                .monitor_data
+                .response_time
+                .as_ref()
+                .unwrap()
+                .average,
+            Duration::from_millis(120)
+        );
+        assert_eq!(
+            res.computer[1].problems(&thresholds),
+            [NodeProblem::Offline]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! [Notification plugin](https://plugins.jenkins.io/notification/) payloads
+//!
+//! Types for services receiving Jenkins build callbacks.
+
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Build phase reported by the notification plugin
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Phase {
+    Queued,
+    Started,
+    Completed,
+    Finalized,
+}
+
+/// Callback payload in the plugin's JSON format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotificationPayload {
+    /// job name
+    pub name: String,
+    pub display_name: Option<String>,
+    /// job url relative to the Jenkins root, e.g. `job/deploy/`
+    pub url: String,
+    pub build: NotificationBuild,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotificationBuild {
+    pub full_url: Option<String>,
+    pub number: i32,
+    pub queue_id: Option<i64>,
+    pub phase: Phase,
+    /// build result, missing while running
+    pub status: Option<String>,
+    /// build url relative to the Jenkins root
+    pub url: String,
+    pub timestamp: Option<i64>,
+    pub duration: Option<i64>,
+    pub scm: Option<NotificationScm>,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    /// tail of the build log when the endpoint is configured with log lines
+    pub log: Option<String>,
+    pub notes: Option<String>,
+    /// artifact name to archive and S3 urls
+    #[serde(default)]
+    pub artifacts: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotificationScm {
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub changes: Vec<String>,
+    #[serde(default)]
+    pub culprits: Vec<String>,
+}
+
+impl NotificationPayload {
+    pub fn parse(body: &[u8]) -> serde_json::Result<NotificationPayload> {
+        serde_json::from_slice(body)
+    }
+
+    /// Finished with a result, i.e. phase is `COMPLETED` or `FINALIZED`
+    pub fn is_finished(&self) -> bool {
+        matches!(self.build.phase, Phase::Completed | Phase::Finalized)
+    }
+}
+
+/// Verify a hex HMAC-SHA256 signature of a callback body
+///
+/// The plugin doesn't sign requests itself, this is for endpoints behind a
+/// signing relay, or pass the shared secret in the endpoint url and check it
+/// with [`constant_time_eq`].
+pub fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    constant_time_eq(
+        hmac_sha256_hex(secret, body).as_bytes(),
+        signature.to_ascii_lowercase().as_bytes(),
+    )
+}
+
+fn hmac_sha256_hex(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac takes keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Compare secrets without leaking the position of the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_payload() {
+        let body = br#"{"name":"deploy","url":"job/deploy/","build":{
+            "full_url":"http://jenkins/job/deploy/7/","number":7,"queue_id":12,
+            "phase":"COMPLETED","status":"FAILURE","url":"job/deploy/7/",
+            "scm":{"url":"git@example.com:o/r.git","branch":"origin/main","commit":"abc123"},
+            "parameters":{"ENV":"prod"},"log":"","artifacts":{}}}"#;
+        let payload = NotificationPayload::parse(body).unwrap();
+        assert!(payload.is_finished());
+        assert_eq!(payload.build.status.as_deref(), Some("FAILURE"));
+        assert_eq!(payload.build.parameters["ENV"], "prod");
+
+        let signature = hmac_sha256_hex(b"secret", body);
+        assert!(verify_signature(
+            b"secret",
+            body,
+            &format!("sha256={}", signature)
+        ));
+        assert!(!verify_signature(b"other", body, &signature));
+        // RFC 4231 test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! Typed job parameter definitions from `ParametersDefinitionProperty`
+//!
+//! Covers the core parameter types and common plugin types (Extended Choice,
+//! Active Choices, Git Parameter, Credentials Parameter). Anything else is kept
+//! as raw JSON in [`ParameterDefinition::Unknown`].
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use log::info;
+use reqwest::Url;
+use serde::{Deserialize, Deserializer};
+
+use crate::{Error, Jenkins};
+
+/// Parameter definition of a job, see [`Jenkins::get_parameter_definitions`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterDefinition {
+    String(ValueParameter<String>),
+    /// multi-line string
+    Text(ValueParameter<String>),
+    Boolean(ValueParameter<bool>),
+    Password(ValueParameter<String>),
+    Choice(ChoiceParameter),
+    File(ValueParameter<String>),
+    Run(RunParameter),
+    ExtendedChoice(ExtendedChoiceParameter),
+    ActiveChoice(ActiveChoiceParameter),
+    Git(GitParameter),
+    Credentials(CredentialsParameter),
+    /// type not modeled here, `raw` is the definition as returned by Jenkins
+    Unknown {
+        class: String,
+        name: Option<String>,
+        raw: serde_json::Value,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ValueParameter<T> {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "defaultParameterValue", default = "none")]
+    #[serde(deserialize_with = "default_value", bound = "T: Deserialize<'de>")]
+    pub default: Option<T>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ChoiceParameter {
+    pub name: String,
+    pub description: Option<String>,
+    pub choices: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunParameter {
+    pub name: String,
+    pub description: Option<String>,
+    pub project_name: String,
+    /// `ALL`, `STABLE`, `SUCCESSFUL` or `COMPLETED`
+    pub filter: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedChoiceParameter {
+    pub name: String,
+    pub description: Option<String>,
+    /// widget, e.g. `PT_SINGLE_SELECT`, `PT_CHECKBOX`, `PT_MULTI_SELECT`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// choices joined by `multi_select_delimiter`
+    pub value: Option<String>,
+    pub default_value: Option<String>,
+    pub multi_select_delimiter: Option<String>,
+}
+
+impl ExtendedChoiceParameter {
+    pub fn choices(&self) -> Vec<&str> {
+        let delimiter = self.multi_select_delimiter.as_deref().unwrap_or(",");
+        self.value
+            .as_deref()
+            .map(|v| v.split(delimiter).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Parameter class of the Active Choices plugin
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActiveChoiceKind {
+    /// `ChoiceParameter`
+    #[default]
+    Choice,
+    /// `CascadeChoiceParameter`, depends on `referenced_parameters`
+    Cascade,
+    /// `DynamicReferenceParameter`, renders html
+    DynamicReference,
+}
+
+impl ActiveChoiceKind {
+    pub fn class(&self) -> &'static str {
+        match self {
+            ActiveChoiceKind::Choice => "org.biouno.unochoice.ChoiceParameter",
+            ActiveChoiceKind::Cascade => "org.biouno.unochoice.CascadeChoiceParameter",
+            ActiveChoiceKind::DynamicReference => "org.biouno.unochoice.DynamicReferenceParameter",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveChoiceParameter {
+    #[serde(skip)]
+    pub kind: ActiveChoiceKind,
+    pub name: String,
+    pub description: Option<String>,
+    /// e.g. `PT_SINGLE_SELECT`, `PT_CHECKBOX`, `ET_FORMATTED_HTML`
+    pub choice_type: Option<String>,
+    #[serde(default, deserialize_with = "comma_list")]
+    pub referenced_parameters: Vec<String>,
+    /// id used by the plugin to tell parameters apart in the build form
+    pub random_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GitParameter {
+    pub name: String,
+    pub description: Option<String>,
+    /// `PT_BRANCH`, `PT_TAG`, `PT_BRANCH_TAG`, `PT_REVISION` or `PT_PULL_REQUEST`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub branch_filter: Option<String>,
+    pub tag_filter: Option<String>,
+    pub default_value: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialsParameter {
+    pub name: String,
+    pub description: Option<String>,
+    /// credentials class, e.g. `com.cloudbees.plugins.credentials.common.StandardCredentials`
+    pub credential_type: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// default credentials id
+    #[serde(rename = "defaultParameterValue", default = "none")]
+    #[serde(deserialize_with = "default_value")]
+    pub default: Option<String>,
+}
+
+fn none<T>() -> Option<T> {
+    None
+}
+
+/// Unwrap `defaultParameterValue: {"value": ...}`
+fn default_value<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    struct Wrapper<T> {
+        value: Option<T>,
+    }
+    Ok(Option::<Wrapper<T>>::deserialize(d)?.and_then(|w| w.value))
+}
+
+fn comma_list<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    let s = Option::<String>::deserialize(d)?.unwrap_or_default();
+    Ok(s.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+impl ParameterDefinition {
+    pub fn name(&self) -> Option<&str> {
+        Some(match self {
+            ParameterDefinition::String(p)
+            | ParameterDefinition::Text(p)
+            | ParameterDefinition::Password(p)
+            | ParameterDefinition::File(p) => &p.name,
+            ParameterDefinition::Boolean(p) => &p.name,
+            ParameterDefinition::Choice(p) => &p.name,
+            ParameterDefinition::Run(p) => &p.name,
+            ParameterDefinition::ExtendedChoice(p) => &p.name,
+            ParameterDefinition::ActiveChoice(p) => &p.name,
+            ParameterDefinition::Git(p) => &p.name,
+            ParameterDefinition::Credentials(p) => &p.name,
+            ParameterDefinition::Unknown { name, .. } => return name.as_deref(),
+        })
+    }
+
+    /// Parse a definition by its `_class`, falling back to [`ParameterDefinition::Unknown`]
+    pub fn from_json(raw: serde_json::Value) -> ParameterDefinition {
+        use serde_json::from_value;
+        use ParameterDefinition as P;
+
+        let class = raw["_class"].as_str().unwrap_or_default().to_owned();
+        let active_choice = |kind| {
+            from_value(raw.clone()).map(|p| P::ActiveChoice(ActiveChoiceParameter { kind, ..p }))
+        };
+        let parsed = match class.as_str() {
+            "hudson.model.StringParameterDefinition" => from_value(raw.clone()).map(P::String),
+            "hudson.model.TextParameterDefinition" => from_value(raw.clone()).map(P::Text),
+            "hudson.model.BooleanParameterDefinition" => from_value(raw.clone()).map(P::Boolean),
+            "hudson.model.PasswordParameterDefinition" => from_value(raw.clone()).map(P::Password),
+            "hudson.model.ChoiceParameterDefinition" => from_value(raw.clone()).map(P::Choice),
+            "hudson.model.FileParameterDefinition"
+            | "io.jenkins.plugins.file_parameters.StashedFileParameterDefinition"
+            | "io.jenkins.plugins.file_parameters.Base64FileParameterDefinition" => {
+                from_value(raw.clone()).map(P::File)
+            }
+            "hudson.model.RunParameterDefinition" => from_value(raw.clone()).map(P::Run),
+            "com.cwctravel.hudson.plugins.extended_choice_parameter.ExtendedChoiceParameterDefinition" => {
+                from_value(raw.clone()).map(P::ExtendedChoice)
+            }
+            "org.biouno.unochoice.ChoiceParameter" => active_choice(ActiveChoiceKind::Choice),
+            "org.biouno.unochoice.CascadeChoiceParameter" => {
+                active_choice(ActiveChoiceKind::Cascade)
+            }
+            "org.biouno.unochoice.DynamicReferenceParameter" => {
+                active_choice(ActiveChoiceKind::DynamicReference)
+            }
+            "net.uaznia.lukanus.hudson.plugins.gitparameter.GitParameterDefinition" => {
+                from_value(raw.clone()).map(P::Git)
+            }
+            "com.cloudbees.plugins.credentials.CredentialsParameterDefinition" => {
+                from_value(raw.clone()).map(P::Credentials)
+            }
+            _ => return ParameterDefinition::unknown(class, raw),
+        };
+        parsed.unwrap_or_else(|_| ParameterDefinition::unknown(class, raw))
+    }
+
+    fn unknown(class: String, raw: serde_json::Value) -> ParameterDefinition {
+        ParameterDefinition::Unknown {
+            class,
+            name: raw["name"].as_str().map(str::to_owned),
+            raw,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ParameterDefinition {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_json::Value::deserialize(d).map(ParameterDefinition::from_json)
+    }
+}
+
+/// Parameter value submitted in the `json` envelope of `POST /build`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildParameter {
+    pub name: String,
+    pub value: BuildParameterValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildParameterValue {
+    String(String),
+    Bool(bool),
+    /// credentials id of a credentials parameter
+    Credentials(String),
+    /// run parameter as `job#number`
+    Run(String),
+    /// file parameter, uploaded as `multipart/form-data`
+    File {
+        file_name: String,
+        content: Vec<u8>,
+    },
+}
+
+impl BuildParameter {
+    pub fn string(name: &str, value: &str) -> BuildParameter {
+        BuildParameter {
+            name: name.to_owned(),
+            value: BuildParameterValue::String(value.to_owned()),
+        }
+    }
+
+    pub fn bool(name: &str, value: bool) -> BuildParameter {
+        BuildParameter {
+            name: name.to_owned(),
+            value: BuildParameterValue::Bool(value),
+        }
+    }
+
+    pub fn credentials(name: &str, id: &str) -> BuildParameter {
+        BuildParameter {
+            name: name.to_owned(),
+            value: BuildParameterValue::Credentials(id.to_owned()),
+        }
+    }
+
+    pub fn run(name: &str, job: &str, number: i32) -> BuildParameter {
+        BuildParameter {
+            name: name.to_owned(),
+            value: BuildParameterValue::Run(format!("{}#{}", job, number)),
+        }
+    }
+
+    pub fn file(name: &str, file_name: &str, content: impl Into<Vec<u8>>) -> BuildParameter {
+        BuildParameter {
+            name: name.to_owned(),
+            value: BuildParameterValue::File {
+                file_name: file_name.to_owned(),
+                content: content.into(),
+            },
+        }
+    }
+
+    /// `{"parameter":[{"name":...,"value":...}]}` as posted by the build form
+    ///
+    /// File parameters refer to multipart fields `file0`, `file1`... in the
+    /// order returned by [`BuildParameter::files`].
+    pub fn envelope(params: &[BuildParameter]) -> serde_json::Value {
+        let mut files = 0;
+        let parameter: Vec<_> = params
+            .iter()
+            .map(|p| match &p.value {
+                BuildParameterValue::String(v) | BuildParameterValue::Credentials(v) => {
+                    serde_json::json!({ "name": p.name, "value": v })
+                }
+                BuildParameterValue::Bool(v) => serde_json::json!({ "name": p.name, "value": v }),
+                BuildParameterValue::Run(v) => serde_json::json!({ "name": p.name, "runId": v }),
+                BuildParameterValue::File { .. } => {
+                    files += 1;
+                    serde_json::json!({ "name": p.name, "file": format!("file{}", files - 1) })
+                }
+            })
+            .collect();
+        serde_json::json!({ "parameter": parameter })
+    }
+
+    /// Multipart field, file name and content of file parameters
+    pub fn files(params: &[BuildParameter]) -> Vec<(String, &str, &[u8])> {
+        params
+            .iter()
+            .filter_map(|p| match &p.value {
+                BuildParameterValue::File { file_name, content } => {
+                    Some((file_name.as_str(), content.as_slice()))
+                }
+                _ => None,
+            })
+            .enumerate()
+            .map(|(i, (file_name, content))| (format!("file{}", i), file_name, content))
+            .collect()
+    }
+}
+
+/// Choice resolved by [`Jenkins::evaluate_active_choice`]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceOption {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub selected: bool,
+}
+
+/// `ListBoxModel` returned by `fill*Items` endpoints
+#[derive(Deserialize, Debug)]
+struct ListBoxRes {
+    values: Vec<ChoiceOption>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JobPropertiesRes {
+    #[serde(default)]
+    property: Vec<JobPropertyRes>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JobPropertyRes {
+    #[serde(default)]
+    parameter_definitions: Vec<ParameterDefinition>,
+}
+
+impl Jenkins {
+    /// Get parameter definitions of a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn get_parameter_definitions(&self, job: &str) -> Result<Vec<ParameterDefinition>> {
+        let url = format!(
+            "{}/job/{}/api/json?tree=property[parameterDefinitions[*,defaultParameterValue[*]]]",
+            self.url, job
+        );
+        let res: JobPropertiesRes = self.get_json(&url).await?;
+        Ok(res
+            .property
+            .into_iter()
+            .flat_map(|p| p.parameter_definitions)
+            .collect())
+    }
+
+    /// Resolve choices of an Active Choices parameter through the plugin's
+    /// `fillValueItems` endpoint
+    ///
+    /// Values of the referenced parameters are passed as query parameters so
+    /// cascading choices can be resolved before triggering a build.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `param` - name of the Active Choices parameter
+    /// * `referenced_values` - values of parameters `param` depends on
+    ///
+    pub async fn evaluate_active_choice(
+        &self,
+        job: &str,
+        param: &str,
+        referenced_values: &HashMap<&str, &str>,
+    ) -> Result<Vec<ChoiceOption>> {
+        let definition = self
+            .get_parameter_definitions(job)
+            .await?
+            .into_iter()
+            .find_map(|d| match d {
+                ParameterDefinition::ActiveChoice(p) if p.name == param => Some(p),
+                _ => None,
+            });
+        let Some(definition) = definition else {
+            bail!(Error::APIError(format!(
+                "no active choices parameter {} in job {}",
+                param, job
+            )))
+        };
+        let mut url = Url::parse(&format!(
+            "{}/job/{}/descriptorByName/{}/fillValueItems",
+            self.url,
+            job,
+            definition.kind.class()
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("param", param)
+            .extend_pairs(referenced_values);
+        let res: ListBoxRes = self.get_json(url.as_str()).await?;
+        info!(
+            "evaluate active choice - job={}, param={}, choices={}",
+            job,
+            param,
+            res.values.len()
+        );
+        Ok(res.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_parameter_definitions() {
+        let res: JobPropertiesRes = serde_json::from_str(
+            r#"{"property":[{"_class":"hudson.model.ParametersDefinitionProperty","parameterDefinitions":[
+                {"_class":"hudson.model.BooleanParameterDefinition","name":"DRY_RUN",
+                 "defaultParameterValue":{"name":"DRY_RUN","value":true}},
+                {"_class":"hudson.model.ChoiceParameterDefinition","name":"ENV","choices":["dev","prod"]},
+                {"_class":"org.biouno.unochoice.CascadeChoiceParameter","name":"HOST",
+                 "choiceType":"PT_SINGLE_SELECT","referencedParameters":"ENV, REGION","randomName":"choice-parameter-1"},
+                {"_class":"com.cloudbees.plugins.credentials.CredentialsParameterDefinition","name":"CREDS",
+                 "credentialType":"com.cloudbees.plugins.credentials.common.StandardCredentials","required":true,
+                 "defaultParameterValue":{"value":"deploy-key"}},
+                {"_class":"com.example.FancyParameterDefinition","name":"FANCY","knob":3}
+            ]},{"_class":"jenkins.model.BuildDiscarderProperty"}]}"#,
+        )
+        .unwrap();
+        let defs: Vec<_> = res
+            .property
+            .into_iter()
+            .flat_map(|p| p.parameter_definitions)
+            .collect();
+        assert_eq!(defs.len(), 5);
+        assert!(matches!(&defs[0], ParameterDefinition::Boolean(p) if p.default == Some(true)));
+        assert!(matches!(&defs[1], ParameterDefinition::Choice(p) if p.choices.len() == 2));
+        match &defs[2] {
+            ParameterDefinition::ActiveChoice(p) => {
+                assert_eq!(p.kind, ActiveChoiceKind::Cascade);
+                assert_eq!(p.referenced_parameters, ["ENV", "REGION"]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(
+            matches!(&defs[3], ParameterDefinition::Credentials(p) if p.required && p.default.as_deref() == Some("deploy-key"))
+        );
+        assert!(matches!(&defs[4], ParameterDefinition::Unknown { raw, .. } if raw["knob"] == 3));
+        assert_eq!(defs[4].name(), Some("FANCY"));
+    }
+
+    #[test]
+    fn build_parameter_envelope() {
+        let envelope = BuildParameter::envelope(&[
+            BuildParameter::string("ENV", "prod"),
+            BuildParameter::bool("DRY_RUN", false),
+            BuildParameter::credentials("CREDS", "deploy-key"),
+            BuildParameter::run("UPSTREAM", "build", 12),
+            BuildParameter::file("CONFIG", "app.yaml", b"a: 1".as_slice()),
+        ]);
+        assert_eq!(
+            envelope,
+            serde_json::json!({"parameter": [
+                {"name": "ENV", "value": "prod"},
+                {"name": "DRY_RUN", "value": false},
+                {"name": "CREDS", "value": "deploy-key"},
+                {"name": "UPSTREAM", "runId": "build#12"},
+                {"name": "CONFIG", "file": "file0"},
+            ]})
+        );
+    }
+}
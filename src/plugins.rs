@@ -0,0 +1,287 @@
+//! APIs of optional plugins: ThinBackup, Audit Trail, Role-based Authorization
+//! Strategy and Disk Usage, enabled by the `plugins-ext` feature
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Jenkins};
+
+const BACKUP_STATUS_SCRIPT: &str = r#"
+def cls = org.jvnet.hudson.plugins.thinbackup.ThinBackupPluginImpl
+def plugin = cls.metaClass.respondsTo(cls, 'get') ? cls.get() : cls.getInstance()
+def dir = new File(plugin.backupPath)
+def backups = (dir.listFiles() ?: []).findAll { it.name ==~ /(FULL|DIFF)-.*/ }
+println groovy.json.JsonOutput.toJson([
+    backupPath: plugin.backupPath,
+    backups: backups.collect { [name: it.name, full: it.name.startsWith('FULL'), lastModified: it.lastModified()] },
+])
+"#;
+
+/// See [`Jenkins::get_backup_status`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatus {
+    pub backup_path: String,
+    pub backups: Vec<BackupSet>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSet {
+    /// e.g. `FULL-2024-01-31_02-00`
+    pub name: String,
+    /// `false` for differential backups
+    pub full: bool,
+    /// epoch millis
+    pub last_modified: i64,
+}
+
+/// Entry of audit-trail log, see [`Jenkins::get_audit_entries`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// as formatted by the logger in controller's timezone, e.g. `Jan 31, 2024 2:00:00 PM`
+    pub timestamp: String,
+    /// request uri or build event, e.g. `/job/deploy/configSubmit`
+    pub action: String,
+    pub user: String,
+    pub ip: Option<String>,
+}
+
+impl AuditEntry {
+    /// Parse a line of the default format `<timestamp> <action> by <user>[ from <ip>]`
+    pub fn parse(line: &str) -> Option<AuditEntry> {
+        // default timestamp format has 5 tokens: `Jan 31, 2024 2:00:00 PM`
+        let mut split = line.splitn(6, ' ');
+        let timestamp = split.by_ref().take(5).collect::<Vec<_>>().join(" ");
+        let rest = split.next()?;
+        let (rest, ip) = match rest.rsplit_once(" from ") {
+            Some((rest, ip)) if !ip.contains(' ') => (rest, Some(ip.to_owned())),
+            _ => (rest, None),
+        };
+        let (action, user) = rest.rsplit_once(" by ")?;
+        Some(AuditEntry {
+            timestamp,
+            action: action.to_owned(),
+            user: user.to_owned(),
+            ip,
+        })
+    }
+}
+
+/// Role type of role-strategy plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleType {
+    Global,
+    /// item roles matched by pattern
+    Project,
+    /// agent roles matched by pattern
+    Slave,
+}
+
+impl RoleType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RoleType::Global => "globalRoles",
+            RoleType::Project => "projectRoles",
+            RoleType::Slave => "slaveRoles",
+        }
+    }
+}
+
+/// Plain sids in older role-strategy versions, `{"type":"USER","sid":"x"}` in newer
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum RoleSid {
+    Plain(String),
+    Typed { sid: String },
+}
+
+/// See [`Jenkins::get_disk_usage`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiskUsageRes {
+    #[serde(default)]
+    pub directories: Vec<DiskUsageItem>,
+    #[serde(default)]
+    pub jobs: Vec<JobDiskUsage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsageItem {
+    pub display_name: Option<String>,
+    pub path: String,
+    /// KiB, `-1` when not computed yet
+    pub usage: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobDiskUsage {
+    pub full_name: String,
+    pub url: Option<String>,
+    pub path: String,
+    /// KiB, `-1` when not computed yet
+    pub usage: i64,
+}
+
+impl Jenkins {
+    /// Get disk usage of jobs and controller directories, needs cloudbees-disk-usage-simple plugin
+    pub async fn get_disk_usage(&self) -> Result<DiskUsageRes> {
+        let url = format!("{}/cloudbees-disk-usage-simple/api/json", self.url);
+        self.get_json(&url).await
+    }
+
+    /// Start a manual backup with the thinBackup plugin
+    pub async fn trigger_backup(&self) -> Result<()> {
+        self.post_manage("thinBackup/backupManually").await
+    }
+
+    /// Get thinBackup backup directory and existing backup sets, newest first
+    pub async fn get_backup_status(&self) -> Result<BackupStatus> {
+        let out = self.run_script(BACKUP_STATUS_SCRIPT).await?;
+        let mut status: BackupStatus = serde_json::from_str(out.trim())
+            .with_context(|| format!("parse backup status: {}", out))?;
+        status
+            .backups
+            .sort_by_key(|b| std::cmp::Reverse(b.last_modified));
+        Ok(status)
+    }
+
+    /// Get the last lines written by the audit-trail plugin's log file logger
+    ///
+    /// ## Arguments
+    ///
+    /// * `max_lines` - max number of lines from the end of the current log file
+    ///
+    pub async fn get_audit_log(&self, max_lines: usize) -> Result<String> {
+        let script = format!(
+            r#"
+def plugin = jenkins.model.GlobalConfiguration.all().get(hudson.plugins.audit_trail.AuditTrailPlugin)
+def logger = plugin.loggers.find {{ it instanceof hudson.plugins.audit_trail.LogFileAuditLogger }}
+if (logger == null) {{ throw new IllegalStateException('audit-trail log file logger not configured') }}
+def lines = new File(logger.log.replace('%g', '0')).readLines()
+print lines.takeRight({max_lines}).join('\n')
+"#
+        );
+        self.run_script(&script).await
+    }
+
+    /// Get parsed audit-trail entries, unparseable lines are skipped
+    ///
+    /// ## Arguments
+    ///
+    /// * `max_lines` - max number of lines from the end of the current log file
+    ///
+    pub async fn get_audit_entries(&self, max_lines: usize) -> Result<Vec<AuditEntry>> {
+        let log = self.get_audit_log(max_lines).await?;
+        Ok(log.lines().filter_map(AuditEntry::parse).collect())
+    }
+
+    async fn post_role_strategy(&self, action: &str, form: &[(&str, &str)]) -> Result<()> {
+        let url = format!("{}/role-strategy/strategy/{}", self.url, action);
+        let res = self.send(self.post(&url).form(form)).await?;
+        if !res.status().is_success() {
+            warn!("role-strategy {} - form={:?}, res={:?}", action, form, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!("role-strategy {} - form={:?}", action, form);
+        Ok(())
+    }
+
+    /// List roles of a type with the sids assigned to them
+    pub async fn list_roles(&self, role_type: RoleType) -> Result<BTreeMap<String, Vec<String>>> {
+        let url = format!(
+            "{}/role-strategy/strategy/getAllRoles?type={}",
+            self.url,
+            role_type.as_str()
+        );
+        let roles: BTreeMap<String, Vec<RoleSid>> = self.get_json(&url).await?;
+        Ok(roles
+            .into_iter()
+            .map(|(role, sids)| {
+                let sids = sids
+                    .into_iter()
+                    .map(|s| match s {
+                        RoleSid::Plain(sid) => sid,
+                        RoleSid::Typed { sid } => sid,
+                    })
+                    .collect();
+                (role, sids)
+            })
+            .collect())
+    }
+
+    /// Create or overwrite a role
+    ///
+    /// ## Arguments
+    ///
+    /// * `permissions` - permission ids, e.g. `hudson.model.Item.Build`
+    /// * `pattern` - item/agent name regex, ignored by global roles
+    ///
+    pub async fn add_role(
+        &self,
+        role_type: RoleType,
+        role: &str,
+        permissions: &[&str],
+        pattern: Option<&str>,
+    ) -> Result<()> {
+        let permissions = permissions.join(",");
+        let mut form = vec![
+            ("type", role_type.as_str()),
+            ("roleName", role),
+            ("permissionIds", permissions.as_str()),
+            ("overwrite", "true"),
+        ];
+        if let Some(pattern) = pattern {
+            form.push(("pattern", pattern));
+        }
+        self.post_role_strategy("addRole", &form).await
+    }
+
+    /// Assign a role to a user or group
+    pub async fn assign_role(&self, role_type: RoleType, role: &str, sid: &str) -> Result<()> {
+        self.post_role_strategy(
+            "assignRole",
+            &[
+                ("type", role_type.as_str()),
+                ("roleName", role),
+                ("sid", sid),
+            ],
+        )
+        .await
+    }
+
+    /// Remove a role from a user or group
+    pub async fn unassign_role(&self, role_type: RoleType, role: &str, sid: &str) -> Result<()> {
+        self.post_role_strategy(
+            "unassignRole",
+            &[
+                ("type", role_type.as_str()),
+                ("roleName", role),
+                ("sid", sid),
+            ],
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_audit_entry() {
+        let entry = AuditEntry::parse(
+            "Jan 31, 2024 2:00:00 PM /job/deploy/configSubmit by alice from 10.0.0.1",
+        )
+        .unwrap();
+        assert_eq!(entry.timestamp, "Jan 31, 2024 2:00:00 PM");
+        assert_eq!(entry.action, "/job/deploy/configSubmit");
+        assert_eq!(entry.user, "alice");
+        assert_eq!(entry.ip.as_deref(), Some("10.0.0.1"));
+        assert!(AuditEntry::parse("garbage").is_none());
+    }
+}
@@ -0,0 +1,6 @@
+//! Commonly used types, `use jenkins_rs::prelude::*;`
+
+pub use crate::{
+    BuildHandle, BuildRes, Error, Jenkins, JenkinsBuilder, JobRes, QueueItemHandle, QueueItemRes,
+    QueueItemUrl, RequestOptions,
+};
@@ -0,0 +1,1295 @@
+//! Triggering builds and following them through the build queue
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{error, info, trace, warn};
+use reqwest::{RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "extras")]
+use crate::Extras;
+use crate::{
+    client::FETCH_CONCURRENCY,
+    job::full_name,
+    join::{try_join_all, BoxFuture},
+    label::LabelExpr,
+    model::from_epoch_millis,
+    parameters, BuildCause, BuildHistoryRes, BuildRes, DependencyGraph, Error, Jenkins, Multipart,
+    QueueItem, QueueItemExecutable, QueueItemRes, QueueTask, DRY_RUN_QUEUE_ITEM_ID,
+};
+
+/// Serializable handle of a queued build, see [`Jenkins::resume`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueueItemHandle {
+    pub job: String,
+    /// `location` field in `build`/`buildWithParameters` response header
+    pub queue_item_url: QueueItemUrl,
+}
+
+/// Number of recent builds searched by [`Jenkins::find_build_by_marker`]
+const MARKER_SCAN_BUILDS: usize = 50;
+
+static MARKER_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Unique value identifying one trigger, see [`Jenkins::find_build_by_marker`]
+pub fn new_trigger_marker() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        MARKER_SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Where a marked trigger ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkedTrigger {
+    Queued(QueueItemUrl),
+    Started(BuildHandle),
+}
+
+/// Validated url of a queue item, e.g. `https://host/jenkins/queue/item/42/`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueueItemUrl {
+    url: Url,
+    id: u64,
+}
+
+impl QueueItemUrl {
+    /// Parse the `location` header of a build trigger response
+    ///
+    /// Relative locations are resolved against `base`. Absolute ones pointing
+    /// to another origin, e.g. the internal http address seen by Jenkins behind
+    /// a TLS proxy, are rewritten onto `base`.
+    pub fn from_location(base: &str, location: &str) -> Result<QueueItemUrl> {
+        let base = Url::parse(&format!("{}/", base.trim_end_matches('/')))?;
+        // `/jenkins/queue/item/1/` is server absolute, including the context path
+        let url = Url::parse(location).or_else(|_| base.join(location))?;
+        let item = QueueItemUrl::new(url)?;
+        if item.url.origin() == base.origin() {
+            return Ok(item);
+        }
+        QueueItemUrl::new(base.join(&format!("queue/item/{}/", item.id))?)
+    }
+
+    fn new(mut url: Url) -> Result<QueueItemUrl> {
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+        url.set_query(None);
+        let segments: Vec<&str> = url.path_segments().map_or(Vec::new(), |s| s.collect());
+        let id = match segments.as_slice() {
+            [.., "queue", "item", id, ""] => id.parse().ok(),
+            _ => None,
+        };
+        let Some(id) = id else {
+            bail!(Error::APIError(format!("not a queue item url: {}", url)))
+        };
+        Ok(QueueItemUrl { url, id })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.url.as_str()
+    }
+
+    fn api_url(&self) -> String {
+        format!("{}api/json", self.url)
+    }
+}
+
+impl fmt::Display for QueueItemUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.url.as_str())
+    }
+}
+
+impl FromStr for QueueItemUrl {
+    type Err = anyhow::Error;
+
+    /// Parse an absolute queue item url
+    fn from_str(s: &str) -> Result<Self> {
+        QueueItemUrl::new(Url::parse(s)?)
+    }
+}
+
+impl Serialize for QueueItemUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for QueueItemUrl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializable handle of a started build, see [`Jenkins::wait_build`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BuildHandle {
+    pub job: String,
+    pub number: i32,
+    pub url: String,
+}
+
+/// Decides whether a failed build is retried, gets the build and its console log
+pub type RetryPredicate = Box<dyn Fn(&BuildRes, &str) -> bool + Send + Sync>;
+
+/// Policy of [`Jenkins::run_build_with_retry`]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// delay before the first retry
+    pub backoff: Duration,
+    /// multiplier applied to the delay after each retry
+    pub backoff_factor: f64,
+    /// retry every unsuccessful build if `None`
+    pub retry_if: Option<RetryPredicate>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_secs(30),
+            backoff_factor: 2.0,
+            retry_if: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry only builds finished with one of `results`, e.g. `["ABORTED"]`
+    pub fn on_results(results: &[&str]) -> RetryPolicy {
+        let results: Vec<String> = results.iter().map(|r| (*r).to_owned()).collect();
+        RetryPolicy {
+            retry_if: Some(Box::new(move |build, _| {
+                build.result.as_ref().is_some_and(|r| results.contains(r))
+            })),
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Retry only builds whose console log contains one of `patterns`,
+    /// e.g. infrastructure errors like `ChannelClosedException`
+    pub fn on_log_contains(patterns: &[&str]) -> RetryPolicy {
+        let patterns: Vec<String> = patterns.iter().map(|p| (*p).to_owned()).collect();
+        RetryPolicy {
+            retry_if: Some(Box::new(move |_, log| {
+                patterns.iter().any(|p| log.contains(p.as_str()))
+            })),
+            ..RetryPolicy::default()
+        }
+    }
+}
+
+/// Build of a cascade and the downstream builds it triggered, see [`Jenkins::run_cascade`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CascadeNode {
+    /// full job name, e.g. `folder/job`
+    pub job: String,
+    pub build: BuildRes,
+    pub downstream: Vec<CascadeNode>,
+}
+
+impl CascadeNode {
+    /// This build and every downstream build succeeded
+    pub fn succeeded(&self) -> bool {
+        self.build.result.as_deref() == Some("SUCCESS")
+            && self.downstream.iter().all(CascadeNode::succeeded)
+    }
+
+    /// This build and its downstream builds, depth first
+    pub fn iter(&self) -> impl Iterator<Item = &CascadeNode> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.downstream.iter().rev());
+            Some(node)
+        })
+    }
+}
+
+/// Recent builds of a downstream job searched for the upstream cause
+const CASCADE_SCAN_BUILDS: usize = 20;
+
+/// How long a downstream build may take to leave the queue after its upstream finished
+const CASCADE_START_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct QueueRes {
+    pub(crate) items: Vec<QueueItem>,
+}
+
+/// Queue items with their causes, for [`Jenkins::find_build_by_marker`] and
+/// [`Jenkins::run_cascade`]
+#[derive(Deserialize, Debug)]
+struct QueueCausesRes {
+    items: Vec<QueueCausesItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueueCausesItem {
+    id: i64,
+    task: QueueTask,
+    #[serde(default)]
+    params: String,
+    #[serde(default)]
+    actions: Vec<QueueCausesAction>,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueueCausesAction {
+    #[serde(default)]
+    causes: Vec<BuildCause>,
+}
+
+impl QueueCausesItem {
+    /// Queued by build `number` of the upstream job `job`
+    fn triggered_by(&self, job: &str, number: i32) -> bool {
+        self.actions
+            .iter()
+            .flat_map(|a| &a.causes)
+            .any(|c| c.upstream_project.as_deref() == Some(job) && c.upstream_build == Some(number))
+    }
+
+    /// `marker` passed as a parameter value or as `cause` text, like
+    /// [`BuildRes`] in the build history
+    fn has_marker(&self, marker: &str) -> bool {
+        self.params
+            .lines()
+            .any(|l| l.split_once('=').is_some_and(|(_, v)| v == marker))
+            || self
+                .actions
+                .iter()
+                .flat_map(|a| &a.causes)
+                .any(|c| c.note.as_deref() == Some(marker))
+    }
+}
+
+/// Why a queue item is waiting, classified from its `why` text
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlockReason {
+    QuietPeriod,
+    /// another build of the job is running and concurrent builds are disabled
+    Concurrency,
+    /// upstream or downstream project is building
+    Dependency,
+    /// all executors are busy, for the label if known
+    WaitingForExecutor(Option<String>),
+    /// no online node has the label
+    NoNodeForLabel(String),
+    /// the node or all nodes of the label are offline
+    Offline(String),
+    Other(String),
+}
+
+impl BlockReason {
+    pub fn classify(why: &str) -> BlockReason {
+        // jenkins quotes names with ‘’
+        let quoted = || {
+            why.split_once(['‘', '\''])
+                .and_then(|(_, rest)| rest.split_once(['’', '\'']))
+                .map(|(name, _)| name.to_owned())
+        };
+        if why.starts_with("In the quiet period") {
+            BlockReason::QuietPeriod
+        } else if why.contains("already in progress") {
+            BlockReason::Concurrency
+        } else if why.contains("project") && why.contains("is already building") {
+            BlockReason::Dependency
+        } else if why.starts_with("Waiting for next available executor") {
+            BlockReason::WaitingForExecutor(quoted())
+        } else if why.starts_with("There are no nodes with the label") {
+            BlockReason::NoNodeForLabel(quoted().unwrap_or_default())
+        } else if why.contains("offline") {
+            BlockReason::Offline(quoted().unwrap_or_default())
+        } else {
+            BlockReason::Other(why.to_owned())
+        }
+    }
+
+    /// Label or node the item waits for
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            BlockReason::WaitingForExecutor(label) => label.as_deref(),
+            BlockReason::NoNodeForLabel(label) | BlockReason::Offline(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Variant name used as key of [`QueueAnalysis::by_reason`]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BlockReason::QuietPeriod => "QuietPeriod",
+            BlockReason::Concurrency => "Concurrency",
+            BlockReason::Dependency => "Dependency",
+            BlockReason::WaitingForExecutor(_) => "WaitingForExecutor",
+            BlockReason::NoNodeForLabel(_) => "NoNodeForLabel",
+            BlockReason::Offline(_) => "Offline",
+            BlockReason::Other(_) => "Other",
+        }
+    }
+}
+
+/// See [`Jenkins::analyze_queue`]
+#[derive(Debug, Clone)]
+pub struct QueueAnalysis {
+    pub items: Vec<(QueueItem, BlockReason)>,
+    /// item count by [`BlockReason::kind`]
+    pub by_reason: BTreeMap<&'static str, usize>,
+    /// item count by label or node waited for
+    pub by_label: BTreeMap<String, usize>,
+    pub stuck: usize,
+}
+
+impl QueueAnalysis {
+    pub fn new(items: Vec<QueueItem>) -> QueueAnalysis {
+        let mut analysis = QueueAnalysis {
+            items: Vec::with_capacity(items.len()),
+            by_reason: BTreeMap::new(),
+            by_label: BTreeMap::new(),
+            stuck: 0,
+        };
+        for item in items {
+            let reason = item.block_reason();
+            *analysis.by_reason.entry(reason.kind()).or_default() += 1;
+            if let Some(label) = reason.label() {
+                *analysis.by_label.entry(label.to_owned()).or_default() += 1;
+            }
+            if item.stuck {
+                analysis.stuck += 1;
+            }
+            analysis.items.push((item, reason));
+        }
+        analysis
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CapacityRes {
+    computer: Vec<CapacityNode>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CapacityNode {
+    offline: bool,
+    #[serde(default)]
+    assigned_labels: Vec<CapacityLabel>,
+    #[serde(default)]
+    executors: Vec<CapacityExecutor>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CapacityLabel {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CapacityExecutor {
+    current_executable: Option<RunningBuild>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RunningBuild {
+    timestamp: i64,
+    /// `-1` without a successful build to estimate from
+    estimated_duration: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LastDurationRes {
+    last_successful_build: Option<LastDuration>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastDuration {
+    #[serde(with = "crate::model::duration_millis")]
+    duration: Duration,
+}
+
+/// Wait until an executor is free after the builds `ahead` started
+///
+/// `free_in` holds when each executor becomes idle, builds are handed the
+/// earliest free executor in order. `None` without executors.
+fn simulate_start(free_in: &[Duration], ahead: &[Duration]) -> Option<Duration> {
+    let mut executors: BinaryHeap<_> = free_in.iter().copied().map(Reverse).collect();
+    for duration in ahead {
+        let Reverse(free) = executors.pop()?;
+        executors.push(Reverse(free + *duration));
+    }
+    executors.peek().map(|Reverse(free)| *free)
+}
+
+impl Jenkins {
+    /// Poll from new build queue item url until build number available
+    ///
+    /// [reference](https://docs.cloudbees.com/docs/cloudbees-ci-kb/latest/client-and-managed-controllers/get-build-number-with-rest-api)
+    ///
+    /// ## Arguments
+    ///
+    /// * `queue_item_url` - parsed `location` header of a `build`/`buildWithParameters` response
+    ///
+    pub async fn poll_queue_item(
+        &self,
+        queue_item_url: &QueueItemUrl,
+    ) -> Result<QueueItemRes, anyhow::Error> {
+        if self.dry_run && queue_item_url.id() == DRY_RUN_QUEUE_ITEM_ID {
+            info!("dry-run poll {}", queue_item_url);
+            return Ok(QueueItemRes {
+                why: None,
+                executable: Some(QueueItemExecutable {
+                    number: 0,
+                    url: queue_item_url.to_string(),
+                }),
+                #[cfg(feature = "extras")]
+                extra: Extras::new(),
+            });
+        }
+        let queue_url = queue_item_url.api_url();
+        loop {
+            self.pause(Duration::from_secs(3)).await?;
+            match self.send(self.get(&queue_url)).await {
+                Ok(queue_res) => {
+                    info!("queue_res={:?}", queue_res);
+                    if queue_res.status().is_client_error() {
+                        bail!(Error::QueueItemNotExists)
+                    }
+                    let qi_res: QueueItemRes = self
+                        .json_body(queue_res)
+                        .await
+                        .context("parse queue item payload as json")?;
+                    if qi_res.executable.is_some() {
+                        info!("Get {}: body={:?}", queue_url, qi_res);
+                        return Ok(qi_res);
+                    } else {
+                        trace!("Get {}: body={:?}", queue_url, qi_res);
+                    }
+                }
+                Err(err) => {
+                    error!("Get {}: err={:?}", queue_url, err);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// [Parameterized Build](https://wiki.jenkins.io/display/JENKINS/Parameterized-Build.html)
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - parameters to trigger a build
+    ///
+    pub async fn build_with_parameter(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<QueueItemRes> {
+        let handle = self.queue_build_with_parameter(job, params).await?;
+        self.resume(&handle).await
+    }
+
+    /// Trigger a parameterized build without waiting for it to leave the queue
+    ///
+    /// The returned handle can be persisted and passed to [`Jenkins::resume`] later,
+    /// e.g. after a process restart.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - parameters to trigger a build
+    ///
+    pub async fn queue_build_with_parameter(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<QueueItemHandle> {
+        let url = format!("{}/job/{}/buildWithParameters", self.url, job);
+        self.trigger(job, "buildWithParameters", self.post(&url).form(&params))
+            .await
+    }
+
+    /// Trigger a parameterized build recording `cause` as the reason
+    ///
+    /// Jenkins records the note in a `RemoteCause` ("Started by remote host ...
+    /// with note: ...") only for triggers with the job's authentication token,
+    /// see [`BuildRes::causes`] to read it back.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - parameters to trigger a build
+    /// * `token` - "Trigger builds remotely" authentication token of the job
+    /// * `cause` - free text, e.g. `deploy-bot: release 1.4.2`
+    ///
+    pub async fn queue_build_with_cause(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+        token: &str,
+        cause: &str,
+    ) -> Result<QueueItemHandle> {
+        let url = format!("{}/job/{}/buildWithParameters", self.url, job);
+        let req = self
+            .post(&url)
+            .query(&[("token", token), ("cause", cause)])
+            .form(&params);
+        self.trigger(job, "buildWithParameters", req).await
+    }
+
+    /// Trigger a build with credentials parameters
+    ///
+    /// Credentials parameters are submitted through `POST /build` with the
+    /// `json={"parameter":[...]}` envelope the build form uses, so the credential
+    /// ids get bound the same way as when picked in the UI.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - plain string parameters
+    /// * `credentials` - credentials parameter name to credential id
+    ///
+    pub async fn build_with_credentials(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+        credentials: HashMap<&str, &str>,
+    ) -> Result<QueueItemHandle> {
+        let params: Vec<parameters::BuildParameter> = params
+            .into_iter()
+            .map(|(name, value)| parameters::BuildParameter::string(name, value))
+            .chain(
+                credentials
+                    .into_iter()
+                    .map(|(name, id)| parameters::BuildParameter::credentials(name, id)),
+            )
+            .collect();
+        self.build_with_json_parameters(job, &params).await
+    }
+
+    /// Trigger a build through `POST /build` with the `json={"parameter":[...]}` envelope
+    ///
+    /// Unlike `buildWithParameters` this can express run and file parameters,
+    /// file parameters are uploaded as `multipart/form-data`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - parameters to trigger a build
+    ///
+    pub async fn build_with_json_parameters(
+        &self,
+        job: &str,
+        params: &[parameters::BuildParameter],
+    ) -> Result<QueueItemHandle> {
+        let url = format!("{}/job/{}/build", self.url, job);
+        let json = parameters::BuildParameter::envelope(params).to_string();
+        let files = parameters::BuildParameter::files(params);
+        let req = if files.is_empty() {
+            self.post(&url).form(&[("json", json.as_str())])
+        } else {
+            let mut form = Multipart::new();
+            form.text("json", &json);
+            for (field, file_name, content) in &files {
+                form.file(field, file_name, content);
+            }
+            form.apply(self.post(&url))
+        };
+        self.trigger(job, "build", req).await
+    }
+
+    /// Send a build trigger request and take the queue item from its `location` header
+    pub(crate) async fn trigger(
+        &self,
+        job: &str,
+        action: &str,
+        req: RequestBuilder,
+    ) -> Result<QueueItemHandle> {
+        #[cfg(feature = "otel")]
+        let req = self.inject_trace_context(action, req);
+        match self.send(req).await {
+            Ok(res) => {
+                if res.status().is_success() {
+                    info!("{} - job={}, res={:?}", action, job, res);
+                    if let Some(location) = res.headers().get("location") {
+                        let location = location.to_str().map_err(|_| {
+                            Error::APIError(format!("invalid location header: {:?}", location))
+                        })?;
+                        Ok(QueueItemHandle {
+                            job: job.to_owned(),
+                            queue_item_url: QueueItemUrl::from_location(&self.url, location)?,
+                        })
+                    } else {
+                        bail!(Error::APIError("location header not available".to_owned()))
+                    }
+                } else {
+                    warn!("{} - job={}, res={:?}", action, job, res);
+                    bail!(Error::APIError(format!("http status: {}", res.status())))
+                }
+            }
+            Err(err) => {
+                error!("{} - job={}, err={:?}", action, job, err);
+                bail!(err)
+            }
+        }
+    }
+
+    /// Resume waiting on a persisted queue item until its build number is available
+    pub async fn resume(&self, handle: &QueueItemHandle) -> Result<QueueItemRes> {
+        self.poll_queue_item(&handle.queue_item_url).await
+    }
+
+    /// List items in the build queue
+    pub async fn get_queue(&self) -> Result<Vec<QueueItem>> {
+        let url = format!(
+            "{}/queue/api/json?tree=items[id,why,blocked,buildable,stuck,inQueueSince,params,task[name,url]]",
+            self.url
+        );
+        let res: QueueRes = self.get_json(&url).await?;
+        Ok(res.items)
+    }
+
+    /// Classify queued items by why they are waiting
+    pub async fn analyze_queue(&self) -> Result<QueueAnalysis> {
+        Ok(QueueAnalysis::new(self.get_queue().await?))
+    }
+
+    /// Predict when a queued item starts, to decide between triggering now or later
+    ///
+    /// Simulates the online executors able to run the item: busy ones free up
+    /// at the estimated end of their build, overdue or never estimated builds
+    /// count as ending now. Buildable items queued earlier for the same label
+    /// take the free executors first, for the duration of their job's last
+    /// successful build. Quiet periods and blocked jobs are not accounted for.
+    ///
+    /// Returns `None` if no online node matches the item's label.
+    pub async fn estimate_start_time(&self, item: &QueueItem) -> Result<Option<SystemTime>> {
+        let label = item.block_reason().label().map(str::to_owned);
+        let expr = label
+            .as_deref()
+            .map(|l| l.parse().unwrap_or_else(|_| LabelExpr::Label(l.to_owned())));
+        let url = format!(
+            "{}/computer/api/json?tree=computer[offline,assignedLabels[name],executors[currentExecutable[timestamp,estimatedDuration]]]",
+            self.url
+        );
+        let (capacity, queue) =
+            tokio::try_join!(self.get_json::<CapacityRes>(&url), self.get_queue())?;
+
+        let now = SystemTime::now();
+        let mut free_in = Vec::new();
+        for node in capacity.computer.iter().filter(|n| !n.offline) {
+            let labels: Vec<&str> = node
+                .assigned_labels
+                .iter()
+                .map(|l| l.name.as_str())
+                .collect();
+            if expr.as_ref().is_some_and(|e| !e.matches(&labels)) {
+                continue;
+            }
+            free_in.extend(node.executors.iter().map(|e| match &e.current_executable {
+                Some(b) if b.estimated_duration > 0 => {
+                    let end = from_epoch_millis(b.timestamp + b.estimated_duration);
+                    end.duration_since(now).unwrap_or_default()
+                }
+                _ => Duration::ZERO,
+            }));
+        }
+
+        let ahead: Vec<&QueueItem> = queue
+            .iter()
+            .filter(|other| {
+                other.id != item.id
+                    && other.buildable
+                    && other.in_queue_since < item.in_queue_since
+                    && other.block_reason().label() == label.as_deref()
+            })
+            .collect();
+        let mut urls: Vec<&str> = ahead
+            .iter()
+            .filter_map(|other| other.task.url.as_deref())
+            .collect();
+        urls.sort_unstable();
+        urls.dedup();
+        let last: Vec<LastDurationRes> = self
+            .fetch_many(&urls, "lastSuccessfulBuild[duration]", FETCH_CONCURRENCY)
+            .await?;
+        let last: HashMap<&str, Duration> = urls
+            .into_iter()
+            .zip(last)
+            .map(|(url, res)| {
+                let duration = res
+                    .last_successful_build
+                    .map_or(Duration::ZERO, |b| b.duration);
+                (url, duration)
+            })
+            .collect();
+        let durations: Vec<Duration> = ahead
+            .iter()
+            .map(|other| {
+                other
+                    .task
+                    .url
+                    .as_deref()
+                    .and_then(|url| last.get(url).copied())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let start = simulate_start(&free_in, &durations).map(|wait| now + wait);
+        info!(
+            "estimate start - id={}, label={:?}, executors={}, ahead={}, wait={:?}",
+            item.id,
+            label,
+            free_in.len(),
+            ahead.len(),
+            start.map(|s| s.duration_since(now).unwrap_or_default())
+        );
+        Ok(start)
+    }
+
+    /// Trigger a build and wait for it, re-triggering with the same parameters on failure
+    ///
+    /// Returns the last build, check its `result` for whether a retry succeeded.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `params` - parameters to trigger a build
+    /// * `policy` - when and how often to retry
+    ///
+    pub async fn run_build_with_retry(
+        &self,
+        job: &str,
+        params: HashMap<&str, &str>,
+        policy: &RetryPolicy,
+    ) -> Result<BuildRes> {
+        let mut backoff = policy.backoff;
+        let mut attempt = 0;
+        loop {
+            let queue_item = self.build_with_parameter(job, params.clone()).await?;
+            let Some(handle) = queue_item.build_handle(job) else {
+                bail!(Error::APIError(format!(
+                    "queue item left without build: {:?}",
+                    queue_item.why
+                )))
+            };
+            let build = self.wait_build(&handle).await?;
+            if build.result.as_deref() == Some("SUCCESS") || attempt >= policy.max_retries {
+                return Ok(build);
+            }
+            let retry = match &policy.retry_if {
+                Some(retry_if) => {
+                    let log = self.get_console_text(job, build.number).await?;
+                    retry_if(&build, &log)
+                }
+                None => true,
+            };
+            if !retry {
+                return Ok(build);
+            }
+            attempt += 1;
+            warn!(
+                "build failed, retrying - job={}, number={}, result={:?}, attempt={}",
+                job, build.number, build.result, attempt
+            );
+            self.pause(backoff).await?;
+            backoff = backoff.mul_f64(policy.backoff_factor);
+        }
+    }
+
+    /// Trigger a job and wait for it and the downstream builds it triggers, transitively
+    ///
+    /// Downstream jobs come from [`Jenkins::get_dependency_graph`], their builds
+    /// are matched by the upstream cause. A downstream job neither built nor
+    /// queued by its upstream once that finished, e.g. one only triggered on
+    /// success, is left out of the tree, as is one still queued after five
+    /// minutes.
+    ///
+    /// ## Arguments
+    ///
+    /// * `root_job` - job name or folder path, e.g. `team/build`
+    /// * `params` - parameters to trigger the root build
+    ///
+    pub async fn run_cascade(
+        &self,
+        root_job: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<CascadeNode> {
+        // the graph is keyed by full name
+        let root_job = full_name(root_job);
+        let graph = self.get_dependency_graph().await?;
+        let url = format!("{}/buildWithParameters", self.item_url(&root_job));
+        let handle = self
+            .trigger(
+                &root_job,
+                "buildWithParameters",
+                self.post(&url).form(&params),
+            )
+            .await?;
+        let queue_item = self.resume(&handle).await?;
+        let Some(number) = queue_item.executable.map(|e| e.number) else {
+            bail!(Error::APIError(format!(
+                "queue item left without build: {:?}",
+                queue_item.why
+            )))
+        };
+        let build = self.wait_item_build(&root_job, number).await?;
+        let node = self
+            .cascade_downstream(&graph, root_job.clone(), build)
+            .await?;
+        info!(
+            "cascade finished - job={}, builds={}, succeeded={}",
+            root_job,
+            node.iter().count(),
+            node.succeeded()
+        );
+        Ok(node)
+    }
+
+    fn cascade_downstream<'a>(
+        &'a self,
+        graph: &'a DependencyGraph,
+        job: String,
+        build: BuildRes,
+    ) -> BoxFuture<'a, Result<CascadeNode>> {
+        Box::pin(async move {
+            let downstream = try_join_all(graph.downstream(&job).map(|down| {
+                let (job, number) = (&job, build.number);
+                async move {
+                    let Some(number) = self.find_downstream_build(down, job, number).await? else {
+                        return Ok(None);
+                    };
+                    let build = self.wait_item_build(down, number).await?;
+                    let node = self
+                        .cascade_downstream(graph, down.to_owned(), build)
+                        .await?;
+                    Ok::<_, anyhow::Error>(Some(node))
+                }
+            }))
+            .await?;
+            Ok(CascadeNode {
+                job,
+                build,
+                downstream: downstream.into_iter().flatten().collect(),
+            })
+        })
+    }
+
+    /// Number of the build of `job` started by build `number` of `upstream`
+    async fn find_downstream_build(
+        &self,
+        job: &str,
+        upstream: &str,
+        number: i32,
+    ) -> Result<Option<i32>> {
+        let url = format!(
+            "{}/api/json?tree=builds[number,url,actions[causes[upstreamProject,upstreamBuild]]]{{0,{}}}",
+            self.item_url(job),
+            CASCADE_SCAN_BUILDS
+        );
+        let queue_url = format!(
+            "{}/queue/api/json?tree=items[id,task[name,url],actions[causes[shortDescription,upstreamProject,upstreamBuild]]]",
+            self.url
+        );
+        let find = || async {
+            let res: BuildHistoryRes = self.get_json(&url).await?;
+            Ok::<_, anyhow::Error>(
+                res.builds
+                    .iter()
+                    .find(|b| b.triggered_by(upstream, number))
+                    .map(|b| b.number),
+            )
+        };
+        let deadline = tokio::time::Instant::now() + CASCADE_START_TIMEOUT;
+        loop {
+            if let Some(found) = find().await? {
+                return Ok(Some(found));
+            }
+            // the upstream build finished, so it queued everything it triggers
+            let queue: QueueCausesRes = self.get_json(&queue_url).await?;
+            let queued = queue.items.iter().any(|item| {
+                item.task
+                    .url
+                    .as_deref()
+                    .is_some_and(|url| self.is_item_url(url, job))
+                    && item.triggered_by(upstream, number)
+            });
+            if !queued {
+                // it may have left the queue since the first look
+                let found = find().await?;
+                if found.is_none() {
+                    info!(
+                        "downstream build not triggered - job={}, upstream={}, number={}",
+                        job, upstream, number
+                    );
+                }
+                return Ok(found);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                warn!(
+                    "downstream build not started - job={}, upstream={}, number={}",
+                    job, upstream, number
+                );
+                return Ok(None);
+            }
+            trace!(
+                "waiting for downstream build - job={}, upstream={}, number={}",
+                job,
+                upstream,
+                number
+            );
+            self.pause(Duration::from_secs(3)).await?;
+        }
+    }
+
+    /// [`Jenkins::wait_build`] for a job given by full name
+    async fn wait_item_build(&self, job: &str, number: i32) -> Result<BuildRes> {
+        let url = format!("{}/{}/api/json", self.item_url(job), number);
+        loop {
+            let build: BuildRes = self.get_json(&url).await?;
+            if !build.building {
+                info!("build finished - job={}, build={:?}", job, build);
+                return Ok(build);
+            }
+            trace!("build running - job={}, number={}", job, number);
+            self.pause(Duration::from_secs(3)).await?;
+        }
+    }
+
+    /// Find the build or queue item triggered with a marker from [`new_trigger_marker`]
+    ///
+    /// Pass the marker as a build parameter (the job needs a matching string
+    /// parameter) or as the cause of [`Jenkins::queue_build_with_cause`], then
+    /// after a failed or timed out trigger this tells whether
+    /// the trigger went through, so it can be retried without a double build.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name or folder path, e.g. `team/deploy`
+    /// * `marker` - marker passed to the trigger
+    ///
+    pub async fn find_build_by_marker(
+        &self,
+        job: &str,
+        marker: &str,
+    ) -> Result<Option<MarkedTrigger>> {
+        let url = format!(
+            "{}/queue/api/json?tree=items[id,params,task[name,url],actions[causes[shortDescription,note]]]",
+            self.url
+        );
+        let queue: QueueCausesRes = self.get_json(&url).await?;
+        let queued = queue.items.into_iter().find(|item| {
+            item.task
+                .url
+                .as_deref()
+                .is_some_and(|url| self.is_item_url(url, job))
+                && item.has_marker(marker)
+        });
+        if let Some(item) = queued {
+            let url = QueueItemUrl::from_location(&self.url, &format!("queue/item/{}/", item.id))?;
+            return Ok(Some(MarkedTrigger::Queued(url)));
+        }
+        let url = format!(
+            "{}/api/json?tree=builds[number,url,actions[parameters[name,value],causes[shortDescription,note]]]{{0,{}}}",
+            self.item_url(job),
+            MARKER_SCAN_BUILDS
+        );
+        let res: BuildHistoryRes = self.get_json(&url).await?;
+        Ok(res
+            .builds
+            .into_iter()
+            .find(|b| b.has_marker(marker))
+            .map(|b| {
+                MarkedTrigger::Started(BuildHandle {
+                    job: job.to_owned(),
+                    number: b.number,
+                    url: b.url,
+                })
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[tokio::test]
+    async fn build_with_parameter() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let cli = Jenkins::new(
+            "https://jenkins.domain.com",
+            "jenkins-user",
+            "jenkins-token",
+        );
+        let params = HashMap::from([("HostLimit", "xxx"), ("Module", "ansible.builtin.ping")]);
+        let res = cli
+            .build_with_parameter("ansible-global-adhoc", params)
+            .await;
+        println!("{:?}", res);
+    }
+
+    #[test]
+    fn queue_item_handle_roundtrip() {
+        let handle = QueueItemHandle {
+            job: "deploy".to_owned(),
+            queue_item_url: "https://jenkins.domain.com/queue/item/42/".parse().unwrap(),
+        };
+        let json = serde_json::to_string(&handle).unwrap();
+        let parsed: QueueItemHandle = serde_json::from_str(&json).unwrap();
+        assert_eq!(handle, parsed);
+    }
+
+    #[tokio::test]
+    async fn dry_run_build_with_parameter() {
+        let cli = Jenkins::builder("http://127.0.0.1:9", "jenkins-user", "jenkins-token")
+            .dry_run(true)
+            .build();
+        let res = cli
+            .build_with_parameter("deploy", HashMap::from([("Version", "1.0")]))
+            .await
+            .unwrap();
+        assert_eq!(res.executable.unwrap().number, 0);
+    }
+
+    #[tokio::test]
+    async fn non_ascii_location_is_an_error() {
+        let server = crate::mock::MockServer::start(vec![
+            b"HTTP/1.1 201 Created\r\nLocation: /queue/item/\xe9/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "jenkins-user", "jenkins-token");
+        let err = cli
+            .queue_build_with_parameter("deploy", HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid location header"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn cause_sent_with_token() {
+        use crate::mock::{response, MockServer};
+
+        let server = MockServer::start(vec![response(
+            "201 Created",
+            &[("Location", "/queue/item/3/")],
+            "",
+        )])
+        .await;
+        let cli = Jenkins::new(&server.url, "jenkins-user", "jenkins-token");
+        cli.queue_build_with_cause("deploy", HashMap::new(), "s3cret", "bot: 1.4.2")
+            .await
+            .unwrap();
+        assert!(server.requests()[0]
+            .starts_with("POST /job/deploy/buildWithParameters?token=s3cret&cause=bot%3A+1.4.2 "));
+    }
+
+    #[tokio::test]
+    async fn marker_found_in_queued_cause() {
+        use crate::mock::{response, MockServer};
+
+        let json = [("Content-Type", "application/json")];
+        let server = MockServer::start(vec![
+            response(
+                "200 OK",
+                &json,
+                r#"{"items":[
+                    {"id":8,"params":"","task":{"name":"deploy","url":"http://ci/job/other/job/deploy/"},
+                     "actions":[{"causes":[{"shortDescription":"remote","note":"m-1"}]}]},
+                    {"id":9,"params":"","task":{"name":"deploy","url":"http://ci/job/team/job/deploy/"},
+                     "actions":[{},{"causes":[{"shortDescription":"remote","note":"m-1"}]}]}]}"#,
+            ),
+            response("200 OK", &json, r#"{"items":[]}"#),
+            response("200 OK", &json, r#"{"builds":[]}"#),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "jenkins-user", "jenkins-token");
+        match cli
+            .find_build_by_marker("team/deploy", "m-1")
+            .await
+            .unwrap()
+        {
+            Some(MarkedTrigger::Queued(url)) => assert_eq!(url.id(), 9),
+            other => panic!("{:?}", other),
+        }
+        assert!(server.requests()[0].contains("causes[shortDescription,note]"));
+
+        assert!(cli
+            .find_build_by_marker("team/deploy", "m-1")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(server.requests()[2].starts_with("GET /job/team/job/deploy/api/json?"));
+    }
+
+    #[test]
+    fn classify_queue_reasons() {
+        assert_eq!(
+            BlockReason::classify("In the quiet period. Expires in 4.9 sec"),
+            BlockReason::QuietPeriod
+        );
+        assert_eq!(
+            BlockReason::classify("Build #12 is already in progress (ETA: 3 min 2 sec)"),
+            BlockReason::Concurrency
+        );
+        assert_eq!(
+            BlockReason::classify("Waiting for next available executor on ‘linux’"),
+            BlockReason::WaitingForExecutor(Some("linux".to_owned()))
+        );
+        assert_eq!(
+            BlockReason::classify("There are no nodes with the label ‘gpu’"),
+            BlockReason::NoNodeForLabel("gpu".to_owned())
+        );
+        assert_eq!(
+            BlockReason::classify("All nodes of label ‘mac’ are offline"),
+            BlockReason::Offline("mac".to_owned())
+        );
+    }
+
+    #[test]
+    fn queue_item_url_behind_prefix() {
+        let cli = Jenkins::new("https://host.example.com/jenkins/", "u", "p");
+        assert_eq!(cli.get_url(), "https://host.example.com/jenkins");
+        for location in [
+            "https://host.example.com/jenkins/queue/item/5/",
+            "https://host.example.com/jenkins/queue/item/5",
+            "http://10.0.0.5:8080/queue/item/5/",
+            "/jenkins/queue/item/5/",
+            "queue/item/5",
+        ] {
+            let url = QueueItemUrl::from_location(cli.get_url(), location).unwrap();
+            assert_eq!(
+                url.as_str(),
+                "https://host.example.com/jenkins/queue/item/5/"
+            );
+            assert_eq!(url.id(), 5);
+        }
+        assert!(QueueItemUrl::from_location(cli.get_url(), "/jenkins/job/x/").is_err());
+    }
+
+    #[test]
+    fn trigger_markers_are_unique() {
+        assert_ne!(new_trigger_marker(), new_trigger_marker());
+    }
+
+    #[tokio::test]
+    async fn cascade_in_folder_skips_untriggered_downstream() {
+        use crate::mock::{response, MockServer};
+
+        let json = [("Content-Type", "application/json")];
+        let server = MockServer::start(vec![
+            response(
+                "200 OK",
+                &json,
+                r#"{"jobs":[{"_class":"com.cloudbees.hudson.plugins.folder.Folder","fullName":"team","jobs":[
+                    {"fullName":"team/build","downstreamProjects":[{"fullName":"team/deploy"}]},
+                    {"fullName":"team/deploy","upstreamProjects":[{"fullName":"team/build"}]}]}]}"#,
+            ),
+            response("201 Created", &[("Location", "/queue/item/4/")], ""),
+            response("200 OK", &json, r#"{"executable":{"number":12,"url":"u"}}"#),
+            response(
+                "200 OK",
+                &json,
+                r#"{"number":12,"url":"u","building":false,"result":"FAILURE","duration":1,"timestamp":1}"#,
+            ),
+            response("200 OK", &json, r#"{"builds":[]}"#),
+            response("200 OK", &json, r#"{"items":[]}"#),
+            response("200 OK", &json, r#"{"builds":[]}"#),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "jenkins-user", "jenkins-token");
+        let node = cli
+            .run_cascade("team/job/build", HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(node.job, "team/build");
+        assert!(node.downstream.is_empty());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 7);
+        assert!(requests[1].starts_with("POST /job/team/job/build/buildWithParameters "));
+        assert!(requests[3].starts_with("GET /job/team/job/build/12/api/json "));
+        assert!(requests[4].starts_with("GET /job/team/job/deploy/api/json?"));
+    }
+
+    #[test]
+    fn cascade_matches_upstream_cause() {
+        let res: BuildHistoryRes = serde_json::from_str(
+            r#"{"builds":[
+                {"number":8,"url":"u8","actions":[{"causes":[{"shortDescription":"Started by upstream project \"build\" build number 41","upstreamProject":"build","upstreamBuild":41}]}]},
+                {"number":7,"url":"u7","actions":[{},{"causes":[{"shortDescription":"Started by upstream project \"build\" build number 40","upstreamProject":"build","upstreamBuild":40}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let found: Vec<_> = res
+            .builds
+            .iter()
+            .filter(|b| b.triggered_by("build", 40))
+            .map(|b| b.number)
+            .collect();
+        assert_eq!(found, [7]);
+
+        let build = |number, result: &str| -> BuildRes {
+            serde_json::from_value(
+                serde_json::json!({"number":number,"url":"u","building":false,
+                "result":result,"duration":1,"timestamp":1}),
+            )
+            .unwrap()
+        };
+        let tree = CascadeNode {
+            job: "build".to_owned(),
+            build: build(40, "SUCCESS"),
+            downstream: vec![CascadeNode {
+                job: "apps/deploy".to_owned(),
+                build: build(7, "FAILURE"),
+                downstream: Vec::new(),
+            }],
+        };
+        assert!(!tree.succeeded());
+        let jobs: Vec<_> = tree.iter().map(|n| n.job.as_str()).collect();
+        assert_eq!(jobs, ["build", "apps/deploy"]);
+    }
+
+    #[test]
+    fn simulate_executor_capacity() {
+        let mins = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(simulate_start(&[], &[]), None);
+        assert_eq!(
+            simulate_start(&[Duration::ZERO, mins(5)], &[]),
+            Some(Duration::ZERO)
+        );
+        // the idle executor takes the first build, the second waits for the busy one
+        assert_eq!(
+            simulate_start(&[Duration::ZERO, mins(5)], &[mins(10), mins(3)]),
+            Some(mins(8))
+        );
+        assert_eq!(simulate_start(&[mins(2)], &[mins(10)]), Some(mins(12)));
+    }
+}
@@ -0,0 +1,320 @@
+//! Recording Jenkins interactions to a cassette file and replaying them offline
+//!
+//! Record once against a real Jenkins, then run the same test hermetically:
+//!
+//! ```no_run
+//! # async fn f() -> anyhow::Result<()> {
+//! use jenkins_rs::replay::Cassette;
+//!
+//! let cassette = if std::env::var("RECORD").is_ok() {
+//!     Cassette::record("tests/cassettes/jobs.json")
+//! } else {
+//!     Cassette::replay("tests/cassettes/jobs.json")?
+//! };
+//! let cli = jenkins_rs::Jenkins::builder("http://localhost:8080", "bot", "token")
+//!     .cassette(cassette)
+//!     .build();
+//! let config = cli.get_job_config("api").await?;
+//! // also written once the last clone of the client is dropped
+//! cli.save_cassette().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests are matched on method, URL and body. Replaying takes recorded
+//! interactions in order, once all matching ones were used the last is
+//! repeated, e.g. for polling. A request without a match gets a
+//! `501 Not Implemented`.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::{trace, warn};
+use reqwest::{header, Method, Response, ResponseBuilderExt, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::Jenkins;
+
+/// Response headers not worth keeping in a cassette
+const SKIPPED_HEADERS: [header::HeaderName; 4] = [
+    header::CONTENT_LENGTH,
+    header::DATE,
+    header::SET_COOKIE,
+    header::TRANSFER_ENCODING,
+];
+
+/// One request and its response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// UTF-8 body, empty when `binary_body` is set
+    #[serde(default)]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_body: Option<Vec<u8>>,
+}
+
+impl Interaction {
+    fn matches(&self, method: &Method, url: &Url, body: Option<&str>) -> bool {
+        self.method == method.as_str()
+            && self.url == url.as_str()
+            && self.request_body.as_deref() == body
+    }
+
+    fn response(&self, url: &Url) -> Response {
+        let body = match &self.binary_body {
+            Some(bytes) => bytes.clone(),
+            None => self.body.clone().into_bytes(),
+        };
+        let mut res = http::Response::builder()
+            .status(StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK))
+            .url(url.clone())
+            .body(body)
+            .expect("replayed response");
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                header::HeaderName::from_bytes(name.as_bytes()),
+                header::HeaderValue::from_str(value),
+            ) {
+                res.headers_mut().append(name, value);
+            }
+        }
+        res.into()
+    }
+}
+
+enum Mode {
+    Record,
+    Replay { used: Vec<bool> },
+}
+
+struct Tape {
+    mode: Mode,
+    interactions: Vec<Interaction>,
+    /// interactions in the cassette file
+    saved: usize,
+}
+
+/// Cassette file of recorded interactions, see
+/// [`JenkinsBuilder::cassette`](crate::JenkinsBuilder::cassette)
+pub struct Cassette {
+    path: PathBuf,
+    recording: bool,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    /// Send requests to Jenkins and keep the interactions for `path`,
+    /// replacing the file on [`Cassette::save`] or once dropped
+    pub fn record(path: impl AsRef<Path>) -> Cassette {
+        Cassette {
+            path: path.as_ref().to_owned(),
+            recording: true,
+            tape: Mutex::new(Tape {
+                mode: Mode::Record,
+                interactions: Vec::new(),
+                saved: 0,
+            }),
+        }
+    }
+
+    /// Answer requests from the interactions recorded in `path`, without
+    /// sending anything
+    pub fn replay(path: impl AsRef<Path>) -> Result<Cassette> {
+        let json = std::fs::read_to_string(path.as_ref())?;
+        let interactions: Vec<Interaction> = serde_json::from_str(&json)?;
+        Ok(Cassette {
+            path: path.as_ref().to_owned(),
+            recording: false,
+            tape: Mutex::new(Tape {
+                mode: Mode::Replay {
+                    used: vec![false; interactions.len()],
+                },
+                saved: interactions.len(),
+                interactions,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the interactions recorded so far to the cassette file
+    pub async fn save(&self) -> Result<()> {
+        let mut tape = self.tape.lock().await;
+        if !self.recording || tape.saved == tape.interactions.len() {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(&tape.interactions)?;
+        tokio::fs::write(&self.path, json).await?;
+        tape.saved = tape.interactions.len();
+        trace!(
+            "cassette saved - path={:?}, interactions={}",
+            self.path,
+            tape.saved
+        );
+        Ok(())
+    }
+
+    /// Recorded response to the request, `None` while recording
+    pub(crate) async fn play(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: Option<&str>,
+    ) -> Option<Response> {
+        let mut tape = self.tape.lock().await;
+        let Tape {
+            mode, interactions, ..
+        } = &mut *tape;
+        let Mode::Replay { used } = mode else {
+            return None;
+        };
+        let matching: Vec<usize> = (0..interactions.len())
+            .filter(|i| interactions[*i].matches(method, url, body))
+            .collect();
+        let Some(i) = matching
+            .iter()
+            .find(|i| !used[**i])
+            .or(matching.last())
+            .copied()
+        else {
+            warn!("replay, no recorded interaction - {} {}", method, url);
+            let mut res = http::Response::new(format!(
+                "replay: no interaction recorded for {} {}",
+                method, url
+            ));
+            *res.status_mut() = StatusCode::NOT_IMPLEMENTED;
+            return Some(res.into());
+        };
+        trace!("replay - {} {}, interaction={}", method, url, i);
+        used[i] = true;
+        Some(interactions[i].response(url))
+    }
+
+    /// Keep the interaction when recording, handing back an equivalent response
+    pub(crate) async fn record_response(
+        &self,
+        method: &Method,
+        url: &Url,
+        request_body: Option<String>,
+        res: Response,
+    ) -> Result<Response, reqwest::Error> {
+        if !self.recording {
+            return Ok(res);
+        }
+        let (status, version, headers) = (res.status(), res.version(), res.headers().clone());
+        let bytes = res.bytes().await?;
+        let (body, binary_body) = match std::str::from_utf8(&bytes) {
+            Ok(text) => (text.to_owned(), None),
+            Err(_) => (String::new(), Some(bytes.to_vec())),
+        };
+        let interaction = Interaction {
+            method: method.to_string(),
+            url: url.to_string(),
+            request_body,
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(name, _)| !SKIPPED_HEADERS.contains(name))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_owned()))
+                })
+                .collect(),
+            body,
+            binary_body,
+        };
+        self.tape.lock().await.interactions.push(interaction);
+        let mut copy = http::Response::builder()
+            .status(status)
+            .version(version)
+            .url(url.clone())
+            .body(bytes)
+            .expect("recorded response");
+        *copy.headers_mut() = headers;
+        Ok(copy.into())
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        let tape = self.tape.get_mut();
+        if !self.recording || tape.saved == tape.interactions.len() {
+            return;
+        }
+        let written = serde_json::to_string_pretty(&tape.interactions)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&self.path, json)?));
+        if let Err(e) = written {
+            warn!("record cassette - path={:?}, err={}", self.path, e);
+        }
+    }
+}
+
+impl Jenkins {
+    /// Write the interactions recorded so far, see [`Cassette::save`]
+    ///
+    /// Does nothing without a recording cassette.
+    pub async fn save_cassette(&self) -> Result<()> {
+        match &self.cassette {
+            Some(cassette) => cassette.save().await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", std::process::id()));
+        let cassette = Cassette::record(&path);
+        let url: Url = "http://ci/job/api/api/json".parse().unwrap();
+        assert!(cassette.play(&Method::GET, &url, None).await.is_none());
+        for body in ["{\"nextBuildNumber\":3}", "{\"nextBuildNumber\":4}"] {
+            let res = http::Response::builder()
+                .header("X-Jenkins", "2.440")
+                .header("Date", "Mon, 01 Jan 2024 00:00:00 GMT")
+                .body(body)
+                .unwrap();
+            let res = cassette
+                .record_response(&Method::GET, &url, None, res.into())
+                .await
+                .unwrap();
+            assert_eq!(res.text().await.unwrap(), body);
+        }
+        assert!(!path.exists());
+        drop(cassette);
+
+        let cassette = Cassette::replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // responses in order, then the last one again
+        let play = || async {
+            let res = cassette.play(&Method::GET, &url, None).await.unwrap();
+            assert_eq!(res.headers()["x-jenkins"], "2.440");
+            assert!(res.headers().get("date").is_none());
+            res.text().await.unwrap()
+        };
+        assert_eq!(play().await, "{\"nextBuildNumber\":3}");
+        assert_eq!(play().await, "{\"nextBuildNumber\":4}");
+        assert_eq!(play().await, "{\"nextBuildNumber\":4}");
+        let res = cassette
+            .play(&Method::POST, &url, Some("json={}"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+}
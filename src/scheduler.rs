@@ -0,0 +1,347 @@
+//! Triggering jobs on a cron schedule from the application instead of Jenkins timers
+//!
+//! ```no_run
+//! # async fn f(cli: jenkins_rs::Jenkins) -> anyhow::Result<()> {
+//! use std::time::Duration;
+//! use jenkins_rs::scheduler::{ScheduledJob, Scheduler};
+//!
+//! Scheduler::new(cli)
+//!     .schedule(ScheduledJob::new("nightly", "0 2 * * *".parse()?).jitter(Duration::from_secs(600)))
+//!     .schedule(ScheduledJob::new("sync", "*/15 * * * 1-5".parse()?).param("TARGET", "staging"))
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use log::{info, trace, warn};
+
+use crate::{job::full_name, join::try_join_all, model::civil_date, Jenkins, QueueItemHandle};
+
+/// Days searched for the next match, e.g. `0 0 29 2 *` fires every 4 or 8 years
+const SEARCH_DAYS: i64 = 9 * 366;
+
+/// Cron expression of five fields in UTC: minute, hour, day of month, month
+/// and day of week
+///
+/// Fields take `*`, values, ranges `a-b`, steps `*/n` or `a-b/n` and lists of
+/// those. Sunday is 0 or 7. As in cron, a day matches either restricted day
+/// field when both are restricted. `@hourly`, `@daily`, `@weekly`,
+/// `@monthly` and `@yearly` are shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// `*` day of month
+    any_day: bool,
+    /// `*` day of week
+    any_weekday: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let Some(step) = step else {
+            bail!("scheduler: invalid step in `{}`", part)
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (from.parse()?, to.parse()?),
+            // `5/10` runs from 5 to the end
+            None if step > 1 => (range.parse()?, max),
+            None => {
+                let value = range.parse()?;
+                (value, value)
+            }
+        };
+        if from < min || to > max || from > to {
+            bail!("scheduler: `{}` out of range {}-{}", part, min, max)
+        }
+        for value in (from..=to).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let spec = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            spec => spec,
+        };
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("scheduler: expected 5 fields in `{}`", s)
+        };
+        let weekdays = parse_field(weekday, 0, 7)?;
+        Ok(Schedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            // 7 is Sunday too
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl Schedule {
+    fn day_matches(&self, days: i64) -> bool {
+        let (_, month, day) = civil_date(days);
+        // 1970-01-01 was a Thursday
+        let weekday = (days + 4).rem_euclid(7);
+        let by_day = self.days & 1 << day != 0;
+        let by_weekday = self.weekdays & 1 << weekday != 0;
+        let day_ok = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => by_weekday,
+            (false, true) => by_day,
+            (false, false) => by_day || by_weekday,
+        };
+        self.months & 1 << month != 0 && day_ok
+    }
+
+    /// First minute strictly after `time` the schedule fires at
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64 / 60 * 60 + 60;
+        let (first_day, mut minute) = (secs.div_euclid(86400), secs.rem_euclid(86400) / 60);
+        for days in first_day..first_day + SEARCH_DAYS {
+            if self.day_matches(days) {
+                let found = (minute..1440)
+                    .find(|m| self.hours & 1 << (m / 60) != 0 && self.minutes & 1 << (m % 60) != 0);
+                if let Some(m) = found {
+                    return Some(UNIX_EPOCH + Duration::from_secs((days * 86400 + m * 60) as u64));
+                }
+            }
+            minute = 0;
+        }
+        None
+    }
+}
+
+/// Job triggered by a [`Scheduler`]
+#[derive(Debug, Clone)]
+pub struct ScheduledJob {
+    job: String,
+    schedule: Schedule,
+    params: HashMap<String, String>,
+    jitter: Duration,
+    allow_overlap: bool,
+}
+
+impl ScheduledJob {
+    /// `job` is the name or the folder path of the job, e.g. `team/nightly`
+    pub fn new(job: &str, schedule: Schedule) -> ScheduledJob {
+        ScheduledJob {
+            job: job.to_owned(),
+            schedule,
+            params: HashMap::new(),
+            jitter: Duration::ZERO,
+            allow_overlap: false,
+        }
+    }
+
+    /// Trigger with parameters through `buildWithParameters`
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.insert(name.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Delay each trigger by a random duration up to `max`, spreading jobs
+    /// scheduled at the same minute
+    pub fn jitter(mut self, max: Duration) -> Self {
+        self.jitter = max;
+        self
+    }
+
+    /// Trigger even while a build of the job is queued or running, skipped
+    /// by default
+    pub fn allow_overlap(mut self, allow: bool) -> Self {
+        self.allow_overlap = allow;
+        self
+    }
+}
+
+fn random_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (max.as_millis() as u64 + 1))
+}
+
+/// Triggers jobs on their [`Schedule`] while [`Scheduler::run`] is awaited
+pub struct Scheduler {
+    jenkins: Jenkins,
+    jobs: Vec<ScheduledJob>,
+}
+
+impl Scheduler {
+    pub fn new(jenkins: Jenkins) -> Scheduler {
+        Scheduler {
+            jenkins,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn schedule(mut self, job: ScheduledJob) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Trigger the jobs at their scheduled times
+    ///
+    /// Failed triggers are logged and retried at the next scheduled time.
+    /// Runs until the client is shut down, see
+    /// [`Shutdown`](crate::shutdown::Shutdown).
+    pub async fn run(&self) -> Result<()> {
+        info!("scheduler started - jobs={}", self.jobs.len());
+        try_join_all(self.jobs.iter().map(|job| self.run_job(job))).await?;
+        Ok(())
+    }
+
+    async fn run_job(&self, job: &ScheduledJob) -> Result<()> {
+        loop {
+            let now = SystemTime::now();
+            let Some(next) = job.schedule.next_after(now) else {
+                warn!("schedule never fires - job={}", job.job);
+                return Ok(());
+            };
+            let delay = next.duration_since(now).unwrap_or_default() + random_jitter(job.jitter);
+            trace!("scheduled - job={}, in={:?}", job.job, delay);
+            self.jenkins.pause(delay).await?;
+            match self.fire(job).await {
+                Ok(Some(handle)) => info!(
+                    "scheduled trigger - job={}, queue_item={}",
+                    job.job, handle.queue_item_url
+                ),
+                Ok(None) => info!("scheduled trigger skipped, still running - job={}", job.job),
+                Err(e) => warn!("scheduled trigger - job={}, err={}", job.job, e),
+            }
+        }
+    }
+
+    async fn fire(&self, job: &ScheduledJob) -> Result<Option<QueueItemHandle>> {
+        let full = full_name(&job.job);
+        if !job.allow_overlap {
+            let any_params = HashMap::new();
+            let (queue, running) = tokio::try_join!(
+                self.jenkins.get_queue(),
+                self.jenkins.find_running_builds(&full, &any_params),
+            )?;
+            // task names are not unique across folders, urls are
+            let queued = queue.iter().any(|item| {
+                item.task
+                    .url
+                    .as_deref()
+                    .is_some_and(|url| self.jenkins.is_item_url(url, &full))
+            });
+            if !running.is_empty() || queued {
+                return Ok(None);
+            }
+        }
+        let action = if job.params.is_empty() {
+            "build"
+        } else {
+            "buildWithParameters"
+        };
+        let url = format!("{}/{}", self.jenkins.item_url(&full), action);
+        // handles take the `a/job/b` form of the other job name arguments
+        let handle = self
+            .jenkins
+            .trigger(
+                &full.replace('/', "/job/"),
+                action,
+                self.jenkins.post(&url).form(&job.params),
+            )
+            .await?;
+        Ok(Some(handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_scheduled_time() {
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        // 2024-01-01T00:00:00Z, a Monday
+        let monday = 1704067200;
+        let next = |spec: &str, from| spec.parse::<Schedule>().unwrap().next_after(at(from));
+
+        assert_eq!(next("*/15 * * * *", monday), Some(at(monday + 15 * 60)));
+        assert_eq!(
+            next("30 2 * * *", monday + 3 * 3600),
+            Some(at(monday + 86400 + 9000))
+        );
+        // Saturday
+        assert_eq!(
+            next("0 9 * * 6", monday),
+            Some(at(monday + 5 * 86400 + 9 * 3600))
+        );
+        // both day fields restricted: the 1st or a Sunday, Sunday the 7th comes first
+        assert_eq!(next("0 0 1 * 7", monday), Some(at(monday + 6 * 86400)));
+        // 2024-02-29
+        assert_eq!(next("0 0 29 2 *", monday), Some(at(monday + 59 * 86400)));
+        assert_eq!(next("0 0 31 2 *", monday), None);
+
+        assert!("* * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn fire_in_folder() {
+        use crate::mock::{response, MockServer};
+
+        let json = [("Content-Type", "application/json")];
+        // answers the queue and the running builds requests, whichever comes first
+        let body = r#"{"builds":[],"items":[{"id":1,"inQueueSince":0,"task":{"name":"nightly",
+            "url":"http://jenkins.internal/job/team/job/other/job/nightly/"}}]}"#;
+        let server = MockServer::start(vec![
+            response("200 OK", &json, body),
+            response("200 OK", &json, body),
+            response("201 Created", &[("Location", "/queue/item/7/")], ""),
+        ])
+        .await;
+        let scheduler = Scheduler::new(Jenkins::new(&server.url, "user", "token"));
+        let job = ScheduledJob::new("team/nightly", "@daily".parse().unwrap());
+        // a job of the same name queued in another folder doesn't count
+        let handle = scheduler.fire(&job).await.unwrap().unwrap();
+        assert_eq!(handle.queue_item_url.id(), 7);
+        let requests = server.requests();
+        assert!(requests[..2]
+            .iter()
+            .any(|r| r.starts_with("GET /job/team/job/nightly/api/json?tree=builds")));
+        assert!(
+            requests[2].starts_with("POST /job/team/job/nightly/build "),
+            "{}",
+            requests[2]
+        );
+    }
+}
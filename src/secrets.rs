@@ -0,0 +1,649 @@
+//! Credential providers loading the Jenkins token from a secret store
+//!
+//! [`CachedCredentials`] keeps the last fetched value for a ttl, refreshes it in
+//! the background once expired and re-fetches right away when Jenkins answers
+//! `401`, e.g. after the token was rotated in the store.
+
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::{
+    credentials::{Credentials, CredentialsProvider},
+    BoxFuture, Error,
+};
+
+/// Store [`CachedCredentials`] fetches from
+pub trait SecretSource: Send + Sync + 'static {
+    fn fetch<'a>(&'a self, hc: &'a reqwest::Client) -> BoxFuture<'a, Result<Credentials>>;
+}
+
+struct Cached<S> {
+    source: S,
+    hc: reqwest::Client,
+    ttl: Duration,
+    current: RwLock<(Credentials, Instant)>,
+    /// held while fetching
+    refreshing: Mutex<()>,
+}
+
+impl<S: SecretSource> Cached<S> {
+    fn current(&self) -> (Credentials, Instant) {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Fetch once, `true` if the value changed
+    ///
+    /// Waits for a refresh already running and takes its result instead of
+    /// fetching again.
+    async fn refresh(&self) -> bool {
+        let (before, requested) = (self.current().0, Instant::now());
+        let _refreshing = self.refreshing.lock().await;
+        let (current, fetched) = self.current();
+        if fetched > requested {
+            return current != before;
+        }
+        match self.source.fetch(&self.hc).await {
+            Ok(credentials) => {
+                let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+                let changed = before != credentials;
+                *current = (credentials, Instant::now());
+                info!("credentials refreshed - changed={}", changed);
+                changed
+            }
+            Err(e) => {
+                warn!("credentials refresh failed - err={:?}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Credentials from a [`SecretSource`], cached for a ttl
+///
+/// ```no_run
+/// # async fn f() -> anyhow::Result<()> {
+/// use std::time::Duration;
+/// use jenkins_rs::{secrets::{CachedCredentials, VaultKv}, Jenkins};
+/// let source = VaultKv::new("https://vault:8200", &std::env::var("VAULT_TOKEN")?, "secret", "ci/jenkins");
+/// let credentials = CachedCredentials::load(source, Duration::from_secs(300)).await?;
+/// let cli = Jenkins::builder("https://jenkins.domain.com", "", "")
+///     .credentials(credentials)
+///     .build();
+/// # Ok(())
+/// # }
+/// ```
+pub struct CachedCredentials<S> {
+    inner: Arc<Cached<S>>,
+}
+
+impl<S> Clone for CachedCredentials<S> {
+    fn clone(&self) -> Self {
+        CachedCredentials {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: SecretSource> CachedCredentials<S> {
+    /// Fetch the credentials once, failing if the store is not reachable
+    pub async fn load(source: S, ttl: Duration) -> Result<CachedCredentials<S>> {
+        let hc = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(3))
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let credentials = source.fetch(&hc).await?;
+        Ok(CachedCredentials {
+            inner: Arc::new(Cached {
+                source,
+                hc,
+                ttl,
+                current: RwLock::new((credentials, Instant::now())),
+                refreshing: Mutex::new(()),
+            }),
+        })
+    }
+}
+
+impl<S: SecretSource> CredentialsProvider for CachedCredentials<S> {
+    fn credentials(&self) -> Credentials {
+        let (credentials, fetched) = self.inner.current();
+        // serve the expired value while refreshing, requests must not block on the store
+        if fetched.elapsed() >= self.inner.ttl && self.inner.refreshing.try_lock().is_ok() {
+            if let Ok(rt) = tokio::runtime::Handle::try_current() {
+                let inner = self.inner.clone();
+                rt.spawn(async move { inner.refresh().await });
+            }
+        }
+        credentials
+    }
+
+    fn refresh(&self) -> BoxFuture<'_, bool> {
+        Box::pin(self.inner.refresh())
+    }
+}
+
+/// Field of a secret json object
+fn secret_field(secret: &Value, key: &str) -> Result<String> {
+    secret[key]
+        .as_str()
+        .map(str::to_owned)
+        .with_context(|| format!("secret has no string field {}", key))
+}
+
+/// Secret of the HashiCorp Vault KV version 2 engine
+#[cfg(feature = "vault")]
+#[derive(Debug, Clone)]
+pub struct VaultKv {
+    /// e.g. `https://vault:8200`
+    pub addr: String,
+    pub token: String,
+    /// mount of the KV engine, e.g. `secret`
+    pub mount: String,
+    pub path: String,
+    /// secret field holding the Jenkins user, `user` by default
+    pub user_key: String,
+    /// secret field holding the Jenkins api token, `token` by default
+    pub password_key: String,
+}
+
+#[cfg(feature = "vault")]
+impl VaultKv {
+    pub fn new(addr: &str, token: &str, mount: &str, path: &str) -> VaultKv {
+        VaultKv {
+            addr: addr.trim_end_matches('/').to_owned(),
+            token: token.to_owned(),
+            mount: mount.trim_matches('/').to_owned(),
+            path: path.trim_matches('/').to_owned(),
+            user_key: "user".to_owned(),
+            password_key: "token".to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "vault")]
+impl SecretSource for VaultKv {
+    fn fetch<'a>(&'a self, hc: &'a reqwest::Client) -> BoxFuture<'a, Result<Credentials>> {
+        Box::pin(async move {
+            let url = format!("{}/v1/{}/data/{}", self.addr, self.mount, self.path);
+            let res = hc
+                .get(&url)
+                .header("X-Vault-Token", &self.token)
+                .send()
+                .await
+                .map_err(Error::NetworkError)?;
+            if !res.status().is_success() {
+                warn!("vault - url={}, res={:?}", url, res);
+                bail!(Error::APIError(format!("http status: {}", res.status())))
+            }
+            let body: Value = res.json().await.context("parse vault payload as json")?;
+            let secret = &body["data"]["data"];
+            Ok(Credentials {
+                user: secret_field(secret, &self.user_key)?,
+                password: secret_field(secret, &self.password_key)?,
+            })
+        })
+    }
+}
+
+/// Json secret of AWS Secrets Manager
+///
+/// Requests are signed with the first access key found in
+/// - the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and optional
+///   `AWS_SESSION_TOKEN` environment variables
+/// - the profile of the shared credentials file, `~/.aws/credentials` or
+///   `AWS_SHARED_CREDENTIALS_FILE`
+/// - the role of the EC2 instance, from the instance metadata service
+#[cfg(feature = "aws-secrets")]
+#[derive(Debug, Clone)]
+pub struct AwsSecret {
+    /// e.g. `eu-west-1`
+    pub region: String,
+    /// name or arn of the secret
+    pub secret_id: String,
+    /// profile of the shared credentials file, `AWS_PROFILE` or `default`
+    /// when `None`
+    pub profile: Option<String>,
+    /// secret field holding the Jenkins user, `user` by default
+    pub user_key: String,
+    /// secret field holding the Jenkins api token, `token` by default
+    pub password_key: String,
+}
+
+#[cfg(feature = "aws-secrets")]
+impl AwsSecret {
+    pub fn new(region: &str, secret_id: &str) -> AwsSecret {
+        AwsSecret {
+            region: region.to_owned(),
+            secret_id: secret_id.to_owned(),
+            profile: None,
+            user_key: "user".to_owned(),
+            password_key: "token".to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "aws-secrets")]
+impl SecretSource for AwsSecret {
+    fn fetch<'a>(&'a self, hc: &'a reqwest::Client) -> BoxFuture<'a, Result<Credentials>> {
+        Box::pin(async move {
+            let key = aws::access_key(hc, self.profile.as_deref()).await?;
+            let host = format!("secretsmanager.{}.amazonaws.com", self.region);
+            let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+            let signed = aws::sign(
+                &key,
+                &self.region,
+                &host,
+                "secretsmanager.GetSecretValue",
+                &body,
+                std::time::SystemTime::now(),
+            );
+            let mut req = hc
+                .post(format!("https://{}/", host))
+                .header("Content-Type", aws::CONTENT_TYPE)
+                .header("X-Amz-Target", "secretsmanager.GetSecretValue")
+                .header("X-Amz-Date", &signed.date_time)
+                .header("Authorization", &signed.authorization)
+                .body(body);
+            if let Some(token) = &key.session_token {
+                req = req.header("X-Amz-Security-Token", token);
+            }
+            let res = req.send().await.map_err(Error::NetworkError)?;
+            if !res.status().is_success() {
+                warn!("secrets manager - secret={}, res={:?}", self.secret_id, res);
+                bail!(Error::APIError(format!("http status: {}", res.status())))
+            }
+            let body: Value = res
+                .json()
+                .await
+                .context("parse secrets manager payload as json")?;
+            let secret: Value = serde_json::from_str(
+                body["SecretString"]
+                    .as_str()
+                    .context("secret has no SecretString")?,
+            )
+            .context("parse SecretString as json")?;
+            Ok(Credentials {
+                user: secret_field(&secret, &self.user_key)?,
+                password: secret_field(&secret, &self.password_key)?,
+            })
+        })
+    }
+}
+
+/// Signature Version 4 of the json requests to AWS, and the access keys to sign with
+#[cfg(feature = "aws-secrets")]
+mod aws {
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use anyhow::{bail, Context, Result};
+    use hmac::{Hmac, Mac};
+    use log::{trace, warn};
+    use serde::Deserialize;
+    use sha2::{Digest, Sha256};
+
+    use crate::{model::civil_date, Error};
+
+    pub(super) const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+    const SERVICE: &str = "secretsmanager";
+    const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+    /// the metadata service answers in milliseconds, don't hang off EC2
+    const IMDS_TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[derive(Debug, PartialEq)]
+    pub(super) struct AccessKey {
+        pub(super) id: String,
+        pub(super) secret: String,
+        pub(super) session_token: Option<String>,
+    }
+
+    /// Access key of the environment, the shared credentials file or the
+    /// instance role, in that order
+    pub(super) async fn access_key(
+        hc: &reqwest::Client,
+        profile: Option<&str>,
+    ) -> Result<AccessKey> {
+        if let (Ok(id), Ok(secret)) = (
+            std::env::var("AWS_ACCESS_KEY_ID"),
+            std::env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(AccessKey {
+                id,
+                secret,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+        let name = match profile {
+            Some(name) => name.to_owned(),
+            None => std::env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_owned()),
+        };
+        let path = std::env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws/credentials"))
+            });
+        if let Some(ini) = path.and_then(|path| std::fs::read_to_string(path).ok()) {
+            if let Some(key) = profile_key(&ini, &name) {
+                trace!("aws credentials from profile - profile={}", name);
+                return Ok(key);
+            }
+        }
+        if profile.is_some() {
+            bail!("aws profile {} not found", name)
+        }
+        if std::env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|v| v.eq_ignore_ascii_case("true"))
+        {
+            bail!("no aws credentials in the environment or profile {}", name)
+        }
+        let endpoint = std::env::var("AWS_EC2_METADATA_SERVICE_ENDPOINT")
+            .unwrap_or_else(|_| IMDS_ENDPOINT.to_owned());
+        instance_key(hc, endpoint.trim_end_matches('/'))
+            .await
+            .with_context(|| {
+                format!(
+                    "no aws credentials in the environment, profile {} or instance metadata",
+                    name
+                )
+            })
+    }
+
+    /// Keys of `[profile]` in a shared credentials file
+    pub(super) fn profile_key(ini: &str, profile: &str) -> Option<AccessKey> {
+        let mut section = None;
+        let mut keys = HashMap::new();
+        for line in ini.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim());
+            } else if section == Some(profile) {
+                if let Some((key, value)) = line.split_once('=') {
+                    keys.insert(key.trim(), value.trim());
+                }
+            }
+        }
+        Some(AccessKey {
+            id: keys.get("aws_access_key_id")?.to_string(),
+            secret: keys.get("aws_secret_access_key")?.to_string(),
+            session_token: keys.get("aws_session_token").map(|t| t.to_string()),
+        })
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct InstanceCredentials {
+        access_key_id: String,
+        secret_access_key: String,
+        token: String,
+    }
+
+    /// Temporary keys of the instance role, through IMDSv2
+    pub(super) async fn instance_key(hc: &reqwest::Client, endpoint: &str) -> Result<AccessKey> {
+        let res = hc
+            .put(format!("{}/latest/api/token", endpoint))
+            .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+            .timeout(IMDS_TIMEOUT)
+            .send()
+            .await
+            .map_err(Error::NetworkError)?;
+        let token = metadata_text(res).await?;
+        let get = |path: String| {
+            hc.get(format!(
+                "{}/latest/meta-data/iam/security-credentials/{}",
+                endpoint, path
+            ))
+            .header("X-aws-ec2-metadata-token", &token)
+            .timeout(IMDS_TIMEOUT)
+            .send()
+        };
+        let roles = metadata_text(get(String::new()).await.map_err(Error::NetworkError)?).await?;
+        let Some(role) = roles.lines().next() else {
+            bail!("instance has no iam role")
+        };
+        let res = get(role.to_owned()).await.map_err(Error::NetworkError)?;
+        let credentials: InstanceCredentials = serde_json::from_str(&metadata_text(res).await?)
+            .context("parse instance credentials as json")?;
+        trace!("aws credentials from instance role - role={}", role);
+        Ok(AccessKey {
+            id: credentials.access_key_id,
+            secret: credentials.secret_access_key,
+            session_token: Some(credentials.token),
+        })
+    }
+
+    async fn metadata_text(res: reqwest::Response) -> Result<String> {
+        if !res.status().is_success() {
+            warn!("instance metadata - res={:?}", res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.text().await.map_err(Error::NetworkError)?)
+    }
+
+    pub(super) struct Signed {
+        /// `X-Amz-Date` header, e.g. `20231114T221320Z`
+        pub(super) date_time: String,
+        pub(super) authorization: String,
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac takes keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// `YYYYMMDDTHHMMSSZ` in UTC
+    pub(super) fn amz_date_time(time: SystemTime) -> String {
+        let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+        let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+        let (year, month, day) = civil_date(days);
+        format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            year,
+            month,
+            day,
+            rem / 3600,
+            rem % 3600 / 60,
+            rem % 60
+        )
+    }
+    pub(super) fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+        let k_date = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+        let k_region = hmac_sha256(&k_date, region.as_bytes());
+        let k_service = hmac_sha256(&k_region, service.as_bytes());
+        hmac_sha256(&k_service, b"aws4_request")
+    }
+
+    pub(super) fn sign(
+        key: &AccessKey,
+        region: &str,
+        host: &str,
+        target: &str,
+        body: &str,
+        now: SystemTime,
+    ) -> Signed {
+        let date_time = amz_date_time(now);
+        let date = &date_time[..8];
+        // sorted by header name
+        let mut headers = vec![
+            ("content-type", CONTENT_TYPE),
+            ("host", host),
+            ("x-amz-date", &date_time),
+        ];
+        if let Some(token) = &key.session_token {
+            headers.push(("x-amz-security-token", token));
+        }
+        headers.push(("x-amz-target", target));
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            hex(&Sha256::digest(body))
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, region, SERVICE);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            date_time,
+            scope,
+            hex(&Sha256::digest(canonical_request))
+        );
+        let signature = hex(&hmac_sha256(
+            &signing_key(&key.secret, date, region, SERVICE),
+            string_to_sign.as_bytes(),
+        ));
+        Signed {
+            authorization: format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                key.id, scope, signed_headers, signature
+            ),
+            date_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sequence(std::sync::Mutex<Vec<&'static str>>);
+
+    impl SecretSource for Sequence {
+        fn fetch<'a>(&'a self, _: &'a reqwest::Client) -> BoxFuture<'a, Result<Credentials>> {
+            let token = self.0.lock().unwrap().remove(0);
+            Box::pin(async move { Ok(Credentials::new("bot", token)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_on_rejected_credentials() {
+        let source = Sequence(std::sync::Mutex::new(vec!["old", "old", "new"]));
+        let cached = CachedCredentials::load(source, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert!(!cached.refresh().await);
+        assert!(cached.refresh().await);
+        assert_eq!(cached.credentials().password, "new");
+    }
+
+    struct Slow(std::sync::atomic::AtomicUsize);
+
+    impl SecretSource for Slow {
+        fn fetch<'a>(&'a self, _: &'a reqwest::Client) -> BoxFuture<'a, Result<Credentials>> {
+            let n = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Credentials::new("bot", &format!("token-{}", n)))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_refresh_waits() {
+        let cached = CachedCredentials::load(Slow(0.into()), Duration::from_secs(3600))
+            .await
+            .unwrap();
+        // both callers saw token-0 rejected, one fetch serves both
+        let (a, b) = tokio::join!(cached.refresh(), cached.refresh());
+        assert!(a && b);
+        assert_eq!(cached.credentials().password, "token-1");
+        assert_eq!(
+            cached
+                .inner
+                .source
+                .0
+                .load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+    }
+
+    #[cfg(feature = "aws-secrets")]
+    #[test]
+    fn aws_signing_key() {
+        // example of the AWS Signature Version 4 documentation
+        let key = aws::signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(aws::amz_date_time(time), "20231114T221320Z");
+    }
+
+    #[cfg(feature = "aws-secrets")]
+    #[test]
+    fn aws_profile_key() {
+        let ini = "[default]\naws_access_key_id = AKIADEFAULT\naws_secret_access_key = s1\n\n\
+            # ci account\n[ci]\naws_access_key_id=AKIACI\naws_secret_access_key=s2\n\
+            aws_session_token=t2\n";
+        assert_eq!(
+            aws::profile_key(ini, "ci"),
+            Some(aws::AccessKey {
+                id: "AKIACI".to_owned(),
+                secret: "s2".to_owned(),
+                session_token: Some("t2".to_owned()),
+            })
+        );
+        assert_eq!(aws::profile_key(ini, "default").unwrap().id, "AKIADEFAULT");
+        assert_eq!(aws::profile_key(ini, "prod"), None);
+    }
+
+    #[cfg(feature = "aws-secrets")]
+    #[tokio::test]
+    async fn aws_instance_key() {
+        use crate::mock::{response, MockServer};
+        let server = MockServer::start(vec![
+            response("200 OK", &[], "imds-token"),
+            response("200 OK", &[], "jenkins-role"),
+            response(
+                "200 OK",
+                &[],
+                r#"{"Code":"Success","Type":"AWS-HMAC","AccessKeyId":"ASIAROLE",
+                    "SecretAccessKey":"s3","Token":"t3","Expiration":"2024-01-01T06:00:00Z"}"#,
+            ),
+        ])
+        .await;
+        let key = aws::instance_key(&reqwest::Client::new(), &server.url)
+            .await
+            .unwrap();
+        assert_eq!(key.id, "ASIAROLE");
+        assert_eq!(key.session_token.as_deref(), Some("t3"));
+        let requests = server.requests();
+        assert!(requests[0].starts_with("PUT /latest/api/token"));
+        assert!(
+            requests[2].starts_with("GET /latest/meta-data/iam/security-credentials/jenkins-role")
+        );
+        assert!(requests[2].contains("x-aws-ec2-metadata-token: imds-token"));
+    }
+}
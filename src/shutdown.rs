@@ -0,0 +1,121 @@
+//! Stopping the polls and streams of a client when the application exits
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use anyhow::{bail, Result};
+use log::info;
+use tokio::{sync::watch, time::sleep};
+
+use crate::{Error, Jenkins};
+
+/// Aborts waits of a [`Jenkins`] and all its clones, see [`Jenkins::shutdown_handle`]
+///
+/// Polling loops like [`Jenkins::wait_build`] return [`Error::Shutdown`] at
+/// their next wait once [`Shutdown::shutdown`] is called, instead of
+/// sleeping until the build finishes. Requests in flight and streamed
+/// downloads or logs are dropped, and later requests fail the same way.
+///
+/// ```no_run
+/// # async fn f(cli: jenkins_rs::Jenkins, handle: jenkins_rs::BuildHandle) -> anyhow::Result<()> {
+/// let shutdown = cli.shutdown_handle();
+/// let task = tokio::spawn(async move { cli.wait_build(&handle).await });
+/// // on SIGTERM
+/// shutdown.shutdown();
+/// assert!(task.await?.is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl Shutdown {
+    /// Abort current and future waits, can't be undone
+    pub fn shutdown(&self) {
+        if !self.tx.send_replace(true) {
+            info!("shutdown - waiters={}", self.tx.receiver_count());
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Completes once [`Shutdown::shutdown`] is called
+    pub async fn cancelled(&self) {
+        let mut rx = self.tx.subscribe();
+        // the sender lives in `self`, so the channel can't close
+        let _ = rx.wait_for(|down| *down).await;
+    }
+}
+
+impl Jenkins {
+    /// Handle to abort waits of this client and its clones
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Sleep between polls, failing with [`Error::Shutdown`] once shut down
+    pub(crate) async fn pause(&self, duration: Duration) -> Result<()> {
+        self.until_shutdown(sleep(duration)).await
+    }
+
+    /// Run `fut` unless the client is or gets shut down first, e.g. a request
+    /// or the read of the next chunk of a body
+    pub(crate) async fn until_shutdown<T>(&self, fut: impl Future<Output = T>) -> Result<T> {
+        tokio::select! {
+            biased;
+            _ = self.shutdown.cancelled() => bail!(Error::Shutdown),
+            out = fut => Ok(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_aborts_pause() {
+        let cli = Jenkins::new("http://localhost:8080", "user", "token");
+        let shutdown = cli.clone().shutdown_handle();
+        let pause = tokio::spawn(async move { cli.pause(Duration::from_secs(60)).await });
+        shutdown.shutdown();
+        let err = pause.await.unwrap().unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Error::Shutdown)));
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_requests_in_flight() {
+        use tokio::{io::AsyncReadExt, net::TcpListener};
+
+        // accepts and reads, never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let _ = stream.read_to_end(&mut buf).await;
+        });
+        let cli = Jenkins::new(&url, "user", "token");
+        let shutdown = cli.shutdown_handle();
+        let request = tokio::spawn(async move { cli.get_job_config("api").await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown.shutdown();
+        let err = tokio::time::timeout(Duration::from_secs(5), request)
+            .await
+            .expect("request aborted")
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Error::Shutdown)));
+    }
+}
@@ -0,0 +1,228 @@
+//! Live events of the [SSE Gateway](https://plugins.jenkins.io/sse-gateway/)
+//! plugin, enabled by the `sse` feature
+//!
+//! ```no_run
+//! # async fn f(cli: jenkins_rs::Jenkins) -> anyhow::Result<()> {
+//! let mut events = cli.subscribe_events(&["job", "queue"]).await?;
+//! while let Some(event) = events.next().await? {
+//!     let data: serde_json::Value = event.json()?;
+//!     println!("{} {} {}", event.channel, data["jenkins_event"], data["job_name"]);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use anyhow::{bail, Result};
+use log::{info, trace, warn};
+use reqwest::Response;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::{Error, Jenkins};
+
+/// One server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// `event` field, the Jenkins channel like `job` or `queue`
+    pub channel: String,
+    /// `data` field, json for Jenkins channels
+    pub data: String,
+}
+
+impl Event {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.data)?)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectRes {
+    data: ConnectData,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectData {
+    jsessionid: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OpenData {
+    dispatcher_id: String,
+}
+
+/// Events of the channels passed to [`Jenkins::subscribe_events`]
+pub struct EventStream {
+    jenkins: Jenkins,
+    res: Response,
+    buf: Vec<u8>,
+    channel: String,
+    data: Vec<String>,
+}
+
+impl EventStream {
+    /// Next event, `None` once Jenkins closes the stream
+    pub async fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(event) = self.line(line.trim_end_matches(['\r', '\n'])) {
+                    return Ok(Some(event));
+                }
+            }
+            match self
+                .jenkins
+                .until_shutdown(self.res.chunk())
+                .await?
+                .map_err(Error::NetworkError)?
+            {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Add a line to the pending event, completed by an empty line
+    fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            if self.data.is_empty() {
+                self.channel.clear();
+                return None;
+            }
+            let event = Event {
+                channel: match std::mem::take(&mut self.channel) {
+                    channel if channel.is_empty() => "message".to_owned(),
+                    channel => channel,
+                },
+                data: std::mem::take(&mut self.data).join("\n"),
+            };
+            trace!("sse event - channel={}", event.channel);
+            return Some(event);
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.channel = value.to_owned(),
+            "data" => self.data.push(value.to_owned()),
+            // comments, `id` and `retry`
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Jenkins {
+    /// Stream events of the SSE Gateway plugin, e.g. `job` for build starts
+    /// and ends or `queue` for queue changes
+    ///
+    /// Acknowledgements of the subscription on the `configure` channel are
+    /// passed through, like every other event.
+    pub async fn subscribe_events(&self, channels: &[&str]) -> Result<EventStream> {
+        let client_id = format!(
+            "jenkins-rs-{:x}",
+            RandomState::new().build_hasher().finish()
+        );
+        let url = format!("{}/sse-gateway/connect?clientId={}", self.url, client_id);
+        let connect: ConnectRes = self.get_json(&url).await?;
+        let session = connect.data.jsessionid;
+
+        let url = format!(
+            "{}/sse-gateway/listen/{};jsessionid={}",
+            self.url, client_id, session
+        );
+        let res = self
+            .send(
+                self.get(&url)
+                    .header(reqwest::header::ACCEPT, "text/event-stream"),
+            )
+            .await?;
+        if !res.status().is_success() {
+            warn!("sse listen - client_id={}, res={:?}", client_id, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let mut events = EventStream {
+            jenkins: self.clone(),
+            res,
+            buf: Vec::new(),
+            channel: String::new(),
+            data: Vec::new(),
+        };
+        // the first event names the dispatcher to subscribe
+        let open: OpenData = match events.next().await? {
+            Some(event) if event.channel == "open" => event.json()?,
+            event => bail!(Error::APIError(format!(
+                "sse: expected open event, got {:?}",
+                event
+            ))),
+        };
+
+        let url = format!(
+            "{}/sse-gateway/configure;jsessionid={}?batchId=1",
+            self.url, session
+        );
+        let subscribe: Vec<_> = channels
+            .iter()
+            .map(|channel| json!({ "jenkins_channel": channel }))
+            .collect();
+        let res = self
+            .send(self.post(&url).json(&json!({
+                "dispatcherId": open.dispatcher_id,
+                "subscribe": subscribe,
+                "unsubscribe": [],
+            })))
+            .await?;
+        if !res.status().is_success() {
+            warn!("sse configure - client_id={}, res={:?}", client_id, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        info!(
+            "sse subscribed - client_id={}, channels={:?}",
+            client_id, channels
+        );
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{response, MockServer};
+
+    #[tokio::test]
+    async fn subscribe_and_read_events() {
+        let stream = "event: open\ndata: {\"dispatcherId\":\"d1\"}\n\n\
+            : keep-alive\n\n\
+            event: job\nid: 7\ndata: {\"jenkins_event\":\"job_run_started\",\r\ndata: \"job_name\":\"api\"}\n\n";
+        let server = MockServer::start(vec![
+            response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                r#"{"status":"OK","data":{"jsessionid":"s1"}}"#,
+            ),
+            response("200 OK", &[("Content-Type", "text/event-stream")], stream),
+            response("200 OK", &[], r#"{"status":"OK"}"#),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        let mut events = cli.subscribe_events(&["job"]).await.unwrap();
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event.channel, "job");
+        let data: serde_json::Value = event.json().unwrap();
+        assert_eq!(data["job_name"], "api");
+        assert_eq!(events.next().await.unwrap(), None);
+
+        let requests = server.requests();
+        assert!(requests[1].starts_with("GET /sse-gateway/listen/jenkins-rs-"));
+        assert!(requests[1].contains(";jsessionid=s1 "));
+        assert!(requests[2].starts_with("POST /sse-gateway/configure;jsessionid=s1?batchId=1 "));
+        assert!(requests[2].contains(
+            r#"{"dispatcherId":"d1","subscribe":[{"jenkins_channel":"job"}],"unsubscribe":[]}"#
+        ));
+    }
+}
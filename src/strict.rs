@@ -0,0 +1,258 @@
+//! Validation pass for strict parsing, see [`JenkinsBuilder::strict`](crate::JenkinsBuilder::strict)
+//!
+//! Deserializes from a parsed `Value` and records the path of every field the
+//! model skipped, which is what `deny_unknown_fields` would reject. Only
+//! structs listed in [`FULLY_MODELLED`] are checked, the others model a
+//! subset of their response on purpose. `_class` is never reported.
+
+use std::cell::RefCell;
+
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
+
+/// Serde names of the structs modelling every field of the response they are
+/// read from, usually one narrowed by a `tree` filter
+pub(crate) const FULLY_MODELLED: &[&str] = &[
+    "Artifact",
+    "ComputerRes",
+    "ExecutorRes",
+    "ExecutorsRes",
+    "FolderRes",
+    "JobRes",
+    "QueueItem",
+    "QueueItemExecutable",
+    "QueueRes",
+    "QueueTask",
+];
+
+/// Deserialize `value`, returning the paths of fields unknown to `T`
+pub(crate) fn from_value_tracked<T: DeserializeOwned>(
+    value: &Value,
+) -> Result<(T, Vec<String>), serde_json::Error> {
+    let unknown = RefCell::new(Vec::new());
+    let t = T::deserialize(Tracked {
+        value,
+        path: "$".to_owned(),
+        record: false,
+        unknown: &unknown,
+    })?;
+    Ok((t, unknown.into_inner()))
+}
+
+struct Tracked<'a> {
+    value: &'a Value,
+    path: String,
+    /// a skipped value here is an unknown field of a fully modelled struct
+    record: bool,
+    unknown: &'a RefCell<Vec<String>>,
+}
+
+impl<'de> de::Deserializer<'de> for Tracked<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Object(map) => visitor.visit_map(TrackedMap {
+                iter: map.iter(),
+                next: None,
+                modelled: false,
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            Value::Array(items) => visitor.visit_seq(TrackedSeq {
+                iter: items.iter().enumerate(),
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Object(map) => visitor.visit_map(TrackedMap {
+                iter: map.iter(),
+                next: None,
+                modelled: FULLY_MODELLED.contains(&name),
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            value => value.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        // fields inside enum variants are not tracked
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.record {
+            self.unknown.borrow_mut().push(self.path);
+        }
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier
+    }
+}
+
+struct TrackedMap<'a> {
+    iter: serde_json::map::Iter<'a>,
+    next: Option<(&'a String, &'a Value)>,
+    /// map of a struct in [`FULLY_MODELLED`]
+    modelled: bool,
+    path: String,
+    unknown: &'a RefCell<Vec<String>>,
+}
+
+impl<'de> MapAccess<'de> for TrackedMap<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        self.next = self.iter.next();
+        match self.next {
+            Some((key, _)) => seed.deserialize(key.as_str().into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .next
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+        seed.deserialize(Tracked {
+            value,
+            path: format!("{}.{}", self.path, key),
+            record: self.modelled && key != "_class",
+            unknown: self.unknown,
+        })
+    }
+}
+
+struct TrackedSeq<'a> {
+    iter: std::iter::Enumerate<std::slice::Iter<'a, Value>>,
+    path: String,
+    unknown: &'a RefCell<Vec<String>>,
+}
+
+impl<'de> SeqAccess<'de> for TrackedSeq<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((i, value)) => seed
+                .deserialize(Tracked {
+                    value,
+                    path: format!("{}[{}]", self.path, i),
+                    record: false,
+                    unknown: self.unknown,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildRes;
+
+    #[test]
+    fn track_unknown_fields() {
+        let value: Value = serde_json::from_str(
+            r#"{"number":3,"url":"u","building":false,"result":null,"duration":1,"timestamp":2,
+                "keepLog":true,"artifacts":[{"_class":"hudson.model.Run$Artifact",
+                "fileName":"a","relativePath":"a","size":3}]}"#,
+        )
+        .unwrap();
+        let (build, unknown) = from_value_tracked::<BuildRes>(&value).unwrap();
+        assert_eq!(build.result, None);
+        // BuildRes models part of a build, artifacts all of theirs
+        assert_eq!(unknown, ["$.artifacts[0].size"]);
+    }
+
+    /// `/job/api/12/api/json` of a freestyle build on Jenkins 2.440
+    const BUILD_JSON: &str = r##"{"_class":"hudson.model.FreeStyleBuild","actions":[
+        {"_class":"hudson.model.CauseAction","causes":[{"_class":"hudson.model.Cause$UserIdCause",
+        "shortDescription":"Started by user admin","userId":"admin","userName":"admin"}]},{},
+        {"_class":"hudson.plugins.git.util.BuildData","buildsByBranchName":{"refs/remotes/origin/main":
+        {"_class":"hudson.plugins.git.util.Build","buildNumber":12,"buildResult":null,"marked":{
+        "SHA1":"5d1b0c6a9e3f","branch":[{"SHA1":"5d1b0c6a9e3f","name":"refs/remotes/origin/main"}]},
+        "revision":{"SHA1":"5d1b0c6a9e3f","branch":[{"SHA1":"5d1b0c6a9e3f","name":"refs/remotes/origin/main"}]}}},
+        "lastBuiltRevision":{"SHA1":"5d1b0c6a9e3f","branch":[{"SHA1":"5d1b0c6a9e3f",
+        "name":"refs/remotes/origin/main"}]},"remoteUrls":["https://git.example.com/api.git"],"scmName":""},
+        {"_class":"hudson.plugins.git.GitTagAction"},{},
+        {"_class":"org.jenkinsci.plugins.displayurlapi.actions.RunDisplayAction"}],
+        "artifacts":[{"displayPath":"api.jar","fileName":"api.jar","relativePath":"target/api.jar"}],
+        "building":false,"description":null,"displayName":"#12","duration":15234,
+        "estimatedDuration":14876,"executor":null,"fullDisplayName":"api #12","id":"12",
+        "keepLog":false,"number":12,"queueId":431,"result":"SUCCESS","timestamp":1704067200000,
+        "url":"http://ci.example.com/job/api/12/","builtOn":"linux-1","changeSet":{
+        "_class":"hudson.plugins.git.GitChangeSetList","items":[],"kind":"git"},"culprits":[]}"##;
+
+    /// `/queue/api/json` with the tree filter of `get_queue`
+    const QUEUE_JSON: &str = r#"{"_class":"hudson.model.Queue","items":[
+        {"_class":"hudson.model.Queue$BuildableItem","blocked":false,"buildable":true,"id":432,
+        "inQueueSince":1704067260000,"params":"\nTARGET=staging","stuck":false,
+        "task":{"_class":"hudson.model.FreeStyleProject","name":"api",
+        "url":"http://ci.example.com/job/api/"},"why":"Waiting for next available executor"}]}"#;
+
+    #[test]
+    fn captured_payloads_pass() {
+        let (build, unknown) =
+            from_value_tracked::<BuildRes>(&serde_json::from_str(BUILD_JSON).unwrap()).unwrap();
+        assert_eq!(build.number, 12);
+        assert!(unknown.is_empty(), "{:?}", unknown);
+
+        let (queue, unknown) = from_value_tracked::<crate::queue::QueueRes>(
+            &serde_json::from_str(QUEUE_JSON).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(queue.items[0].task.name, "api");
+        assert!(unknown.is_empty(), "{:?}", unknown);
+    }
+}
@@ -0,0 +1,176 @@
+//! W3C trace context propagation into triggered builds
+//!
+//! With the Jenkins OpenTelemetry plugin installed, builds triggered with a
+//! `traceparent` join the caller's trace. The crate doesn't depend on the
+//! OpenTelemetry SDK, the application hands over its current span through a
+//! [`TraceContextProvider`]:
+//!
+//! ```no_run
+//! use jenkins_rs::trace::{Propagation, TraceContext};
+//!
+//! // e.g. from `opentelemetry::Context::current().span().span_context()`
+//! fn current_span() -> Option<(u128, u64, bool)> {
+//!     None
+//! }
+//!
+//! let cli = jenkins_rs::Jenkins::builder("https://ci.example.com", "bot", "token")
+//!     .trace_context(
+//!         || current_span().map(|(trace, span, sampled)| TraceContext::new(trace, span, sampled)),
+//!         Propagation::Header,
+//!     )
+//!     .build();
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use reqwest::RequestBuilder;
+
+use crate::Jenkins;
+
+/// `traceparent` and `tracestate` of the span triggering a build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub traceparent: String,
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    pub fn new(trace_id: u128, span_id: u64, sampled: bool) -> TraceContext {
+        TraceContext {
+            traceparent: format!(
+                "00-{:032x}-{:016x}-{:02x}",
+                trace_id, span_id, sampled as u8
+            ),
+            tracestate: None,
+        }
+    }
+
+    /// Validate a `traceparent` received from elsewhere, e.g. an incoming request
+    pub fn parse(traceparent: &str) -> Result<TraceContext> {
+        let hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+        match parts[..] {
+            [version, trace, span, flags]
+                if hex(version, 2)
+                    && version != "ff"
+                    && hex(trace, 32)
+                    && trace.bytes().any(|b| b != b'0')
+                    && hex(span, 16)
+                    && span.bytes().any(|b| b != b'0')
+                    && hex(flags, 2) =>
+            {
+                Ok(TraceContext {
+                    traceparent: traceparent.trim().to_ascii_lowercase(),
+                    tracestate: None,
+                })
+            }
+            _ => bail!("trace: invalid traceparent `{}`", traceparent),
+        }
+    }
+
+    pub fn with_tracestate(mut self, tracestate: &str) -> Self {
+        self.tracestate = Some(tracestate.to_owned());
+        self
+    }
+}
+
+/// Source of the current [`TraceContext`], consulted per trigger
+pub trait TraceContextProvider: Send + Sync {
+    fn current(&self) -> Option<TraceContext>;
+}
+
+impl<F: Fn() -> Option<TraceContext> + Send + Sync> TraceContextProvider for F {
+    fn current(&self) -> Option<TraceContext> {
+        self()
+    }
+}
+
+/// How the context reaches the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Propagation {
+    /// `traceparent`/`tracestate` headers of the trigger request
+    Header,
+    /// string parameter of the job receiving the `traceparent`, e.g. for
+    /// pipelines passing it on themselves; only `buildWithParameters`
+    /// triggers carry it
+    Parameter(String),
+}
+
+#[derive(Clone)]
+pub(crate) struct TracePropagation {
+    pub(crate) provider: Arc<dyn TraceContextProvider>,
+    pub(crate) propagation: Propagation,
+}
+
+impl Jenkins {
+    /// Attach the current trace context to a build trigger request
+    pub(crate) fn inject_trace_context(&self, action: &str, req: RequestBuilder) -> RequestBuilder {
+        let Some(trace) = &self.trace else {
+            return req;
+        };
+        let Some(context) = trace.provider.current() else {
+            return req;
+        };
+        match &trace.propagation {
+            Propagation::Header => {
+                let req = req.header("traceparent", &context.traceparent);
+                match &context.tracestate {
+                    Some(state) => req.header("tracestate", state),
+                    None => req,
+                }
+            }
+            Propagation::Parameter(name) if action == "buildWithParameters" => {
+                req.query(&[(name.as_str(), context.traceparent.as_str())])
+            }
+            Propagation::Parameter(_) => req,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn propagate_trace_context() {
+        let context =
+            TraceContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, true);
+        assert_eq!(
+            context.traceparent,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(TraceContext::parse(&context.traceparent).unwrap(), context);
+        assert!(
+            TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_err()
+        );
+        assert!(TraceContext::parse("00-4bf92f35-00f067aa0ba902b7-01").is_err());
+
+        let traced = |propagation| {
+            let context = context.clone().with_tracestate("vendor=1");
+            Jenkins::builder("http://ci", "user", "token")
+                .trace_context(move || Some(context.clone()), propagation)
+                .build()
+        };
+        let cli = traced(Propagation::Header);
+        let req = cli
+            .inject_trace_context("build", cli.post("http://ci/job/a/build"))
+            .build()
+            .unwrap();
+        assert_eq!(req.headers()["traceparent"], context.traceparent.as_str());
+        assert_eq!(req.headers()["tracestate"], "vendor=1");
+
+        let cli = traced(Propagation::Parameter("TRACEPARENT".to_owned()));
+        let req = cli
+            .inject_trace_context(
+                "buildWithParameters",
+                cli.post("http://ci/job/a/buildWithParameters"),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(
+            req.url().query(),
+            Some("TRACEPARENT=00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        );
+    }
+}
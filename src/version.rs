@@ -0,0 +1,227 @@
+//! Jenkins server version and the behavior differences between releases
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Jenkins};
+
+/// Release of the controller, e.g. `2.426.3` for an LTS or `2.440` for a weekly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JenkinsVersion {
+    pub major: u32,
+    pub minor: u32,
+    /// LTS patch release, `0` for weeklies
+    pub patch: u32,
+}
+
+impl JenkinsVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> JenkinsVersion {
+        JenkinsVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Name of the crumb field when the crumb issuer doesn't report it
+    pub fn crumb_field(&self) -> &'static str {
+        if *self < JenkinsVersion::new(2, 0, 0) {
+            ".crumb"
+        } else {
+            "Jenkins-Crumb"
+        }
+    }
+
+    /// Requests authenticated with an api token need a crumb, exempt since 2.96
+    pub fn api_token_needs_crumb(&self) -> bool {
+        *self < JenkinsVersion::new(2, 96, 0)
+    }
+}
+
+impl fmt::Display for JenkinsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        } else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+impl FromStr for JenkinsVersion {
+    type Err = anyhow::Error;
+
+    /// Parse `2.426.3`, `2.440` or snapshots like `2.441-SNAPSHOT`
+    fn from_str(s: &str) -> Result<Self> {
+        let release = s.split(['-', ' ']).next().unwrap_or_default();
+        let parts: Vec<_> = release.split('.').map(str::parse::<u32>).collect();
+        match parts[..] {
+            [Ok(major), Ok(minor)] => Ok(JenkinsVersion::new(major, minor, 0)),
+            [Ok(major), Ok(minor), Ok(patch)] => Ok(JenkinsVersion::new(major, minor, patch)),
+            _ => bail!(Error::APIError(format!("invalid jenkins version: {}", s))),
+        }
+    }
+}
+
+/// Oldest release providing an API, checked by [`Jenkins::require_version`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinVersion {
+    pub api: &'static str,
+    pub version: JenkinsVersion,
+}
+
+impl MinVersion {
+    /// Jenkins CLI over plain HTTP, used by `Jenkins::cli`
+    pub const CLI_HTTP: MinVersion = MinVersion {
+        api: "CLI over HTTP",
+        version: JenkinsVersion::new(2, 54, 0),
+    };
+}
+
+/// CSRF protection token, sent as header `field: value` with `POST` requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Crumb {
+    #[serde(rename = "crumbRequestField")]
+    pub field: String,
+    #[serde(rename = "crumb")]
+    pub value: String,
+}
+
+impl Jenkins {
+    /// Version of the controller from the `X-Jenkins` header, fetched once per client
+    pub async fn server_version(&self) -> Result<JenkinsVersion> {
+        let version = self
+            .server_version
+            .get_or_try_init(|| async {
+                let url = format!("{}/api/json?tree=mode", self.url);
+                let res = self.send(self.get(&url)).await?;
+                if !res.status().is_success() {
+                    warn!("server version - res={:?}", res);
+                    bail!(Error::APIError(format!("http status: {}", res.status())))
+                }
+                let header = res
+                    .headers()
+                    .get("X-Jenkins")
+                    .context("missing X-Jenkins header")?
+                    .to_str()?;
+                let version: JenkinsVersion = header.parse()?;
+                info!("server version - version={}", version);
+                Ok(version)
+            })
+            .await?;
+        Ok(*version)
+    }
+
+    /// Fail with [`Error::UnsupportedVersion`] if the controller is older than `min`
+    pub async fn require_version(&self, min: MinVersion) -> Result<()> {
+        let actual = self.server_version().await?;
+        if actual < min.version {
+            bail!(Error::UnsupportedVersion {
+                api: min.api,
+                required: min.version.to_string(),
+                actual: actual.to_string(),
+            })
+        }
+        Ok(())
+    }
+
+    /// Get a crumb from the crumb issuer, `None` if CSRF protection is disabled
+    pub async fn get_crumb(&self) -> Result<Option<Crumb>> {
+        self.get_crumb_via(&self.hc).await
+    }
+
+    /// [`Jenkins::get_crumb`] through `hc`, the client of the rejected request
+    pub(crate) async fn get_crumb_via(&self, hc: &reqwest::Client) -> Result<Option<Crumb>> {
+        let url = format!("{}/crumbIssuer/api/json", self.url);
+        let res = self.send_via(hc, self.get(&url)).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let mut crumb: Crumb = self.json_body(res).await?;
+                if crumb.field.is_empty() {
+                    crumb.field = self.server_version().await?.crumb_field().to_owned();
+                }
+                Ok(Some(crumb))
+            }
+            status => bail!(Error::APIError(format!("http status: {}", status))),
+        }
+    }
+
+    /// Open a connection and fetch what the first trigger would wait for
+    ///
+    /// Resolves DNS, completes the TLS handshake into the connection pool,
+    /// caches the server version and, for releases that still require one
+    /// with an api token, the crumb, which is then sent with every `POST`.
+    /// Call it at startup of latency sensitive clients like chat-ops bots.
+    pub async fn warm_up(&self) -> Result<()> {
+        let start = std::time::Instant::now();
+        let version = self.server_version().await?;
+        if version.api_token_needs_crumb() {
+            let crumb = self.get_crumb().await?;
+            *self.crumb.write().unwrap_or_else(|e| e.into_inner()) = crumb;
+        }
+        let has_crumb = self
+            .crumb
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some();
+        info!(
+            "warm up - version={}, crumb={}, elapsed={:?}",
+            version,
+            has_crumb,
+            start.elapsed()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        let lts: JenkinsVersion = "2.426.3".parse().unwrap();
+        let weekly: JenkinsVersion = "2.440".parse().unwrap();
+        assert_eq!(lts, JenkinsVersion::new(2, 426, 3));
+        assert!(lts < weekly);
+        assert_eq!(
+            "2.441-SNAPSHOT".parse::<JenkinsVersion>().unwrap(),
+            JenkinsVersion::new(2, 441, 0)
+        );
+        assert_eq!(weekly.to_string(), "2.440");
+        assert!("jenkins".parse::<JenkinsVersion>().is_err());
+        assert!(!lts.api_token_needs_crumb());
+        assert_eq!(JenkinsVersion::new(1, 651, 3).crumb_field(), ".crumb");
+    }
+
+    #[tokio::test]
+    async fn warm_up_fetches_crumb_only_when_needed() {
+        use crate::mock::{response, MockServer};
+
+        let server =
+            MockServer::start(vec![response("200 OK", &[("X-Jenkins", "2.440")], "{}")]).await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        cli.warm_up().await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+        assert!(cli.crumb.read().unwrap().is_none());
+
+        let server = MockServer::start(vec![
+            response("200 OK", &[("X-Jenkins", "2.89.4")], "{}"),
+            response(
+                "200 OK",
+                &[("Content-Type", "application/json")],
+                r#"{"crumbRequestField":"Jenkins-Crumb","crumb":"c1"}"#,
+            ),
+        ])
+        .await;
+        let cli = Jenkins::new(&server.url, "user", "token");
+        cli.warm_up().await.unwrap();
+        assert!(server.requests()[1].starts_with("GET /crumbIssuer/api/json "));
+        assert_eq!(cli.crumb.read().unwrap().as_ref().unwrap().value, "c1");
+    }
+}
@@ -0,0 +1,165 @@
+//! Browsing workspaces on agents through the controller
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use log::{info, warn};
+
+use crate::{Error, Jenkins};
+
+/// File or directory of a workspace, see [`Jenkins::list_workspace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Parse the `*plain*` directory listing, directories end with `/`
+fn parse_listing(text: &str) -> Vec<WorkspaceEntry> {
+    text.lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| match l.strip_suffix('/') {
+            Some(dir) => WorkspaceEntry {
+                name: dir.to_owned(),
+                is_dir: true,
+            },
+            None => WorkspaceEntry {
+                name: l.to_owned(),
+                is_dir: false,
+            },
+        })
+        .collect()
+}
+
+impl Jenkins {
+    /// List a directory of the last workspace of a job
+    ///
+    /// Fails when the agent holding the workspace is offline.
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `path` - directory relative to the workspace, `""` for its root
+    ///
+    pub async fn list_workspace(&self, job: &str, path: &str) -> Result<Vec<WorkspaceEntry>> {
+        let url = format!("{}/job/{}/ws", self.url, job);
+        self.list_ws(&url, path).await
+    }
+
+    /// Download a file of the last workspace of a job
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `path` - file relative to the workspace
+    ///
+    pub async fn get_workspace_file(&self, job: &str, path: &str) -> Result<Bytes> {
+        let url = format!("{}/job/{}/ws", self.url, job);
+        self.get_ws_file(&url, path).await
+    }
+
+    /// Delete the workspace of a job on all nodes, e.g. before retrying a corrupted checkout
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    ///
+    pub async fn wipe_workspace(&self, job: &str) -> Result<()> {
+        self.post_manage(&format!("job/{}/doWipeOutWorkspace", job))
+            .await
+    }
+
+    /// List a directory of the workspace allocated by a pipeline `node` step
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `node_id` - flow node id of the `node` step, listed on the build's `flowGraphTable` page
+    /// * `path` - directory relative to the workspace, `""` for its root
+    ///
+    pub async fn list_pipeline_workspace(
+        &self,
+        job: &str,
+        number: i32,
+        node_id: &str,
+        path: &str,
+    ) -> Result<Vec<WorkspaceEntry>> {
+        let url = format!(
+            "{}/job/{}/{}/execution/node/{}/ws",
+            self.url, job, number, node_id
+        );
+        self.list_ws(&url, path).await
+    }
+
+    /// Download a file of the workspace allocated by a pipeline `node` step
+    ///
+    /// ## Arguments
+    ///
+    /// * `job` - job name
+    /// * `number` - build number
+    /// * `node_id` - flow node id of the `node` step
+    /// * `path` - file relative to the workspace
+    ///
+    pub async fn get_pipeline_workspace_file(
+        &self,
+        job: &str,
+        number: i32,
+        node_id: &str,
+        path: &str,
+    ) -> Result<Bytes> {
+        let url = format!(
+            "{}/job/{}/{}/execution/node/{}/ws",
+            self.url, job, number, node_id
+        );
+        self.get_ws_file(&url, path).await
+    }
+
+    async fn list_ws(&self, ws_url: &str, path: &str) -> Result<Vec<WorkspaceEntry>> {
+        let path = path.trim_matches('/');
+        let url = if path.is_empty() {
+            format!("{}/*plain*", ws_url)
+        } else {
+            format!("{}/{}/*plain*", ws_url, path)
+        };
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("list workspace - url={}, res={:?}", url, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        let entries = parse_listing(&res.text().await.map_err(Error::NetworkError)?);
+        info!("list workspace - url={}, entries={}", url, entries.len());
+        Ok(entries)
+    }
+
+    async fn get_ws_file(&self, ws_url: &str, path: &str) -> Result<Bytes> {
+        let url = format!("{}/{}", ws_url, path.trim_start_matches('/'));
+        let res = self.send(self.get(&url)).await?;
+        if !res.status().is_success() {
+            warn!("get workspace file - url={}, res={:?}", url, res);
+            bail!(Error::APIError(format!("http status: {}", res.status())))
+        }
+        Ok(res.bytes().await.map_err(Error::NetworkError)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_listing() {
+        assert_eq!(
+            parse_listing("target/\nCargo.toml\n\n"),
+            [
+                WorkspaceEntry {
+                    name: "target".to_owned(),
+                    is_dir: true
+                },
+                WorkspaceEntry {
+                    name: "Cargo.toml".to_owned(),
+                    is_dir: false
+                },
+            ]
+        );
+    }
+}
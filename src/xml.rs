@@ -0,0 +1,370 @@
+//! Minimal XML tree for reading and rewriting Jenkins `config.xml`
+//!
+//! Keeps every node (including comments and elements nobody asked for) so a
+//! document can be modified and written back without losing plugin data.
+
+use std::fmt::{self, Write};
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+/// Parsed document with its `<?xml ...?>` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub declaration: Option<String>,
+    pub root: Element,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element {
+            name: name.to_owned(),
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Element with a single text child, e.g. `<spec>H * * * *</spec>`
+    pub fn with_text(name: &str, text: &str) -> Element {
+        let mut e = Element::new(name);
+        e.set_text(text);
+        e
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        match self.attrs.iter_mut().find(|(k, _)| k == name) {
+            Some((_, v)) => *v = value.to_owned(),
+            None => self.attrs.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|n| match n {
+            Node::Element(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find_map(|n| match n {
+            Node::Element(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Get child element `name`, appending an empty one if missing
+    pub fn child_or_insert(&mut self, name: &str) -> &mut Element {
+        if self.child(name).is_none() {
+            self.children.push(Node::Element(Element::new(name)));
+        }
+        self.child_mut(name).expect("child just inserted")
+    }
+
+    /// Descend through nested children, e.g. `["properties", "triggers"]`
+    pub fn path(&self, names: &[&str]) -> Option<&Element> {
+        names.iter().try_fold(self, |e, name| e.child(name))
+    }
+
+    pub fn remove_children(&mut self, name: &str) {
+        self.children
+            .retain(|n| !matches!(n, Node::Element(e) if e.name == name));
+    }
+
+    pub fn push(&mut self, child: Element) {
+        self.children.push(Node::Element(child));
+    }
+
+    /// Concatenated text content of direct text children
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|n| match n {
+                Node::Text(t) => Some(t.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.children = vec![Node::Text(text.to_owned())];
+    }
+
+    /// Text of child element `name`
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name).map(|e| e.text())
+    }
+
+    /// Set text of child element `name`, appending it if missing
+    pub fn set_child_text(&mut self, name: &str, text: &str) {
+        self.child_or_insert(name).set_text(text);
+    }
+
+    fn write_to(&self, out: &mut String) -> fmt::Result {
+        write!(out, "<{}", self.name)?;
+        for (k, v) in &self.attrs {
+            write!(out, " {}=\"{}\"", k, escape(v, true))?;
+        }
+        if self.children.is_empty() {
+            return out.write_str("/>");
+        }
+        out.write_char('>')?;
+        for child in &self.children {
+            match child {
+                Node::Element(e) => e.write_to(out)?,
+                Node::Text(t) => out.write_str(&escape(t, false))?,
+                Node::Comment(c) => write!(out, "<!--{}-->", c)?,
+            }
+        }
+        write!(out, "</{}>", self.name)
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write_to(&mut out)?;
+        f.write_str(&out)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(decl) = &self.declaration {
+            writeln!(f, "<?{}?>", decl)?;
+        }
+        write!(f, "{}", self.root)
+    }
+}
+
+impl Document {
+    pub fn parse(s: &str) -> Result<Document> {
+        let mut p = Parser { s, pos: 0 };
+        let mut declaration = None;
+        loop {
+            p.skip_ws();
+            if p.eat("<?") {
+                let body = p.until("?>")?;
+                if body.starts_with("xml") {
+                    declaration = Some(body.to_owned());
+                }
+            } else if p.eat("<!--") {
+                p.until("-->")?;
+            } else if p.eat("<!DOCTYPE") {
+                p.until(">")?;
+            } else {
+                break;
+            }
+        }
+        let root = p.element()?;
+        Ok(Document { declaration, root })
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn until(&mut self, token: &str) -> Result<&'a str> {
+        match self.rest().find(token) {
+            Some(i) => {
+                let body = &self.rest()[..i];
+                self.pos += i + token.len();
+                Ok(body)
+            }
+            None => bail!("xml: missing `{}` at {}", token, self.pos),
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            bail!("xml: expected name at {}", self.pos)
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn element(&mut self) -> Result<Element> {
+        if !self.eat("<") {
+            bail!("xml: expected element at {}", self.pos)
+        }
+        let mut e = Element::new(self.name()?);
+        loop {
+            self.skip_ws();
+            if self.eat("/>") {
+                return Ok(e);
+            }
+            if self.eat(">") {
+                break;
+            }
+            let key = self.name()?;
+            self.skip_ws();
+            if !self.eat("=") {
+                bail!("xml: expected `=` at {}", self.pos)
+            }
+            self.skip_ws();
+            let quote = if self.eat("\"") {
+                "\""
+            } else if self.eat("'") {
+                "'"
+            } else {
+                bail!("xml: expected quote at {}", self.pos)
+            };
+            let value = unescape(self.until(quote)?)?;
+            e.attrs.push((key.to_owned(), value));
+        }
+        loop {
+            if self.eat("</") {
+                let name = self.name()?;
+                if name != e.name {
+                    bail!("xml: `</{}>` closes `<{}>` at {}", name, e.name, self.pos)
+                }
+                self.skip_ws();
+                if !self.eat(">") {
+                    bail!("xml: expected `>` at {}", self.pos)
+                }
+                return Ok(e);
+            } else if self.eat("<!--") {
+                e.children
+                    .push(Node::Comment(self.until("-->")?.to_owned()));
+            } else if self.eat("<![CDATA[") {
+                e.children.push(Node::Text(self.until("]]>")?.to_owned()));
+            } else if self.rest().starts_with('<') {
+                e.children.push(Node::Element(self.element()?));
+            } else if self.rest().is_empty() {
+                bail!("xml: unclosed `<{}>`", e.name)
+            } else {
+                let end = self.rest().find('<').unwrap_or(self.rest().len());
+                let text = unescape(&self.rest()[..end])?;
+                self.pos += end;
+                e.children.push(Node::Text(text));
+            }
+        }
+    }
+}
+
+fn escape(s: &str, attr: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attr => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub(crate) fn unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let Some(end) = rest.find(';') else {
+            bail!("xml: unterminated entity in `{}`", s)
+        };
+        let entity = &rest[..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                match code.and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => bail!("xml: unknown entity `&{};`", entity),
+                }
+            }
+        };
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_preserves_unknown_nodes() {
+        let src = "<?xml version='1.1' encoding='UTF-8'?>\n\
+            <project><!-- note --><description>a &amp; b</description>\
+            <com.example.Unknown plugin=\"x@1.0\"><flag>true</flag></com.example.Unknown>\
+            <empty/></project>";
+        let doc = Document::parse(src).unwrap();
+        assert_eq!(doc.root.child_text("description").unwrap(), "a & b");
+        assert_eq!(
+            doc.root
+                .child("com.example.Unknown")
+                .unwrap()
+                .attr("plugin"),
+            Some("x@1.0")
+        );
+        assert_eq!(doc.to_string(), src);
+    }
+
+    #[test]
+    fn rejects_mismatched_tags() {
+        assert!(Document::parse("<a><b></a></b>").is_err());
+    }
+}